@@ -0,0 +1,46 @@
+//! Decoding string escapes into one reused buffer against a fresh `String` per literal.
+//!
+//! Run with `cargo bench --bench escape`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use tlc::lex::{decode_escapes, decode_escapes_into, tokenize, LuaToken};
+
+fn escaped_source() -> String {
+    (0..10_000).map(|i| format!("local s{i} = \"col\\tumn {i}\\n\\\"quoted\\\" \\\\ \\z   end\"\n")).collect()
+}
+
+fn decode(c: &mut Criterion) {
+    let source = escaped_source();
+    let tokens = tokenize(&source).unwrap();
+    let strings: Vec<&str> = tokens
+        .iter()
+        .filter_map(|(token, _)| match token {
+            LuaToken::String(raw) => Some(*raw),
+            _ => None,
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("decode_escapes");
+    group.bench_function("fresh", |b| {
+        b.iter(|| {
+            for raw in &strings {
+                black_box(decode_escapes(raw).unwrap());
+            }
+        })
+    });
+    group.bench_function("reuse", |b| {
+        let mut scratch = Vec::new();
+        b.iter(|| {
+            for raw in &strings {
+                decode_escapes_into(raw, &mut scratch).unwrap();
+                black_box(&scratch);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+//! Lexer throughput over generated identifier-, number- and string-heavy inputs
+//! in three sizes, reported in tokens per second, next to the payload-free
+//! `count_tokens` scan.
+//!
+//! Run with `cargo bench --bench lex`. Add `--features full_moon` to lex the same
+//! inputs with `full_moon` as a baseline.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tlc::lex::{count_tokens, tokenize};
+
+const SIZES: [(&str, usize); 3] = [("small", 100), ("medium", 2_000), ("large", 20_000)];
+
+/// Builds line `i` of a generated input.
+type LineFn = fn(usize) -> String;
+
+fn identifier_line(i: usize) -> String {
+    format!("local alpha_{i} = beta_{i} .. gamma.delta_{i} or epsilon[zeta_{i}]\n")
+}
+
+fn number_line(i: usize) -> String {
+    format!("n = {i} + {i}.25 * 0x{}0 - 1e{} // 3 % 7\n", i % 10, i % 300)
+}
+
+fn string_line(i: usize) -> String {
+    format!("s = \"line {i}\\t\\\"quoted\\\"\" .. 'single {i}' .. [[long {i}]]\n")
+}
+
+fn lex(c: &mut Criterion) {
+    let inputs: [(&str, LineFn); 3] = [("identifiers", identifier_line), ("numbers", number_line), ("strings", string_line)];
+    for (name, line) in inputs {
+        let mut group = c.benchmark_group(format!("lex/{name}"));
+        for (size, lines) in SIZES {
+            let source: String = (0..lines).map(line).collect();
+            group.throughput(Throughput::Elements(tokenize(&source).unwrap().len() as u64));
+            group.bench_with_input(BenchmarkId::new("tlc", size), &source, |b, source| {
+                b.iter(|| tokenize(source).unwrap())
+            });
+            group.bench_with_input(BenchmarkId::new("count_tokens", size), &source, |b, source| {
+                b.iter(|| count_tokens(source))
+            });
+            #[cfg(feature = "full_moon")]
+            group.bench_with_input(BenchmarkId::new("full_moon", size), &source, |b, source| {
+                b.iter(|| full_moon::tokenizer::Lexer::new(source, full_moon::LuaVersion::new()).collect())
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, lex);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+//! Parser throughput over a large generated program and large table
+//! constructors, and the cost of dispatching expressions on whole peeked
+//! tokens against only their kinds.
+//!
+//! Run with `cargo bench --bench parse`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tlc::lex::tokenize;
+use tlc::parse::{parse, Parser};
+
+fn program(lines: usize) -> String {
+    (0..lines)
+        .map(|i| format!("local v{i} = f(a.b[{i}], \"s{i}\") .. t:m({i}.5) or x_{i} * 2 + #y\nif v{i} then z = {{v{i}, k = 1}} end\n"))
+        .collect()
+}
+
+fn parse_program(c: &mut Criterion) {
+    let source = program(10_000);
+    let tokens = tokenize(&source).unwrap();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(tokens.len() as u64));
+    group.bench_function("program", |b| b.iter(|| parse(&source).unwrap()));
+    // Parse pre-lexed tokens, so only the parser is timed, dispatching on kinds and then on whole tokens.
+    for (name, payload_peeks) in [("peek_kind", false), ("peek", true)] {
+        group.bench_function(name, |b| {
+            let setup = || Parser::from_tokens(tokens.clone(), &source).with_payload_peeks(payload_peeks);
+            b.iter_batched(setup, |mut parser| parser.chunk().unwrap(), BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+/// A flat table of `entries` fields, and one of `entries` fields each nested `depth` deep.
+fn tables(entries: usize, depth: usize) -> (String, String) {
+    let flat = (0..entries).map(|i| format!("{i}, k{i} = \"v\"")).collect::<Vec<_>>().join(", ");
+    let nested = format!("{}1{}", "{".repeat(depth), "}".repeat(depth));
+    let nested = vec![nested; entries / depth].join(", ");
+    (format!("return {{{flat}}}"), format!("return {{{nested}}}"))
+}
+
+fn parse_tables(c: &mut Criterion) {
+    let (flat, nested) = tables(50_000, 100);
+    let mut group = c.benchmark_group("parse_table");
+    group.throughput(Throughput::Bytes(flat.len() as u64));
+    group.bench_function("flat", |b| b.iter(|| parse(&flat).unwrap()));
+    group.throughput(Throughput::Bytes(nested.len() as u64));
+    group.bench_function("nested", |b| b.iter(|| parse(&nested).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, parse_program, parse_tables);
+criterion_main!(benches);
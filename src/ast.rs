@@ -0,0 +1,573 @@
+use crate::lex::{LuaToken, TokenKind};
+use crate::span::{Span, Spanned};
+
+pub mod build;
+mod diff;
+mod visit;
+
+pub use diff::{ast_diff, AstDiff};
+pub use visit::{
+    for_each_child_block, for_each_child_expr, for_each_child_expr_mut, for_each_expr_mut, for_each_function_body, for_each_stmt,
+    for_each_stmt_expr, for_each_subexpr,
+};
+
+/// Precedence shared by all unary operators, tighter than every binary operator but `^`.
+pub const UNARY_PRECEDENCE: u8 = 11;
+
+/// Precedence of the call, field and index suffixes, tighter than every operator.
+pub const SUFFIX_PRECEDENCE: u8 = 13;
+
+//==-----------
+// Statements
+//==-----------
+/// A sequence of statements, e.g. a chunk or a function body.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub stmts: Vec<Spanned<Stmt>>,
+    pub span: Span,
+}
+
+// Statements are held in vectors, not moved around, so boxing buys little.
+#[allow(clippy::large_enum_variant)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(tag = "type", content = "value"))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    /// `targets = values`, where every target is a name, field or index.
+    Assign {
+        targets: Vec<Spanned<Expr>>,
+        values: Vec<Spanned<Expr>>,
+    },
+    /// Luau's `target op= value`, e.g. `x += 1`, see
+    /// [`crate::transform::desugar_compound_assign`].
+    CompoundAssign {
+        target: Spanned<Expr>,
+        op: BinOp,
+        value: Spanned<Expr>,
+    },
+    /// A function or method call run for its effects.
+    Call(Spanned<Expr>),
+    #[cfg_attr(feature = "serde", serde(rename = "LocalDecl"))]
+    Local {
+        names: Vec<Spanned<String>>,
+        /// The Luau type annotation of each name, all `None` in standard Lua.
+        types: Vec<Option<Spanned<TypeExpr>>>,
+        /// The Lua 5.4 attribute of each name, all `None` in other dialects.
+        attribs: Vec<Option<Spanned<Attrib>>>,
+        values: Vec<Spanned<Expr>>,
+    },
+    Do(Block),
+    While {
+        cond: Spanned<Expr>,
+        body: Block,
+    },
+    /// `repeat body until cond`, where `cond` can see the body's locals.
+    Repeat {
+        body: Block,
+        cond: Spanned<Expr>,
+    },
+    If {
+        /// The `if` and each `elseif` condition with its block.
+        arms: Vec<(Spanned<Expr>, Block)>,
+        else_block: Option<Block>,
+    },
+    NumericFor {
+        var: Spanned<String>,
+        start: Spanned<Expr>,
+        end: Spanned<Expr>,
+        step: Option<Spanned<Expr>>,
+        body: Block,
+    },
+    GenericFor {
+        names: Vec<Spanned<String>>,
+        exprs: Vec<Spanned<Expr>>,
+        body: Block,
+    },
+    Break,
+    Goto(Spanned<String>),
+    Label(Spanned<String>),
+    Return(Vec<Spanned<Expr>>),
+    /// `function name(params) body end`; a method's `params` start with `self`.
+    Function {
+        name: FuncName,
+        params: Vec<Spanned<String>>,
+        is_vararg: bool,
+        types: FuncTypes,
+        body: Block,
+    },
+    /// `local function name(params) body end`, where unlike `local name =
+    /// function` the name is already in scope in `body`.
+    LocalFunction {
+        name: Spanned<String>,
+        params: Vec<Spanned<String>>,
+        is_vararg: bool,
+        types: FuncTypes,
+        body: Block,
+    },
+}
+
+/// Whether a numeric `for` counts with integers or floats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForKind {
+    /// Start and step are both integers, so the loop never wraps around.
+    Integer,
+    /// Start or step is a float, so the counter accumulates rounding error.
+    Float,
+}
+
+impl Stmt {
+    /// Lua 5.4's choice of counter for a numeric `for`, decided by the start and
+    /// step (1 if absent) alone. `None` for other statements, or if that needs a
+    /// value only known at run time.
+    pub fn for_kind(&self) -> Option<ForKind> {
+        let Stmt::NumericFor { start, step, .. } = self else { return None };
+        let kind = |expr: &Spanned<Expr>| match &expr.node {
+            Expr::Integer(..) => Some(ForKind::Integer),
+            Expr::Float(..) => Some(ForKind::Float),
+            Expr::Unary { op: UnOp::Neg, operand } => match operand.node {
+                Expr::Integer(..) => Some(ForKind::Integer),
+                Expr::Float(..) => Some(ForKind::Float),
+                _ => None,
+            },
+            _ => None,
+        };
+        let step = step.as_ref().map_or(Some(ForKind::Integer), kind);
+        match (kind(start), step) {
+            (Some(ForKind::Float), _) | (_, Some(ForKind::Float)) => Some(ForKind::Float),
+            (Some(ForKind::Integer), Some(ForKind::Integer)) => Some(ForKind::Integer),
+            _ => None,
+        }
+    }
+}
+
+/// A Luau type, kept as its source text since nothing here checks types.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeExpr(pub String);
+
+/// The Luau type annotations of a function, all `None` in standard Lua.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FuncTypes {
+    /// One per parameter, `None` where unannotated.
+    pub params: Vec<Option<Spanned<TypeExpr>>>,
+    /// The type of `...`, for `...: T`.
+    pub vararg: Option<Spanned<TypeExpr>>,
+    pub ret: Option<Spanned<TypeExpr>>,
+}
+
+/// The name of a function statement, `a.b.c:d` being path `[a, b, c]` and method `d`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncName {
+    pub path: Vec<String>,
+    pub method: Option<String>,
+}
+
+/// A Lua 5.4 local attribute, `local x <const>`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Attrib {
+    /// A local that can't be assigned to after its declaration.
+    Const,
+    /// A constant whose value's `__close` metamethod runs when it goes out of scope.
+    Close,
+}
+
+impl Attrib {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Attrib::Const => "const",
+            Attrib::Close => "close",
+        }
+    }
+}
+
+//==------------
+// Expressions
+//==------------
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(tag = "type", content = "value"))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Nil,
+    Bool(bool),
+    /// A number with its source spelling, e.g. `0xFF` for 255, or `None` if
+    /// not parsed from source.
+    Integer(i64, Option<String>),
+    Float(f64, Option<String>),
+    /// A string with its escapes decoded. Lua strings are bytes, so `'\255'`
+    /// needn't be UTF-8.
+    String(Vec<u8>),
+    /// Luau's `` `text {expr}` ``, the decoded text between holes alternating
+    /// with the expressions in them. Empty text is left out.
+    InterpolatedString {
+        parts: Vec<StringPart>,
+    },
+    /// `...`, the extra arguments of a vararg function.
+    Vararg,
+    Name(String),
+    Function {
+        params: Vec<Spanned<String>>,
+        is_vararg: bool,
+        types: FuncTypes,
+        body: Block,
+    },
+    Table(Vec<TableField>),
+    /// A parenthesised expression, kept since `(f())` truncates to one value.
+    Paren(Box<Spanned<Expr>>),
+    /// `obj.name`
+    Field {
+        obj: Box<Spanned<Expr>>,
+        name: Spanned<String>,
+    },
+    /// `obj[key]`
+    Index {
+        obj: Box<Spanned<Expr>>,
+        key: Box<Spanned<Expr>>,
+    },
+    Call {
+        func: Box<Spanned<Expr>>,
+        args: Vec<Spanned<Expr>>,
+    },
+    /// `obj:method(args)`, passing `obj` as the implicit `self`.
+    MethodCall {
+        obj: Box<Spanned<Expr>>,
+        method: Spanned<String>,
+        args: Vec<Spanned<Expr>>,
+    },
+    Binary {
+        op: BinOp,
+        lhs: Box<Spanned<Expr>>,
+        rhs: Box<Spanned<Expr>>,
+    },
+    /// `and`/`or`, kept apart from [`Expr::Binary`] as `rhs` only runs when
+    /// `lhs` doesn't decide the result. `op` is [`BinOp::And`] or [`BinOp::Or`].
+    Logical {
+        op: BinOp,
+        lhs: Box<Spanned<Expr>>,
+        rhs: Box<Spanned<Expr>>,
+    },
+    Unary {
+        op: UnOp,
+        operand: Box<Spanned<Expr>>,
+    },
+}
+
+impl Expr {
+    /// Whether this is a prefix expression, the only kind Lua lets you call,
+    /// index or assign through.
+    pub fn is_prefix(&self) -> bool {
+        matches!(
+            self,
+            Expr::Name(_) | Expr::Paren(_) | Expr::Field { .. } | Expr::Index { .. } | Expr::Call { .. } | Expr::MethodCall { .. }
+        )
+    }
+
+    /// Whether this can produce any number of values: a call or `...`.
+    /// Parenthesising one truncates it to a single value.
+    pub fn is_multi_valued(&self) -> bool {
+        matches!(self, Expr::Vararg | Expr::Call { .. } | Expr::MethodCall { .. })
+    }
+}
+
+/// How many values an expression in a list contributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjust {
+    /// Adjusted to exactly one value, the first or `nil`.
+    Single,
+    /// A multi-valued expression last in its list, expanded to all its values.
+    MultiTail,
+}
+
+/// The adjustment of each of `exprs`, a list of assignment or `local` values,
+/// return values or call arguments.
+pub fn adjustments(exprs: &[Spanned<Expr>]) -> Vec<Adjust> {
+    let last = exprs.len().saturating_sub(1);
+    let adjust = |(i, expr): (usize, &Spanned<Expr>)| {
+        if i == last && expr.node.is_multi_valued() { Adjust::MultiTail } else { Adjust::Single }
+    };
+    exprs.iter().enumerate().map(adjust).collect()
+}
+
+/// The adjustment of each field of a table constructor. Only a positional field
+/// last in the constructor can expand, filling the array part from its index on.
+pub fn field_adjustments(fields: &[TableField]) -> Vec<Adjust> {
+    let last = fields.len().saturating_sub(1);
+    let adjust = |(i, field): (usize, &TableField)| match field {
+        TableField::Positional(value) if i == last && value.node.is_multi_valued() => Adjust::MultiTail,
+        _ => Adjust::Single,
+    };
+    fields.iter().enumerate().map(adjust).collect()
+}
+
+/// The condition and branches of the `cond and a or b` idiom Lua uses in place
+/// of a conditional expression.
+///
+/// This isn't quite a conditional: when `a` is `nil` or `false` the result is
+/// `b` whatever `cond` is, so rewriting it as one is only safe if `a` is truthy.
+pub fn as_ternary(expr: &Spanned<Expr>) -> Option<(&Spanned<Expr>, &Spanned<Expr>, &Spanned<Expr>)> {
+    let Expr::Logical { op: BinOp::Or, lhs, rhs: else_ } = &expr.node else { return None };
+    let Expr::Logical { op: BinOp::And, lhs: cond, rhs: then } = &lhs.node else { return None };
+    Some((cond, then, else_))
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(tag = "type", content = "value"))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableField {
+    /// `value`, stored at the next array index.
+    Positional(Spanned<Expr>),
+    /// `name = value`
+    Named(Spanned<String>, Spanned<Expr>),
+    /// `[key] = value`
+    Keyed(Spanned<Expr>, Spanned<Expr>),
+}
+
+/// A piece of an [`Expr::InterpolatedString`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(tag = "type", content = "value"))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    /// Text with its escapes decoded, `\{` giving a literal `{`.
+    Literal(Vec<u8>),
+    /// The expression in a `{expr}` hole.
+    Expr(Spanned<Expr>),
+}
+
+//==----------
+// Operators
+//==----------
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Or,
+    And,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    NotEqual,
+    Equal,
+    BitOr,
+    BitXor,
+    BitAnd,
+    ShiftLeft,
+    ShiftRight,
+    Concat,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Mod,
+    Pow,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnOp {
+    Neg,
+    Not,
+    Len,
+    BitNot,
+}
+
+/// Which operand of a binary expression a child sits in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl BinOp {
+    pub fn from_token(token: &LuaToken) -> Option<BinOp> {
+        BinOp::from_kind(token.kind())
+    }
+
+    pub fn from_kind(kind: TokenKind) -> Option<BinOp> {
+        Some(match kind {
+            TokenKind::Or => BinOp::Or,
+            TokenKind::And => BinOp::And,
+            TokenKind::Less => BinOp::Less,
+            TokenKind::Greater => BinOp::Greater,
+            TokenKind::LessEqual => BinOp::LessEqual,
+            TokenKind::GreaterEqual => BinOp::GreaterEqual,
+            TokenKind::NotEqual => BinOp::NotEqual,
+            TokenKind::DoubleEqual => BinOp::Equal,
+            TokenKind::Pipe => BinOp::BitOr,
+            TokenKind::Tilde => BinOp::BitXor,
+            TokenKind::Ampersand => BinOp::BitAnd,
+            TokenKind::ShiftLeft => BinOp::ShiftLeft,
+            TokenKind::ShiftRight => BinOp::ShiftRight,
+            TokenKind::Concatenate => BinOp::Concat,
+            TokenKind::Plus => BinOp::Add,
+            TokenKind::Minus => BinOp::Sub,
+            TokenKind::Multiply => BinOp::Mul,
+            TokenKind::Divide => BinOp::Div,
+            TokenKind::FloorDivide => BinOp::FloorDiv,
+            TokenKind::Modulus => BinOp::Mod,
+            TokenKind::Exponent => BinOp::Pow,
+            _ => return None,
+        })
+    }
+
+    /// Lua 5.4 operator precedence, from `or` (1) up to `^` (12).
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::Less
+            | BinOp::Greater
+            | BinOp::LessEqual
+            | BinOp::GreaterEqual
+            | BinOp::NotEqual
+            | BinOp::Equal => 3,
+            BinOp::BitOr => 4,
+            BinOp::BitXor => 5,
+            BinOp::BitAnd => 6,
+            BinOp::ShiftLeft | BinOp::ShiftRight => 7,
+            BinOp::Concat => 8,
+            BinOp::Add | BinOp::Sub => 9,
+            BinOp::Mul | BinOp::Div | BinOp::FloorDiv | BinOp::Mod => 10,
+            BinOp::Pow => 12,
+        }
+    }
+
+    /// `..` and `^` group to the right, everything else to the left.
+    pub fn is_right_assoc(self) -> bool {
+        matches!(self, BinOp::Concat | BinOp::Pow)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinOp::Or => "or",
+            BinOp::And => "and",
+            BinOp::Less => "<",
+            BinOp::Greater => ">",
+            BinOp::LessEqual => "<=",
+            BinOp::GreaterEqual => ">=",
+            BinOp::NotEqual => "~=",
+            BinOp::Equal => "==",
+            BinOp::BitOr => "|",
+            BinOp::BitXor => "~",
+            BinOp::BitAnd => "&",
+            BinOp::ShiftLeft => "<<",
+            BinOp::ShiftRight => ">>",
+            BinOp::Concat => "..",
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::FloorDiv => "//",
+            BinOp::Mod => "%",
+            BinOp::Pow => "^",
+        }
+    }
+}
+
+impl UnOp {
+    pub fn from_token(token: &LuaToken) -> Option<UnOp> {
+        Some(match token.kind() {
+            TokenKind::Minus => UnOp::Neg,
+            TokenKind::Not => UnOp::Not,
+            TokenKind::Length => UnOp::Len,
+            TokenKind::Tilde => UnOp::BitNot,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnOp::Neg => "-",
+            UnOp::Not => "not",
+            UnOp::Len => "#",
+            UnOp::BitNot => "~",
+        }
+    }
+}
+
+/// Whether `child`, as the `side` operand of `parent`, must be parenthesised to
+/// keep the same tree when re-emitted.
+pub fn needs_parens(parent: BinOp, child: BinOp, side: Side) -> bool {
+    let (parent_prec, child_prec) = (parent.precedence(), child.precedence());
+    if child_prec != parent_prec {
+        return child_prec < parent_prec;
+    }
+    // Equal precedence: only the side the operator doesn't group towards needs them.
+    match side {
+        Side::Left => parent.is_right_assoc(),
+        Side::Right => !parent.is_right_assoc(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ast_needs_parens_precedence() {
+        assert!(!needs_parens(BinOp::Add, BinOp::Mul, Side::Right));
+        assert!(needs_parens(BinOp::Mul, BinOp::Add, Side::Left));
+        assert!(needs_parens(BinOp::And, BinOp::Or, Side::Right));
+    }
+    #[test]
+    fn ast_child_exprs() {
+        let expr = crate::parse::Parser::new("f(a, {k = b}, function() return c end)[d]").unwrap().expr().unwrap();
+        let mut spans = Vec::new();
+        for_each_child_expr(&expr, |child| spans.push(child.span));
+        assert_eq!(spans, [Span::new(0, 38), Span::new(39, 40)]);
+        let mut names = Vec::new();
+        for_each_subexpr(&expr, &mut |expr| {
+            if let Expr::Name(name) = &expr.node {
+                names.push(name.as_str());
+            }
+        });
+        assert_eq!(names, ["f", "a", "b", "d"]);
+    }
+    #[test]
+    fn ast_adjustments() {
+        let values = |source: &str| match crate::parse::parse(source).unwrap().stmts.remove(0).node {
+            Stmt::Assign { values, .. } | Stmt::Local { values, .. } => values,
+            Stmt::Return(values) => values,
+            stmt => panic!("{stmt:?}"),
+        };
+        assert_eq!(adjustments(&values("a, b = f()")), [Adjust::MultiTail]);
+        assert_eq!(adjustments(&values("a, b = f(), 1")), [Adjust::Single, Adjust::Single]);
+        assert_eq!(adjustments(&values("local a, b = (f())")), [Adjust::Single]);
+        assert_eq!(adjustments(&values("return 1, o:m(), ...")), [Adjust::Single, Adjust::Single, Adjust::MultiTail]);
+        let Expr::Table(fields) = &values("return {f(), x = 1, ...}")[0].node else { panic!() };
+        assert_eq!(field_adjustments(fields), [Adjust::Single, Adjust::Single, Adjust::MultiTail]);
+        let Expr::Table(fields) = &values("return {f(), x = f()}")[0].node else { panic!() };
+        assert_eq!(field_adjustments(fields), [Adjust::Single, Adjust::Single]);
+    }
+    #[test]
+    fn ast_as_ternary() {
+        let parse = |source: &str| crate::parse::Parser::new(source).unwrap().expr().unwrap();
+        let expr = parse("x and 1 or 2");
+        let (cond, then, else_) = as_ternary(&expr).unwrap();
+        assert_eq!(cond.node, Expr::Name("x".to_string()));
+        assert!(matches!(then.node, Expr::Integer(1, _)), "{then:?}");
+        assert!(matches!(else_.node, Expr::Integer(2, _)), "{else_:?}");
+        assert_eq!(as_ternary(&parse("a or b")), None);
+        assert_eq!(as_ternary(&parse("a and b")), None);
+        assert_eq!(as_ternary(&parse("a and (b or c)")), None);
+    }
+    #[test]
+    fn ast_for_kind() {
+        let for_kind = |source: &str| crate::parse::parse(source).unwrap().stmts[0].node.for_kind();
+        assert_eq!(for_kind("for i = 1, 10 do end"), Some(ForKind::Integer));
+        assert_eq!(for_kind("for i = 1.0, 10.0 do end"), Some(ForKind::Float));
+        // The limit doesn't count, but a float step does.
+        assert_eq!(for_kind("for i = 10, 1.5, -1 do end"), Some(ForKind::Integer));
+        assert_eq!(for_kind("for i = 1, 2, 0.5 do end"), Some(ForKind::Float));
+        assert_eq!(for_kind("for i = n, 10 do end"), None);
+        assert_eq!(for_kind("for i = n, 10, 0.1 do end"), Some(ForKind::Float));
+        assert_eq!(for_kind("x = 1"), None);
+    }
+    #[test]
+    fn ast_needs_parens_assoc() {
+        assert!(!needs_parens(BinOp::Sub, BinOp::Sub, Side::Left));
+        assert!(needs_parens(BinOp::Sub, BinOp::Sub, Side::Right));
+        assert!(!needs_parens(BinOp::Pow, BinOp::Pow, Side::Right));
+        assert!(needs_parens(BinOp::Pow, BinOp::Pow, Side::Left));
+        assert!(needs_parens(BinOp::Concat, BinOp::Concat, Side::Left));
+    }
+}
@@ -0,0 +1,237 @@
+//! Helpers for building an AST by hand, e.g. in a code generator, to then
+//! render with [`crate::print::print_block`].
+//!
+//! Every node gets an empty span at offset 0, as it has no source text.
+//! Operator precedence is left to the printer, so `binary(Mul, binary(Add, a, b), c)`
+//! prints as `(a + b) * c`.
+
+use super::{BinOp, Block, Expr, FuncName, FuncTypes, Stmt, TableField, UnOp};
+use crate::span::{Span, Spanned};
+
+fn spanned<T>(node: T) -> Spanned<T> {
+    Spanned::new(node, Span::default())
+}
+
+fn names(names: &[&str]) -> Vec<Spanned<String>> {
+    names.iter().map(|name| spanned(name.to_string())).collect()
+}
+
+/// No annotations, one `None` per parameter as the parser leaves them.
+fn untyped(params: &[Spanned<String>]) -> FuncTypes {
+    FuncTypes { params: vec![None; params.len()], ..FuncTypes::default() }
+}
+
+pub fn block(stmts: Vec<Spanned<Stmt>>) -> Block {
+    Block { stmts, span: Span::default() }
+}
+
+//==-----------
+// Statements
+//==-----------
+/// `local names = values`, leaving every name unannotated and without attributes.
+pub fn local_(names_: &[&str], values: Vec<Spanned<Expr>>) -> Spanned<Stmt> {
+    let types = vec![None; names_.len()];
+    let attribs = vec![None; names_.len()];
+    spanned(Stmt::Local { names: names(names_), types, attribs, values })
+}
+
+pub fn assign(targets: Vec<Spanned<Expr>>, values: Vec<Spanned<Expr>>) -> Spanned<Stmt> {
+    spanned(Stmt::Assign { targets, values })
+}
+
+/// Luau's `target op= value`.
+pub fn compound_assign(target: Spanned<Expr>, op: BinOp, value: Spanned<Expr>) -> Spanned<Stmt> {
+    spanned(Stmt::CompoundAssign { target, op, value })
+}
+
+/// A call statement, `call` being an [`Expr::Call`] or [`Expr::MethodCall`].
+pub fn call_stmt(call: Spanned<Expr>) -> Spanned<Stmt> {
+    spanned(Stmt::Call(call))
+}
+
+pub fn do_(body: Block) -> Spanned<Stmt> {
+    spanned(Stmt::Do(body))
+}
+
+pub fn while_(cond: Spanned<Expr>, body: Block) -> Spanned<Stmt> {
+    spanned(Stmt::While { cond, body })
+}
+
+pub fn repeat_(body: Block, cond: Spanned<Expr>) -> Spanned<Stmt> {
+    spanned(Stmt::Repeat { body, cond })
+}
+
+/// `if` with one arm per `if`/`elseif` condition.
+pub fn if_(arms: Vec<(Spanned<Expr>, Block)>, else_block: Option<Block>) -> Spanned<Stmt> {
+    spanned(Stmt::If { arms, else_block })
+}
+
+pub fn numeric_for(
+    var: &str,
+    start: Spanned<Expr>,
+    end: Spanned<Expr>,
+    step: Option<Spanned<Expr>>,
+    body: Block,
+) -> Spanned<Stmt> {
+    spanned(Stmt::NumericFor { var: spanned(var.to_string()), start, end, step, body })
+}
+
+pub fn generic_for(names_: &[&str], exprs: Vec<Spanned<Expr>>, body: Block) -> Spanned<Stmt> {
+    spanned(Stmt::GenericFor { names: names(names_), exprs, body })
+}
+
+pub fn break_() -> Spanned<Stmt> {
+    spanned(Stmt::Break)
+}
+
+pub fn return_(values: Vec<Spanned<Expr>>) -> Spanned<Stmt> {
+    spanned(Stmt::Return(values))
+}
+
+/// `function name(params) body end`, `name` being a path like `a.b:c`.
+/// A method gets `self` prepended to its parameters, as the parser does.
+pub fn function_stmt(name: &str, params: &[&str], is_vararg: bool, body: Block) -> Spanned<Stmt> {
+    let (path, method) = match name.split_once(':') {
+        Some((path, method)) => (path, Some(method.to_string())),
+        None => (name, None),
+    };
+    let mut params = self::names(params);
+    if method.is_some() {
+        params.insert(0, spanned("self".to_string()));
+    }
+    let name = FuncName { path: path.split('.').map(str::to_string).collect(), method };
+    let types = untyped(&params);
+    spanned(Stmt::Function { name, params, is_vararg, types, body })
+}
+
+/// `local function name(params) body end`.
+pub fn local_function(name: &str, params: &[&str], is_vararg: bool, body: Block) -> Spanned<Stmt> {
+    let params = self::names(params);
+    let types = untyped(&params);
+    spanned(Stmt::LocalFunction { name: spanned(name.to_string()), params, is_vararg, types, body })
+}
+
+//==------------
+// Expressions
+//==------------
+pub fn nil() -> Spanned<Expr> {
+    spanned(Expr::Nil)
+}
+
+pub fn boolean(value: bool) -> Spanned<Expr> {
+    spanned(Expr::Bool(value))
+}
+
+pub fn integer(value: i64) -> Spanned<Expr> {
+    spanned(Expr::Integer(value, None))
+}
+
+pub fn float(value: f64) -> Spanned<Expr> {
+    spanned(Expr::Float(value, None))
+}
+
+pub fn string(value: &str) -> Spanned<Expr> {
+    spanned(Expr::String(value.into()))
+}
+
+pub fn vararg() -> Spanned<Expr> {
+    spanned(Expr::Vararg)
+}
+
+pub fn name(name: &str) -> Spanned<Expr> {
+    spanned(Expr::Name(name.to_string()))
+}
+
+/// An anonymous `function(params) body end`.
+pub fn function(params: &[&str], is_vararg: bool, body: Block) -> Spanned<Expr> {
+    let params = names(params);
+    let types = untyped(&params);
+    spanned(Expr::Function { params, is_vararg, types, body })
+}
+
+pub fn table(fields: Vec<TableField>) -> Spanned<Expr> {
+    spanned(Expr::Table(fields))
+}
+
+/// Only needed to truncate a multi-valued expression, see [`Expr::Paren`].
+pub fn paren(expr: Spanned<Expr>) -> Spanned<Expr> {
+    spanned(Expr::Paren(Box::new(expr)))
+}
+
+pub fn field(obj: Spanned<Expr>, name: &str) -> Spanned<Expr> {
+    spanned(Expr::Field { obj: Box::new(obj), name: spanned(name.to_string()) })
+}
+
+pub fn index(obj: Spanned<Expr>, key: Spanned<Expr>) -> Spanned<Expr> {
+    spanned(Expr::Index { obj: Box::new(obj), key: Box::new(key) })
+}
+
+pub fn call(func: Spanned<Expr>, args: Vec<Spanned<Expr>>) -> Spanned<Expr> {
+    spanned(Expr::Call { func: Box::new(func), args })
+}
+
+pub fn method_call(obj: Spanned<Expr>, method: &str, args: Vec<Spanned<Expr>>) -> Spanned<Expr> {
+    spanned(Expr::MethodCall { obj: Box::new(obj), method: spanned(method.to_string()), args })
+}
+
+/// `lhs op rhs`, an [`Expr::Logical`] for `and`/`or` and [`Expr::Binary`] otherwise.
+pub fn binary(op: BinOp, lhs: Spanned<Expr>, rhs: Spanned<Expr>) -> Spanned<Expr> {
+    let (lhs, rhs) = (Box::new(lhs), Box::new(rhs));
+    match op {
+        BinOp::And | BinOp::Or => spanned(Expr::Logical { op, lhs, rhs }),
+        _ => spanned(Expr::Binary { op, lhs, rhs }),
+    }
+}
+
+pub fn unary(op: UnOp, operand: Spanned<Expr>) -> Spanned<Expr> {
+    spanned(Expr::Unary { op, operand: Box::new(operand) })
+}
+
+//==--------------
+// Table fields
+//==--------------
+pub fn positional(value: Spanned<Expr>) -> TableField {
+    TableField::Positional(value)
+}
+
+pub fn named(name: &str, value: Spanned<Expr>) -> TableField {
+    TableField::Named(spanned(name.to_string()), value)
+}
+
+pub fn keyed(key: Spanned<Expr>, value: Spanned<Expr>) -> TableField {
+    TableField::Keyed(key, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ast_diff;
+    use crate::print::print_block;
+
+    #[test]
+    fn build_print_reparse() {
+        let body = block(vec![
+            local_(&["t"], vec![table(vec![positional(name("x")), named("n", integer(1))])]),
+            if_(
+                vec![(binary(BinOp::Or, name("x"), boolean(false)), block(vec![call_stmt(call(name("print"), vec![string("hi")]))]))],
+                Some(block(vec![assign(vec![field(name("t"), "n")], vec![unary(UnOp::Neg, integer(2))])])),
+            ),
+            return_(vec![binary(BinOp::Add, name("x"), binary(BinOp::Mul, integer(2), index(name("t"), integer(1))))]),
+        ]);
+        let chunk = block(vec![function_stmt("m.f", &["x"], false, body)]);
+        let source = print_block(&chunk);
+        assert_eq!(
+            source,
+            "function m.f(x)\n    local t = {x, n = 1}\n    if x or false then\n        print(\"hi\")\n    else\n        t.n = -2\n    end\n    return x + 2 * t[1]\nend"
+        );
+        let reparsed = crate::parse::parse(&source).unwrap();
+        assert_eq!(ast_diff(&chunk, &reparsed), None);
+    }
+    #[test]
+    fn build_method() {
+        let chunk = block(vec![function_stmt("a.b:c", &["x"], true, block(vec![return_(vec![name("self")])]))]);
+        let source = print_block(&chunk);
+        assert_eq!(source, "function a.b:c(x, ...)\n    return self\nend");
+        assert_eq!(ast_diff(&chunk, &crate::parse::parse(&source).unwrap()), None);
+    }
+}
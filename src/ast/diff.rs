@@ -0,0 +1,352 @@
+use std::fmt::Debug;
+
+use super::{Attrib, BinOp, Block, Expr, FuncName, FuncTypes, Stmt, StringPart, TableField, TypeExpr, UnOp};
+use crate::span::Spanned;
+
+/// Describe the first structural difference between two AST nodes, or `None`
+/// if they match.
+///
+/// Spans are ignored, so a node re-parsed from printed source compares equal
+/// to the original. The result names the path to the differing node, e.g.
+/// `stmts[0].Local.values[1].Binary.rhs: Integer(1, None) != Integer(2, None)`.
+pub fn ast_diff<T: AstDiff>(a: &T, b: &T) -> Option<String> {
+    a.diff(b, "")
+}
+
+/// Structural comparison of AST nodes, see [`ast_diff`].
+pub trait AstDiff {
+    /// First difference between `self` and `other`, reported under `path`.
+    fn diff(&self, other: &Self, path: &str) -> Option<String>;
+}
+
+fn join(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{path}.{segment}")
+    }
+}
+
+fn leaf<T: PartialEq + Debug>(a: &T, b: &T, path: &str) -> Option<String> {
+    (a != b).then(|| format!("{}: {a:?} != {b:?}", if path.is_empty() { "<root>" } else { path }))
+}
+
+macro_rules! leaf_diff {
+    ($($ty:ty),*) => {
+        $(impl AstDiff for $ty {
+            fn diff(&self, other: &Self, path: &str) -> Option<String> {
+                leaf(self, other, path)
+            }
+        })*
+    };
+}
+
+leaf_diff!(String, i64, f64, bool, BinOp, UnOp, FuncName, TypeExpr, Attrib);
+
+impl<T: AstDiff> AstDiff for Spanned<T> {
+    fn diff(&self, other: &Self, path: &str) -> Option<String> {
+        self.node.diff(&other.node, path)
+    }
+}
+
+impl<T: AstDiff> AstDiff for Box<T> {
+    fn diff(&self, other: &Self, path: &str) -> Option<String> {
+        (**self).diff(other, path)
+    }
+}
+
+impl<T: AstDiff> AstDiff for Option<T> {
+    fn diff(&self, other: &Self, path: &str) -> Option<String> {
+        match (self, other) {
+            (Some(a), Some(b)) => a.diff(b, path),
+            (None, None) => None,
+            (a, _) => {
+                let (present, absent) = if a.is_some() { ("Some", "None") } else { ("None", "Some") };
+                Some(format!("{path}: {present} != {absent}"))
+            }
+        }
+    }
+}
+
+impl<T: AstDiff> AstDiff for Vec<T> {
+    fn diff(&self, other: &Self, path: &str) -> Option<String> {
+        for (i, (a, b)) in self.iter().zip(other).enumerate() {
+            if let Some(diff) = a.diff(b, &format!("{path}[{i}]")) {
+                return Some(diff);
+            }
+        }
+        (self.len() != other.len()).then(|| format!("{path}: length {} != {}", self.len(), other.len()))
+    }
+}
+
+impl AstDiff for Block {
+    fn diff(&self, other: &Self, path: &str) -> Option<String> {
+        self.stmts.diff(&other.stmts, &join(path, "stmts"))
+    }
+}
+
+impl AstDiff for FuncTypes {
+    fn diff(&self, other: &Self, path: &str) -> Option<String> {
+        self.params
+            .diff(&other.params, &join(path, "params"))
+            .or_else(|| self.vararg.diff(&other.vararg, &join(path, "vararg")))
+            .or_else(|| self.ret.diff(&other.ret, &join(path, "ret")))
+    }
+}
+
+impl AstDiff for Stmt {
+    fn diff(&self, other: &Self, path: &str) -> Option<String> {
+        let at = |field: &str| join(path, &format!("{}.{field}", stmt_name(self)));
+        match (self, other) {
+            (Stmt::Assign { targets: t1, values: v1 }, Stmt::Assign { targets: t2, values: v2 }) => {
+                t1.diff(t2, &at("targets")).or_else(|| v1.diff(v2, &at("values")))
+            }
+            (Stmt::Call(c1), Stmt::Call(c2)) => c1.diff(c2, &join(path, "Call")),
+            (
+                Stmt::Local { names: n1, types: t1, attribs: a1, values: v1 },
+                Stmt::Local { names: n2, types: t2, attribs: a2, values: v2 },
+            ) => n1
+                .diff(n2, &at("names"))
+                .or_else(|| t1.diff(t2, &at("types")))
+                .or_else(|| a1.diff(a2, &at("attribs")))
+                .or_else(|| v1.diff(v2, &at("values"))),
+            (Stmt::Do(b1), Stmt::Do(b2)) => b1.diff(b2, &join(path, "Do")),
+            (
+                Stmt::CompoundAssign { target: t1, op: o1, value: v1 },
+                Stmt::CompoundAssign { target: t2, op: o2, value: v2 },
+            ) => t1
+                .diff(t2, &at("target"))
+                .or_else(|| o1.diff(o2, &at("op")))
+                .or_else(|| v1.diff(v2, &at("value"))),
+            (Stmt::While { cond: c1, body: b1 }, Stmt::While { cond: c2, body: b2 })
+            | (Stmt::Repeat { body: b1, cond: c1 }, Stmt::Repeat { body: b2, cond: c2 }) => {
+                c1.diff(c2, &at("cond")).or_else(|| b1.diff(b2, &at("body")))
+            }
+            (Stmt::If { arms: a1, else_block: e1 }, Stmt::If { arms: a2, else_block: e2 }) => {
+                for (i, ((c1, b1), (c2, b2))) in a1.iter().zip(a2).enumerate() {
+                    let arm = at(&format!("arms[{i}]"));
+                    if let Some(diff) = c1.diff(c2, &join(&arm, "cond")).or_else(|| b1.diff(b2, &join(&arm, "body"))) {
+                        return Some(diff);
+                    }
+                }
+                if a1.len() != a2.len() {
+                    return Some(format!("{}: length {} != {}", at("arms"), a1.len(), a2.len()));
+                }
+                e1.diff(e2, &at("else_block"))
+            }
+            (
+                Stmt::NumericFor { var: v1, start: s1, end: e1, step: t1, body: b1 },
+                Stmt::NumericFor { var: v2, start: s2, end: e2, step: t2, body: b2 },
+            ) => v1
+                .diff(v2, &at("var"))
+                .or_else(|| s1.diff(s2, &at("start")))
+                .or_else(|| e1.diff(e2, &at("end")))
+                .or_else(|| t1.diff(t2, &at("step")))
+                .or_else(|| b1.diff(b2, &at("body"))),
+            (
+                Stmt::GenericFor { names: n1, exprs: x1, body: b1 },
+                Stmt::GenericFor { names: n2, exprs: x2, body: b2 },
+            ) => n1
+                .diff(n2, &at("names"))
+                .or_else(|| x1.diff(x2, &at("exprs")))
+                .or_else(|| b1.diff(b2, &at("body"))),
+            (Stmt::Break, Stmt::Break) => None,
+            (Stmt::Goto(l1), Stmt::Goto(l2)) => l1.diff(l2, &join(path, "Goto")),
+            (Stmt::Label(l1), Stmt::Label(l2)) => l1.diff(l2, &join(path, "Label")),
+            (Stmt::Return(v1), Stmt::Return(v2)) => v1.diff(v2, &join(path, "Return")),
+            (
+                Stmt::Function { name: n1, params: p1, is_vararg: v1, types: t1, body: b1 },
+                Stmt::Function { name: n2, params: p2, is_vararg: v2, types: t2, body: b2 },
+            ) => n1
+                .diff(n2, &at("name"))
+                .or_else(|| p1.diff(p2, &at("params")))
+                .or_else(|| v1.diff(v2, &at("is_vararg")))
+                .or_else(|| t1.diff(t2, &at("types")))
+                .or_else(|| b1.diff(b2, &at("body"))),
+            (
+                Stmt::LocalFunction { name: n1, params: p1, is_vararg: v1, types: t1, body: b1 },
+                Stmt::LocalFunction { name: n2, params: p2, is_vararg: v2, types: t2, body: b2 },
+            ) => n1
+                .diff(n2, &at("name"))
+                .or_else(|| p1.diff(p2, &at("params")))
+                .or_else(|| v1.diff(v2, &at("is_vararg")))
+                .or_else(|| t1.diff(t2, &at("types")))
+                .or_else(|| b1.diff(b2, &at("body"))),
+            _ => Some(format!("{path}: {} != {}", stmt_name(self), stmt_name(other))),
+        }
+    }
+}
+
+impl AstDiff for Expr {
+    fn diff(&self, other: &Self, path: &str) -> Option<String> {
+        let at = |field: &str| join(path, &format!("{}.{field}", expr_name(self)));
+        match (self, other) {
+            // Numbers compare by value, not by how they were spelled. Floats
+            // compare bit for bit, so a NaN matches an identical NaN.
+            (Expr::Integer(a, _), Expr::Integer(b, _)) => leaf(&Expr::Integer(*a, None), &Expr::Integer(*b, None), path),
+            (Expr::Float(a, _), Expr::Float(b, _)) if a.to_bits() == b.to_bits() => None,
+            (Expr::Float(a, _), Expr::Float(b, _)) => leaf(&Expr::Float(*a, None), &Expr::Float(*b, None), path),
+            (Expr::String(_), Expr::String(_))
+            | (Expr::Name(_), Expr::Name(_))
+            | (Expr::Bool(_), Expr::Bool(_)) => leaf(self, other, path),
+            (Expr::Nil, Expr::Nil) | (Expr::Vararg, Expr::Vararg) => None,
+            (
+                Expr::Function { params: p1, is_vararg: v1, types: t1, body: b1 },
+                Expr::Function { params: p2, is_vararg: v2, types: t2, body: b2 },
+            ) => p1
+                .diff(p2, &at("params"))
+                .or_else(|| v1.diff(v2, &at("is_vararg")))
+                .or_else(|| t1.diff(t2, &at("types")))
+                .or_else(|| b1.diff(b2, &at("body"))),
+            (Expr::InterpolatedString { parts: p1 }, Expr::InterpolatedString { parts: p2 }) => p1.diff(p2, &at("parts")),
+            (Expr::Table(f1), Expr::Table(f2)) => f1.diff(f2, &join(path, "Table")),
+            (Expr::Paren(e1), Expr::Paren(e2)) => e1.diff(e2, &join(path, "Paren")),
+            (Expr::Field { obj: o1, name: n1 }, Expr::Field { obj: o2, name: n2 }) => {
+                o1.diff(o2, &at("obj")).or_else(|| n1.diff(n2, &at("name")))
+            }
+            (Expr::Index { obj: o1, key: k1 }, Expr::Index { obj: o2, key: k2 }) => {
+                o1.diff(o2, &at("obj")).or_else(|| k1.diff(k2, &at("key")))
+            }
+            (Expr::Call { func: f1, args: a1 }, Expr::Call { func: f2, args: a2 }) => {
+                f1.diff(f2, &at("func")).or_else(|| a1.diff(a2, &at("args")))
+            }
+            (
+                Expr::MethodCall { obj: o1, method: m1, args: a1 },
+                Expr::MethodCall { obj: o2, method: m2, args: a2 },
+            ) => o1
+                .diff(o2, &at("obj"))
+                .or_else(|| m1.diff(m2, &at("method")))
+                .or_else(|| a1.diff(a2, &at("args"))),
+            (Expr::Binary { op: o1, lhs: l1, rhs: r1 }, Expr::Binary { op: o2, lhs: l2, rhs: r2 })
+            | (Expr::Logical { op: o1, lhs: l1, rhs: r1 }, Expr::Logical { op: o2, lhs: l2, rhs: r2 }) => o1
+                .diff(o2, &at("op"))
+                .or_else(|| l1.diff(l2, &at("lhs")))
+                .or_else(|| r1.diff(r2, &at("rhs"))),
+            (Expr::Unary { op: o1, operand: e1 }, Expr::Unary { op: o2, operand: e2 }) => {
+                o1.diff(o2, &at("op")).or_else(|| e1.diff(e2, &at("operand")))
+            }
+            _ => Some(format!("{path}: {} != {}", expr_name(self), expr_name(other))),
+        }
+    }
+}
+
+impl AstDiff for TableField {
+    fn diff(&self, other: &Self, path: &str) -> Option<String> {
+        match (self, other) {
+            (TableField::Positional(v1), TableField::Positional(v2)) => v1.diff(v2, path),
+            (TableField::Named(n1, v1), TableField::Named(n2, v2)) => {
+                n1.diff(n2, &join(path, "name")).or_else(|| v1.diff(v2, &join(path, "value")))
+            }
+            (TableField::Keyed(k1, v1), TableField::Keyed(k2, v2)) => {
+                k1.diff(k2, &join(path, "key")).or_else(|| v1.diff(v2, &join(path, "value")))
+            }
+            _ => Some(format!("{path}: {} != {}", field_name(self), field_name(other))),
+        }
+    }
+}
+
+impl AstDiff for StringPart {
+    fn diff(&self, other: &Self, path: &str) -> Option<String> {
+        match (self, other) {
+            (StringPart::Literal(t1), StringPart::Literal(t2)) => leaf(t1, t2, path),
+            (StringPart::Expr(e1), StringPart::Expr(e2)) => e1.diff(e2, path),
+            _ => Some(format!("{path}: {} != {}", part_name(self), part_name(other))),
+        }
+    }
+}
+
+fn stmt_name(stmt: &Stmt) -> &'static str {
+    match stmt {
+        Stmt::Assign { .. } => "Assign",
+        Stmt::CompoundAssign { .. } => "CompoundAssign",
+        Stmt::Call(_) => "Call",
+        Stmt::Local { .. } => "Local",
+        Stmt::Do(_) => "Do",
+        Stmt::While { .. } => "While",
+        Stmt::Repeat { .. } => "Repeat",
+        Stmt::If { .. } => "If",
+        Stmt::NumericFor { .. } => "NumericFor",
+        Stmt::GenericFor { .. } => "GenericFor",
+        Stmt::Break => "Break",
+        Stmt::Goto(_) => "Goto",
+        Stmt::Label(_) => "Label",
+        Stmt::Return(_) => "Return",
+        Stmt::Function { .. } => "Function",
+        Stmt::LocalFunction { .. } => "LocalFunction",
+    }
+}
+
+fn expr_name(expr: &Expr) -> &'static str {
+    match expr {
+        Expr::Nil => "Nil",
+        Expr::Bool(_) => "Bool",
+        Expr::Integer(..) => "Integer",
+        Expr::Float(..) => "Float",
+        Expr::String(_) => "String",
+        Expr::InterpolatedString { .. } => "InterpolatedString",
+        Expr::Vararg => "Vararg",
+        Expr::Name(_) => "Name",
+        Expr::Function { .. } => "Function",
+        Expr::Table(_) => "Table",
+        Expr::Paren(_) => "Paren",
+        Expr::Field { .. } => "Field",
+        Expr::Index { .. } => "Index",
+        Expr::Call { .. } => "Call",
+        Expr::MethodCall { .. } => "MethodCall",
+        Expr::Binary { .. } => "Binary",
+        Expr::Logical { .. } => "Logical",
+        Expr::Unary { .. } => "Unary",
+    }
+}
+
+fn field_name(field: &TableField) -> &'static str {
+    match field {
+        TableField::Positional(_) => "Positional",
+        TableField::Named(..) => "Named",
+        TableField::Keyed(..) => "Keyed",
+    }
+}
+
+fn part_name(part: &StringPart) -> &'static str {
+    match part {
+        StringPart::Literal(_) => "Literal",
+        StringPart::Expr(_) => "Expr",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn diff_names_literal_path() {
+        let a = parse("local x = 1\nwhile x do local y = x + 1 end").unwrap();
+        let b = parse("local x = 1\nwhile x do local y = x + 2 end").unwrap();
+        assert_eq!(ast_diff(&a, &b).unwrap(), "stmts[1].While.body.stmts[0].Local.values[0].Binary.rhs: Integer(1, None) != Integer(2, None)");
+    }
+    #[test]
+    fn diff_ignores_spans() {
+        let a = parse("local x = {1, y = 2}").unwrap();
+        let b = parse("local   x =\n{ 1,y=2 }").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(ast_diff(&a, &b), None);
+    }
+    #[test]
+    fn diff_variant_and_length() {
+        let a = parse("return 1, 2").unwrap();
+        let b = parse("return 1").unwrap();
+        assert_eq!(ast_diff(&a, &b).unwrap(), "stmts[0].Return: length 2 != 1");
+        let c = parse("do end").unwrap();
+        assert_eq!(ast_diff(&a, &c).unwrap(), "stmts[0]: Return != Do");
+    }
+    #[test]
+    fn diff_nan_literal() {
+        let mut a = parse("return 1.5").unwrap();
+        let Stmt::Return(values) = &mut a.stmts[0].node else { unreachable!() };
+        values[0].node = Expr::Float(f64::NAN, None);
+        assert_eq!(ast_diff(&a, &a.clone()), None);
+        let b = parse("return 1.5").unwrap();
+        assert_eq!(ast_diff(&a, &b).unwrap(), "stmts[0].Return[0]: Float(NaN, None) != Float(1.5, None)");
+    }
+}
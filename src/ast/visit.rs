@@ -0,0 +1,203 @@
+use super::{Block, Expr, Stmt, StringPart, TableField};
+use crate::span::Spanned;
+
+/// Call `f` on every expression in `block`, children before their parents,
+/// descending into nested blocks and function bodies.
+pub fn for_each_expr_mut(block: &mut Block, f: &mut impl FnMut(&mut Spanned<Expr>)) {
+    for stmt in &mut block.stmts {
+        match &mut stmt.node {
+            Stmt::Assign { targets, values } => {
+                targets.iter_mut().for_each(|expr| walk_expr(expr, f));
+                values.iter_mut().for_each(|expr| walk_expr(expr, f));
+            }
+            Stmt::CompoundAssign { target, value, .. } => {
+                walk_expr(target, f);
+                walk_expr(value, f);
+            }
+            Stmt::Call(call) => walk_expr(call, f),
+            Stmt::Local { values, .. } | Stmt::Return(values) => values.iter_mut().for_each(|expr| walk_expr(expr, f)),
+            Stmt::Do(body) => for_each_expr_mut(body, f),
+            Stmt::While { cond, body } | Stmt::Repeat { body, cond } => {
+                walk_expr(cond, f);
+                for_each_expr_mut(body, f);
+            }
+            Stmt::If { arms, else_block } => {
+                for (cond, body) in arms {
+                    walk_expr(cond, f);
+                    for_each_expr_mut(body, f);
+                }
+                if let Some(body) = else_block {
+                    for_each_expr_mut(body, f);
+                }
+            }
+            Stmt::NumericFor { start, end, step, body, .. } => {
+                walk_expr(start, f);
+                walk_expr(end, f);
+                if let Some(step) = step {
+                    walk_expr(step, f);
+                }
+                for_each_expr_mut(body, f);
+            }
+            Stmt::GenericFor { exprs, body, .. } => {
+                exprs.iter_mut().for_each(|expr| walk_expr(expr, f));
+                for_each_expr_mut(body, f);
+            }
+            Stmt::Function { body, .. } | Stmt::LocalFunction { body, .. } => for_each_expr_mut(body, f),
+            Stmt::Break | Stmt::Goto(_) | Stmt::Label(_) => {}
+        }
+    }
+}
+
+fn walk_expr(expr: &mut Spanned<Expr>, f: &mut impl FnMut(&mut Spanned<Expr>)) {
+    if let Expr::Function { body, .. } = &mut expr.node {
+        for_each_expr_mut(body, f);
+    }
+    for_each_child_expr_mut(expr, |child| walk_expr(child, f));
+    f(expr);
+}
+
+/// Call `f` on every statement in `block`, parents before their children,
+/// descending into nested blocks and function bodies.
+pub fn for_each_stmt<'ast>(block: &'ast Block, f: &mut impl FnMut(&'ast Spanned<Stmt>)) {
+    for stmt in &block.stmts {
+        f(stmt);
+        let mut nested = |body: &'ast Block| for_each_stmt(body, f);
+        for_each_stmt_expr(&stmt.node, |expr| for_each_function_body(expr, &mut nested));
+        for_each_child_block(&stmt.node, nested);
+    }
+}
+
+/// Call `f` on each expression directly in `stmt`, not those in its blocks.
+pub fn for_each_stmt_expr<'ast>(stmt: &'ast Stmt, mut f: impl FnMut(&'ast Spanned<Expr>)) {
+    match stmt {
+        Stmt::Assign { targets, values } => targets.iter().chain(values).for_each(f),
+        Stmt::CompoundAssign { target, value, .. } => [target, value].into_iter().for_each(f),
+        Stmt::Call(expr) | Stmt::While { cond: expr, .. } | Stmt::Repeat { cond: expr, .. } => f(expr),
+        Stmt::Local { values, .. } | Stmt::Return(values) => values.iter().for_each(f),
+        Stmt::If { arms, .. } => arms.iter().for_each(|(cond, _)| f(cond)),
+        Stmt::NumericFor { start, end, step, .. } => [start, end].into_iter().chain(step).for_each(f),
+        Stmt::GenericFor { exprs, .. } => exprs.iter().for_each(f),
+        Stmt::Do(_) | Stmt::Function { .. } | Stmt::LocalFunction { .. } | Stmt::Break | Stmt::Goto(_) | Stmt::Label(_) => {}
+    }
+}
+
+/// Call `f` on each block directly in `stmt`, a function statement's body included.
+pub fn for_each_child_block<'ast>(stmt: &'ast Stmt, mut f: impl FnMut(&'ast Block)) {
+    match stmt {
+        Stmt::Do(body)
+        | Stmt::While { body, .. }
+        | Stmt::Repeat { body, .. }
+        | Stmt::NumericFor { body, .. }
+        | Stmt::GenericFor { body, .. }
+        | Stmt::Function { body, .. }
+        | Stmt::LocalFunction { body, .. } => f(body),
+        Stmt::If { arms, else_block } => arms.iter().map(|(_, body)| body).chain(else_block).for_each(f),
+        Stmt::Assign { .. }
+        | Stmt::CompoundAssign { .. }
+        | Stmt::Call(_)
+        | Stmt::Local { .. }
+        | Stmt::Break
+        | Stmt::Goto(_)
+        | Stmt::Label(_)
+        | Stmt::Return(_) => {}
+    }
+}
+
+/// Call `f` on the body of every function expression in `expr`, but not on
+/// those nested within such a body.
+pub fn for_each_function_body<'ast>(expr: &'ast Spanned<Expr>, f: &mut impl FnMut(&'ast Block)) {
+    match &expr.node {
+        Expr::Function { body, .. } => f(body),
+        _ => for_each_child_expr(expr, |child| for_each_function_body(child, f)),
+    }
+}
+
+/// Call `f` on `expr` and every expression within it, parents before their
+/// children, but not on those in function bodies.
+pub fn for_each_subexpr<'ast>(expr: &'ast Spanned<Expr>, f: &mut impl FnMut(&'ast Spanned<Expr>)) {
+    f(expr);
+    for_each_child_expr(expr, |child| for_each_subexpr(child, f));
+}
+
+/// Call `f` on each expression directly in `expr`, in source order, but not on
+/// their children or anything in a function body.
+pub fn for_each_child_expr<'ast>(expr: &'ast Spanned<Expr>, mut f: impl FnMut(&'ast Spanned<Expr>)) {
+    match &expr.node {
+        Expr::Table(fields) => {
+            for field in fields {
+                match field {
+                    TableField::Positional(value) | TableField::Named(_, value) => f(value),
+                    TableField::Keyed(key, value) => {
+                        f(key);
+                        f(value);
+                    }
+                }
+            }
+        }
+        Expr::Paren(inner) | Expr::Field { obj: inner, .. } | Expr::Unary { operand: inner, .. } => f(inner),
+        Expr::Index { obj: lhs, key: rhs } | Expr::Binary { lhs, rhs, .. } | Expr::Logical { lhs, rhs, .. } => {
+            f(lhs);
+            f(rhs);
+        }
+        Expr::Call { func: obj, args } | Expr::MethodCall { obj, args, .. } => {
+            f(obj);
+            args.iter().for_each(f);
+        }
+        Expr::InterpolatedString { parts } => {
+            for part in parts {
+                if let StringPart::Expr(value) = part {
+                    f(value);
+                }
+            }
+        }
+        Expr::Function { .. }
+        | Expr::Nil
+        | Expr::Bool(_)
+        | Expr::Integer(..)
+        | Expr::Float(..)
+        | Expr::String(_)
+        | Expr::Vararg
+        | Expr::Name(_) => {}
+    }
+}
+
+/// Like [`for_each_child_expr`], but able to change the children.
+pub fn for_each_child_expr_mut(expr: &mut Spanned<Expr>, mut f: impl FnMut(&mut Spanned<Expr>)) {
+    match &mut expr.node {
+        Expr::Table(fields) => {
+            for field in fields {
+                match field {
+                    TableField::Positional(value) | TableField::Named(_, value) => f(value),
+                    TableField::Keyed(key, value) => {
+                        f(key);
+                        f(value);
+                    }
+                }
+            }
+        }
+        Expr::Paren(inner) | Expr::Field { obj: inner, .. } | Expr::Unary { operand: inner, .. } => f(inner),
+        Expr::Index { obj: lhs, key: rhs } | Expr::Binary { lhs, rhs, .. } | Expr::Logical { lhs, rhs, .. } => {
+            f(lhs);
+            f(rhs);
+        }
+        Expr::Call { func: obj, args } | Expr::MethodCall { obj, args, .. } => {
+            f(obj);
+            args.iter_mut().for_each(f);
+        }
+        Expr::InterpolatedString { parts } => {
+            for part in parts {
+                if let StringPart::Expr(value) = part {
+                    f(value);
+                }
+            }
+        }
+        Expr::Function { .. }
+        | Expr::Nil
+        | Expr::Bool(_)
+        | Expr::Integer(..)
+        | Expr::Float(..)
+        | Expr::String(_)
+        | Expr::Vararg
+        | Expr::Name(_) => {}
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+
+use crate::lex::{lexer_with_comments, unexpected_char, LexError, LuaToken};
+use crate::span::Span;
+
+/// Source text between tokens that the AST drops.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trivia<'source> {
+    Whitespace(&'source str),
+    Comment(&'source str),
+    /// A `#!` line, as at the top of a script.
+    Shebang(&'source str),
+}
+
+impl Trivia<'_> {
+    pub fn text(&self) -> &str {
+        match self {
+            Trivia::Whitespace(text) | Trivia::Comment(text) | Trivia::Shebang(text) => text,
+        }
+    }
+}
+
+/// A token with the trivia in front of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstToken<'source> {
+    pub leading: Vec<Trivia<'source>>,
+    pub token: LuaToken<'source>,
+    /// The token exactly as written.
+    pub text: &'source str,
+    pub span: Span,
+}
+
+/// A lossless view of a source file: every token with its surrounding trivia,
+/// so formatting the tree gives back the input byte for byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cst<'source> {
+    pub tokens: Vec<CstToken<'source>>,
+    /// Trivia after the last token.
+    pub trailing: Vec<Trivia<'source>>,
+}
+
+impl<'source> Cst<'source> {
+    /// Build the tree for `source`, failing on the first lex error.
+    pub fn parse(source: &'source str) -> Result<Self, LexError> {
+        let mut tokens = Vec::new();
+        let mut trivia = Vec::new();
+        let mut last = 0;
+        for (token, range) in lexer_with_comments(source).spanned() {
+            let token = token.map_err(|err| match err {
+                LexError::UnexpectedChar { .. } => unexpected_char(source, range.start),
+                err => err,
+            })?;
+            push_gap(&mut trivia, &source[last..range.start]);
+            last = range.end;
+            let text = &source[range.clone()];
+            if let LuaToken::Comment(_) = token {
+                trivia.push(Trivia::Comment(text));
+                continue;
+            }
+            let leading = std::mem::take(&mut trivia);
+            tokens.push(CstToken { leading, token, text, span: range.into() });
+        }
+        push_gap(&mut trivia, &source[last..]);
+        Ok(Cst { tokens, trailing: trivia })
+    }
+}
+
+/// Record the skipped text between two tokens, whitespace apart from any `#!` line.
+fn push_gap<'source>(trivia: &mut Vec<Trivia<'source>>, mut gap: &'source str) {
+    while !gap.is_empty() {
+        let (piece, rest) = match gap.find("#!") {
+            Some(0) => {
+                let end = gap.find('\n').unwrap_or(gap.len());
+                (Trivia::Shebang(&gap[..end]), &gap[end..])
+            }
+            Some(start) => (Trivia::Whitespace(&gap[..start]), &gap[start..]),
+            None => (Trivia::Whitespace(gap), ""),
+        };
+        trivia.push(piece);
+        gap = rest;
+    }
+}
+
+impl fmt::Display for Cst<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.tokens {
+            for trivia in &token.leading {
+                f.write_str(trivia.text())?;
+            }
+            f.write_str(token.text)?;
+        }
+        for trivia in &self.trailing {
+            f.write_str(trivia.text())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cst_round_trip() {
+        let source = "#!/usr/bin/env lua\n  local   x=1--[==[ long\n\tcomment ]==]+  2 -- tail\n\n\
+                      print( [[raw\r\n]] ,'q\\'' )--[[ ]]\t\n-- last\n";
+        let cst = Cst::parse(source).unwrap();
+        assert_eq!(cst.to_string(), source);
+        assert_eq!(cst.tokens[0].leading, [Trivia::Shebang("#!/usr/bin/env lua"), Trivia::Whitespace("\n  ")]);
+        assert_eq!(cst.tokens[4].leading, [Trivia::Comment("--[==[ long\n\tcomment ]==]")]);
+        assert_eq!(cst.trailing, [Trivia::Comment("--[[ ]]"), Trivia::Whitespace("\t\n"), Trivia::Comment("-- last"), Trivia::Whitespace("\n")]);
+        assert_eq!(cst.tokens.len(), 12);
+    }
+    #[test]
+    fn cst_crlf_file() {
+        let source = "local x = 1\r\nreturn x\r\n";
+        let cst = Cst::parse(source).unwrap();
+        assert_eq!(cst.to_string(), source);
+        assert_eq!(cst.tokens[4].leading, [Trivia::Whitespace("\r\n")]);
+    }
+    #[test]
+    fn cst_lex_error() {
+        assert_eq!(Cst::parse("x = $").unwrap_err(), LexError::UnexpectedChar { ch: '$', span: Span::new(4, 5) });
+    }
+}
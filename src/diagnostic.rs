@@ -0,0 +1,82 @@
+use crate::lex::LexError;
+use crate::parse::ParseError;
+use crate::span::Span;
+
+/// A problem found in the source, from any stage of the pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    /// A stable name for the kind of problem, e.g. `malformed-number`, for
+    /// tools to filter on. `None` where only the message says what went wrong.
+    pub code: Option<&'static str>,
+}
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Severity {
+    /// The source is invalid.
+    #[default]
+    Error,
+    /// The source is valid but likely a mistake.
+    Warning,
+}
+
+impl Diagnostic {
+    /// An error with no code.
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic { severity: Severity::Error, message: message.into(), span, code: None }
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(err: LexError) -> Self {
+        let code = match err {
+            LexError::UnexpectedChar { .. } => "unexpected-char",
+            LexError::InvalidNumber { .. } => "malformed-number",
+            LexError::UnterminatedComment { .. } => "unfinished-comment",
+            LexError::UnterminatedString { .. } => "unfinished-string",
+            LexError::TokenTooLong { .. } => "token-too-long",
+            LexError::NotInDialect { .. } => "not-in-dialect",
+        };
+        Diagnostic::new(err.to_string(), err.span()).with_code(code)
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(err: ParseError) -> Self {
+        let diagnostic = Diagnostic::new(err.message, err.span);
+        match err.dialect {
+            Some(_) => diagnostic.with_code("not-in-dialect"),
+            None => diagnostic,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::{tokenize, Dialect};
+
+    #[test]
+    fn diagnostic_from_errors() {
+        let lex: Diagnostic = tokenize("x = 1.2.3").unwrap_err().into();
+        assert_eq!(lex, Diagnostic::new("malformed number", Span::new(4, 9)).with_code("malformed-number"));
+        let parse: Diagnostic = ParseError::new("expected 'end'", Span::new(3, 3)).into();
+        assert_eq!((parse.severity, parse.code), (Severity::Error, None));
+        assert_eq!((parse.message.as_str(), parse.span), ("expected 'end'", Span::new(3, 3)));
+        let parse: Diagnostic = ParseError::new("type annotations need the Luau dialect", Span::new(7, 8)).in_dialect(Dialect::Lua54).into();
+        assert_eq!(parse.code, Some("not-in-dialect"));
+    }
+}
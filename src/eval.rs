@@ -0,0 +1,414 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::ast::{BinOp, Expr, UnOp};
+use crate::fold::float_to_string;
+use crate::lex::{parse_float, parse_int};
+use crate::span::{Span, Spanned};
+
+/// A value produced by evaluating a constant expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LuaValue {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    Str(Vec<u8>),
+}
+
+impl LuaValue {
+    /// The name Lua's `type` gives this value.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LuaValue::Nil => "nil",
+            LuaValue::Bool(_) => "boolean",
+            LuaValue::Integer(_) | LuaValue::Float(_) => "number",
+            LuaValue::Str(_) => "string",
+        }
+    }
+
+    /// Everything but `nil` and `false` is true in a condition.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, LuaValue::Nil | LuaValue::Bool(false))
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            LuaValue::Integer(value) => Some(*value as f64),
+            LuaValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Formats like Lua's `tostring`.
+impl fmt::Display for LuaValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LuaValue::Nil => write!(f, "nil"),
+            LuaValue::Bool(value) => write!(f, "{value}"),
+            LuaValue::Integer(value) => write!(f, "{value}"),
+            LuaValue::Float(value) => write!(f, "{}", float_to_string(*value)),
+            LuaValue::Str(value) => write!(f, "{}", String::from_utf8_lossy(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl EvalError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        EvalError { message: message.into(), span }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// Evaluate an expression built only from literals and operators, following
+/// Lua 5.4's arithmetic, comparison, concatenation and logical semantics.
+pub fn eval_const(expr: &Spanned<Expr>) -> Result<LuaValue, EvalError> {
+    match &expr.node {
+        Expr::Nil => Ok(LuaValue::Nil),
+        Expr::Bool(value) => Ok(LuaValue::Bool(*value)),
+        Expr::Integer(value, _) => Ok(LuaValue::Integer(*value)),
+        Expr::Float(value, _) => Ok(LuaValue::Float(*value)),
+        Expr::String(value) => Ok(LuaValue::Str(value.clone())),
+        Expr::Paren(inner) => eval_const(inner),
+        // `and` yields a falsy `lhs` and `or` a truthy one without evaluating `rhs`.
+        Expr::Logical { op, lhs, rhs } => {
+            let lhs = eval_const(lhs)?;
+            if lhs.is_truthy() == (*op == BinOp::Or) { Ok(lhs) } else { eval_const(rhs) }
+        }
+        Expr::Binary { op, lhs, rhs } => {
+            let (lhs_value, rhs_value) = (eval_const(lhs)?, eval_const(rhs)?);
+            binary(*op, (lhs_value, lhs.span), (rhs_value, rhs.span), expr.span)
+        }
+        Expr::Unary { op, operand } => unary(*op, eval_const(operand)?, operand.span),
+        Expr::Name(name) => Err(EvalError::new(format!("'{name}' is not a constant"), expr.span)),
+        Expr::Vararg
+        | Expr::InterpolatedString { .. }
+        | Expr::Function { .. }
+        | Expr::Table(_)
+        | Expr::Field { .. }
+        | Expr::Index { .. }
+        | Expr::Call { .. }
+        | Expr::MethodCall { .. } => {
+            Err(EvalError::new("expression is not a constant", expr.span))
+        }
+    }
+}
+
+fn binary(op: BinOp, lhs: (LuaValue, Span), rhs: (LuaValue, Span), span: Span) -> Result<LuaValue, EvalError> {
+    match op {
+        BinOp::Equal => Ok(LuaValue::Bool(raw_equal(&lhs.0, &rhs.0))),
+        BinOp::NotEqual => Ok(LuaValue::Bool(!raw_equal(&lhs.0, &rhs.0))),
+        BinOp::Less => less_than(&lhs.0, &rhs.0, false, span).map(LuaValue::Bool),
+        BinOp::LessEqual => less_than(&lhs.0, &rhs.0, true, span).map(LuaValue::Bool),
+        BinOp::Greater => less_than(&rhs.0, &lhs.0, false, span).map(LuaValue::Bool),
+        BinOp::GreaterEqual => less_than(&rhs.0, &lhs.0, true, span).map(LuaValue::Bool),
+        BinOp::Concat => Ok(LuaValue::Str([concat_operand(lhs)?, concat_operand(rhs)?].concat())),
+        BinOp::BitOr | BinOp::BitXor | BinOp::BitAnd | BinOp::ShiftLeft | BinOp::ShiftRight => {
+            let (a, b) = (to_integer(lhs)?, to_integer(rhs)?);
+            Ok(LuaValue::Integer(match op {
+                BinOp::BitOr => a | b,
+                BinOp::BitXor => a ^ b,
+                BinOp::BitAnd => a & b,
+                BinOp::ShiftLeft => shift_left(a, b),
+                _ => shift_left(a, b.wrapping_neg()),
+            }))
+        }
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::FloorDiv | BinOp::Mod | BinOp::Pow => {
+            arith(op, to_number(lhs)?, to_number(rhs)?, span)
+        }
+        BinOp::And | BinOp::Or => Err(EvalError::new("'and'/'or' must be an Expr::Logical", span)),
+    }
+}
+
+fn arith(op: BinOp, lhs: LuaValue, rhs: LuaValue, span: Span) -> Result<LuaValue, EvalError> {
+    if let (LuaValue::Integer(a), LuaValue::Integer(b)) = (&lhs, &rhs)
+        && !matches!(op, BinOp::Div | BinOp::Pow)
+    {
+        let (a, b) = (*a, *b);
+        return Ok(LuaValue::Integer(match op {
+            BinOp::Add => a.wrapping_add(b),
+            BinOp::Sub => a.wrapping_sub(b),
+            BinOp::Mul => a.wrapping_mul(b),
+            BinOp::FloorDiv if b == 0 => return Err(EvalError::new("attempt to perform 'n//0'", span)),
+            BinOp::FloorDiv => int_floor_div(a, b),
+            BinOp::Mod if b == 0 => return Err(EvalError::new("attempt to perform 'n%0'", span)),
+            _ => int_mod(a, b),
+        }));
+    }
+    let (a, b) = (lhs.as_f64().unwrap(), rhs.as_f64().unwrap());
+    Ok(LuaValue::Float(match op {
+        BinOp::Add => a + b,
+        BinOp::Sub => a - b,
+        BinOp::Mul => a * b,
+        BinOp::Div => a / b,
+        BinOp::FloorDiv => (a / b).floor(),
+        BinOp::Pow => a.powf(b),
+        _ => float_mod(a, b),
+    }))
+}
+
+/// Integer `//`, rounding towards negative infinity.
+fn int_floor_div(a: i64, b: i64) -> i64 {
+    let quotient = a.wrapping_div(b);
+    if a.wrapping_rem(b) != 0 && (a ^ b) < 0 { quotient - 1 } else { quotient }
+}
+
+/// Integer `%`, taking the sign of the divisor.
+fn int_mod(a: i64, b: i64) -> i64 {
+    let rem = a.wrapping_rem(b);
+    if rem != 0 && (rem ^ b) < 0 { rem + b } else { rem }
+}
+
+fn float_mod(a: f64, b: f64) -> f64 {
+    let rem = a % b;
+    if rem != 0.0 && (rem < 0.0) != (b < 0.0) { rem + b } else { rem }
+}
+
+/// `a << n`, a logical shift right for negative `n` and zero once every bit is gone.
+fn shift_left(a: i64, n: i64) -> i64 {
+    match n {
+        n if n <= -64 || n >= 64 => 0,
+        n if n < 0 => ((a as u64) >> -n) as i64,
+        n => ((a as u64) << n) as i64,
+    }
+}
+
+fn unary(op: UnOp, operand: LuaValue, span: Span) -> Result<LuaValue, EvalError> {
+    match op {
+        UnOp::Not => Ok(LuaValue::Bool(!operand.is_truthy())),
+        UnOp::Neg => match to_number((operand, span))? {
+            LuaValue::Integer(value) => Ok(LuaValue::Integer(value.wrapping_neg())),
+            value => Ok(LuaValue::Float(-value.as_f64().unwrap())),
+        },
+        UnOp::Len => match operand {
+            LuaValue::Str(value) => Ok(LuaValue::Integer(value.len() as i64)),
+            value => Err(EvalError::new(format!("attempt to get length of a {} value", value.type_name()), span)),
+        },
+        UnOp::BitNot => Ok(LuaValue::Integer(!to_integer((operand, span))?)),
+    }
+}
+
+/// `==` without metamethods: numbers compare by value across subtypes.
+fn raw_equal(lhs: &LuaValue, rhs: &LuaValue) -> bool {
+    match (lhs, rhs) {
+        (LuaValue::Integer(a), LuaValue::Float(b)) | (LuaValue::Float(b), LuaValue::Integer(a)) => float_to_int(*b) == Some(*a),
+        _ => lhs == rhs,
+    }
+}
+
+fn less_than(lhs: &LuaValue, rhs: &LuaValue, or_equal: bool, span: Span) -> Result<bool, EvalError> {
+    let ordering = match (lhs, rhs) {
+        (LuaValue::Integer(a), LuaValue::Integer(b)) => Some(a.cmp(b)),
+        (LuaValue::Str(a), LuaValue::Str(b)) => Some(a.cmp(b)),
+        (LuaValue::Integer(a), LuaValue::Float(b)) => int_float_cmp(*a, *b),
+        (LuaValue::Float(a), LuaValue::Integer(b)) => int_float_cmp(*b, *a).map(Ordering::reverse),
+        _ => match (lhs.as_f64(), rhs.as_f64()) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => {
+                let message = format!("attempt to compare {} with {}", lhs.type_name(), rhs.type_name());
+                return Err(EvalError::new(message, span));
+            }
+        },
+    };
+    Ok(match ordering {
+        Some(Ordering::Less) => true,
+        Some(Ordering::Equal) => or_equal,
+        _ => false,
+    })
+}
+
+/// Order an integer and a float exactly, without rounding the integer to a float.
+fn int_float_cmp(int: i64, float: f64) -> Option<Ordering> {
+    match float_to_int(float.floor()) {
+        Some(floor) => Some(int.cmp(&floor).then(if float.fract() == 0.0 { Ordering::Equal } else { Ordering::Less })),
+        // Past the i64 range, or NaN.
+        None => 0.0.partial_cmp(&float),
+    }
+}
+
+fn concat_operand((value, span): (LuaValue, Span)) -> Result<Vec<u8>, EvalError> {
+    match value {
+        LuaValue::Str(value) => Ok(value),
+        LuaValue::Integer(_) | LuaValue::Float(_) => Ok(value.to_string().into_bytes()),
+        value => Err(EvalError::new(format!("attempt to concatenate a {} value", value.type_name()), span)),
+    }
+}
+
+/// Coerce an arithmetic operand to a number, converting numeric strings.
+fn to_number((value, span): (LuaValue, Span)) -> Result<LuaValue, EvalError> {
+    let number = match &value {
+        LuaValue::Integer(_) | LuaValue::Float(_) => Some(value.clone()),
+        LuaValue::Str(text) => str_to_number(text),
+        _ => None,
+    };
+    match number {
+        Some(number) => Ok(number),
+        None => Err(EvalError::new(format!("attempt to perform arithmetic on a {} value", value.type_name()), span)),
+    }
+}
+
+/// Coerce a bitwise operand to an integer; floats must have an exact integer value.
+fn to_integer((value, span): (LuaValue, Span)) -> Result<i64, EvalError> {
+    let number = match value {
+        LuaValue::Str(ref text) => str_to_number(text),
+        LuaValue::Integer(_) | LuaValue::Float(_) => Some(value.clone()),
+        _ => None,
+    };
+    match number {
+        Some(LuaValue::Integer(value)) => Ok(value),
+        Some(LuaValue::Float(value)) if float_to_int(value).is_some() => Ok(value as i64),
+        Some(_) => Err(EvalError::new("number has no integer representation", span)),
+        None => Err(EvalError::new(format!("attempt to perform bitwise operation on a {} value", value.type_name()), span)),
+    }
+}
+
+/// The integer `value` is exactly equal to, if it has one.
+fn float_to_int(value: f64) -> Option<i64> {
+    // 2^63 is the first float past i64::MAX.
+    (value.fract() == 0.0 && (-9.223372036854776e18..9.223372036854776e18).contains(&value)).then_some(value as i64)
+}
+
+/// The number a string converts to, as Lua's `tonumber` would read it: one
+/// numeral with an optional sign, and only whitespace around it.
+fn str_to_number(text: &[u8]) -> Option<LuaValue> {
+    let is_space = |b: &u8| b" \t\n\x0b\x0c\r".contains(b);
+    let start = text.iter().position(|b| !is_space(b))?;
+    let end = text.iter().rposition(|b| !is_space(b))? + 1;
+    let (negative, numeral) = match &text[start..end] {
+        [b'-', rest @ ..] => (true, rest),
+        [b'+', rest @ ..] => (false, rest),
+        numeral => (false, numeral),
+    };
+    let value = if numeral_is_float(numeral)? {
+        LuaValue::Float(parse_float(numeral)?)
+    } else {
+        // A decimal integer too big for 64 bits reads as a float.
+        parse_int(numeral).map(LuaValue::Integer).or_else(|| parse_float(numeral).map(LuaValue::Float))?
+    };
+    Some(match value {
+        LuaValue::Integer(value) if negative => LuaValue::Integer(value.wrapping_neg()),
+        LuaValue::Float(value) if negative => LuaValue::Float(-value),
+        value => value,
+    })
+}
+
+/// Whether `numeral` is a float rather than an integer, `None` if it isn't a
+/// decimal or hex numeral at all.
+fn numeral_is_float(numeral: &[u8]) -> Option<bool> {
+    let (is_digit, exponent, digits): (fn(&u8) -> bool, &[u8], _) =
+        match numeral.strip_prefix(b"0x").or_else(|| numeral.strip_prefix(b"0X")) {
+            Some(digits) => (u8::is_ascii_hexdigit, b"pP", digits),
+            None => (u8::is_ascii_digit, b"eE", numeral),
+        };
+    let mut mantissa = digits.iter().take_while(|b| is_digit(b)).count();
+    let mut rest = &digits[mantissa..];
+    let mut is_float = false;
+    if let Some(fraction) = rest.strip_prefix(b".") {
+        let len = fraction.iter().take_while(|b| is_digit(b)).count();
+        (mantissa, rest, is_float) = (mantissa + len, &fraction[len..], true);
+    }
+    if mantissa == 0 {
+        return None;
+    }
+    if let [letter, power @ ..] = rest && exponent.contains(letter) {
+        let power = power.strip_prefix(b"+").or_else(|| power.strip_prefix(b"-")).unwrap_or(power);
+        let len = power.iter().take_while(|b| b.is_ascii_digit()).count();
+        if len == 0 {
+            return None;
+        }
+        (rest, is_float) = (&power[len..], true);
+    }
+    rest.is_empty().then_some(is_float)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+
+    fn eval(source: &str) -> Result<LuaValue, EvalError> {
+        eval_const(&Parser::new(source).unwrap().expr().unwrap())
+    }
+
+    #[test]
+    fn eval_arithmetic() {
+        assert_eq!(eval("2 + 3 * 4"), Ok(LuaValue::Integer(14)));
+        assert_eq!(eval("7 / 2"), Ok(LuaValue::Float(3.5)));
+        assert_eq!(eval("7 // 2"), Ok(LuaValue::Integer(3)));
+        assert_eq!(eval("2 ^ 10"), Ok(LuaValue::Float(1024.0)));
+        assert_eq!(eval("'10' + 1"), Ok(LuaValue::Integer(11)));
+        assert_eq!(eval("'-5' + 1"), Ok(LuaValue::Integer(-4)));
+        assert_eq!(eval("'- 5' + 1").unwrap_err().message, "attempt to perform arithmetic on a string value");
+        assert_eq!(eval("' 0x10 ' + 0"), Ok(LuaValue::Integer(16)));
+        assert_eq!(eval("'+.5' + 0"), Ok(LuaValue::Float(0.5)));
+        assert_eq!(eval("'\\t1e2\\n' + 0"), Ok(LuaValue::Float(100.0)));
+        assert_eq!(eval("'9223372036854775808' + 0"), Ok(LuaValue::Float(9223372036854775808.0)));
+        assert_eq!(eval("1 << 4 | 1"), Ok(LuaValue::Integer(17)));
+        assert_eq!(eval("-5 % 3"), Ok(LuaValue::Integer(1)));
+        assert_eq!(eval("5.0 % -3"), Ok(LuaValue::Float(-1.0)));
+    }
+    #[test]
+    fn eval_concat() {
+        assert_eq!(eval("\"a\"..\"b\""), Ok(LuaValue::Str(b"ab".to_vec())));
+        assert_eq!(eval("1 .. 2.0"), Ok(LuaValue::Str(b"12.0".to_vec())));
+    }
+    #[test]
+    fn eval_logical() {
+        assert_eq!(eval("1 < 2 and \"y\" or \"n\""), Ok(LuaValue::Str(b"y".to_vec())));
+        assert_eq!(eval("1 > 2 and \"y\" or \"n\""), Ok(LuaValue::Str(b"n".to_vec())));
+        assert_eq!(eval("not (1 == 1.0)"), Ok(LuaValue::Bool(false)));
+        assert_eq!(eval("9007199254740993 == 9007199254740992.0"), Ok(LuaValue::Bool(false)));
+        assert_eq!(eval("9007199254740992 == 2^53"), Ok(LuaValue::Bool(true)));
+    }
+    #[test]
+    fn eval_compare_int_float() {
+        assert_eq!(eval("9007199254740993 <= 9007199254740992.0"), Ok(LuaValue::Bool(false)));
+        assert_eq!(eval("9007199254740992.0 < 9007199254740993"), Ok(LuaValue::Bool(true)));
+        assert_eq!(eval("9007199254740992 <= 2^53"), Ok(LuaValue::Bool(true)));
+        assert_eq!(eval("-2 < -1.5"), Ok(LuaValue::Bool(true)));
+        assert_eq!(eval("-1 < -1.5"), Ok(LuaValue::Bool(false)));
+        assert_eq!(eval("9223372036854775807 < 2^63"), Ok(LuaValue::Bool(true)));
+        assert_eq!(eval("1 < 0/0"), Ok(LuaValue::Bool(false)));
+        assert_eq!(eval("0/0 <= 1"), Ok(LuaValue::Bool(false)));
+    }
+    #[test]
+    fn eval_short_circuit() {
+        assert_eq!(eval("nil or 5"), Ok(LuaValue::Integer(5)));
+        assert_eq!(eval("false and x"), Ok(LuaValue::Bool(false)));
+        assert_eq!(eval("1 or x"), Ok(LuaValue::Integer(1)));
+        assert_eq!(eval("nil and x"), Ok(LuaValue::Nil));
+        assert_eq!(eval("true and x").unwrap_err().message, "'x' is not a constant");
+    }
+    #[test]
+    fn eval_not_constant() {
+        let error = eval("x + 1").unwrap_err();
+        assert_eq!(error.message, "'x' is not a constant");
+        assert_eq!(error.span, Span::new(0, 1));
+    }
+    #[test]
+    fn eval_type_errors() {
+        assert_eq!(eval("1 < '2'").unwrap_err().message, "attempt to compare number with string");
+        assert_eq!(eval("1 // 0").unwrap_err().message, "attempt to perform 'n//0'");
+        assert_eq!(eval("1 % 0").unwrap_err().message, "attempt to perform 'n%0'");
+        assert_eq!(eval("#1").unwrap_err().message, "attempt to get length of a number value");
+        assert_eq!(eval("1.5 | 0").unwrap_err().message, "number has no integer representation");
+        for text in ["'1--x'", "'#!x\\n1'", "'\u{feff}1'", "'1 2'", "'0x'", "'1e'", "'.'", "'inf'", "'1_0'", "''"] {
+            let message = eval(&format!("{text} + 0")).unwrap_err().message;
+            assert_eq!(message, "attempt to perform arithmetic on a string value", "{text}");
+        }
+    }
+}
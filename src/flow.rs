@@ -0,0 +1,117 @@
+use crate::ast::{for_each_child_block, for_each_stmt_expr, for_each_subexpr, Block, Expr, Stmt};
+use crate::span::{Span, Spanned};
+
+/// A statement that jumps, or that a jump lands on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlowStmt {
+    Label(String),
+    Goto(String),
+    Break,
+    Return,
+}
+
+/// What encloses a [`FlowScope`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    /// The main chunk, the root of the summary.
+    Chunk,
+    /// A function statement or expression, where `return` leaves.
+    Function,
+    /// A `while`, `repeat` or `for` loop, where `break` leaves.
+    Loop,
+}
+
+/// A function or loop with the jumps and labels directly in it, each with the
+/// span of its statement, and the functions and loops nested in it.
+///
+/// `do` and `if` blocks don't start a scope, so their statements belong to the
+/// function or loop around them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowScope {
+    pub kind: ScopeKind,
+    pub span: Span,
+    pub stmts: Vec<Spanned<FlowStmt>>,
+    pub children: Vec<FlowScope>,
+}
+
+/// Summarise the labels, `goto`s, `break`s and `return`s of `block`, a whole
+/// chunk, by the function or loop they are in.
+pub fn control_flow_summary(block: &Block) -> FlowScope {
+    let mut summary = FlowScope { kind: ScopeKind::Chunk, span: block.span, stmts: Vec::new(), children: Vec::new() };
+    summary.collect(block);
+    summary
+}
+
+impl FlowScope {
+    fn collect(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            for_each_stmt_expr(&stmt.node, |expr| {
+                for_each_subexpr(expr, &mut |expr| {
+                    if let Expr::Function { body, .. } = &expr.node {
+                        self.nest(ScopeKind::Function, expr.span, body);
+                    }
+                });
+            });
+            let flow = match &stmt.node {
+                Stmt::Label(name) => Some(FlowStmt::Label(name.node.clone())),
+                Stmt::Goto(name) => Some(FlowStmt::Goto(name.node.clone())),
+                Stmt::Break => Some(FlowStmt::Break),
+                Stmt::Return(_) => Some(FlowStmt::Return),
+                _ => None,
+            };
+            if let Some(flow) = flow {
+                self.stmts.push(Spanned::new(flow, stmt.span));
+            }
+            match &stmt.node {
+                Stmt::While { body, .. }
+                | Stmt::Repeat { body, .. }
+                | Stmt::NumericFor { body, .. }
+                | Stmt::GenericFor { body, .. } => self.nest(ScopeKind::Loop, stmt.span, body),
+                Stmt::Function { body, .. } | Stmt::LocalFunction { body, .. } => {
+                    self.nest(ScopeKind::Function, stmt.span, body)
+                }
+                stmt => for_each_child_block(stmt, |body| self.collect(body)),
+            }
+        }
+    }
+
+    fn nest(&mut self, kind: ScopeKind, span: Span, body: &Block) {
+        let mut scope = FlowScope { kind, span, stmts: Vec::new(), children: Vec::new() };
+        scope.collect(body);
+        self.children.push(scope);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn flow_scopes() {
+        let source = "\
+function f(t)
+  for i = 1, #t do
+    if not t[i] then goto skip end
+    if i > 9 then break end
+    ::skip::
+  end
+  return t
+end
+return f";
+        let summary = control_flow_summary(&parse(source).unwrap());
+        assert_eq!((summary.kind, summary.stmts.len()), (ScopeKind::Chunk, 1));
+        assert_eq!(summary.stmts[0], Spanned::new(FlowStmt::Return, Span::new(130, 138)));
+        let [function] = &summary.children[..] else { panic!("{summary:?}") };
+        assert_eq!(function.kind, ScopeKind::Function);
+        assert_eq!(function.stmts, [Spanned::new(FlowStmt::Return, Span::new(117, 125))]);
+        let [for_loop] = &function.children[..] else { panic!("{function:?}") };
+        assert_eq!((for_loop.kind, for_loop.span), (ScopeKind::Loop, Span::new(16, 114)));
+        assert_eq!(for_loop.stmts, [
+            Spanned::new(FlowStmt::Goto("skip".to_string()), Span::new(54, 63)),
+            Spanned::new(FlowStmt::Break, Span::new(86, 91)),
+            Spanned::new(FlowStmt::Label("skip".to_string()), Span::new(100, 108)),
+        ]);
+        assert!(for_loop.children.is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::ast::{for_each_child_expr_mut, BinOp, Block, Expr, Stmt};
+use crate::eval::{eval_const, LuaValue};
+use crate::span::Spanned;
+
+/// Constant fold every expression in `block`.
+pub fn fold_block(block: &mut Block) {
+    for stmt in &mut block.stmts {
+        match &mut stmt.node {
+            Stmt::Assign { targets, values } => {
+                targets.iter_mut().for_each(fold_expr);
+                values.iter_mut().for_each(fold_expr);
+            }
+            Stmt::CompoundAssign { target, value, .. } => {
+                fold_expr(target);
+                fold_expr(value);
+            }
+            Stmt::Call(call) => fold_expr(call),
+            Stmt::Local { values, .. } | Stmt::Return(values) => values.iter_mut().for_each(fold_expr),
+            Stmt::Do(body) => fold_block(body),
+            Stmt::While { cond, body } | Stmt::Repeat { body, cond } => {
+                fold_expr(cond);
+                fold_block(body);
+            }
+            Stmt::If { arms, else_block } => {
+                for (cond, body) in arms {
+                    fold_expr(cond);
+                    fold_block(body);
+                }
+                if let Some(body) = else_block {
+                    fold_block(body);
+                }
+            }
+            Stmt::NumericFor { start, end, step, body, .. } => {
+                fold_expr(start);
+                fold_expr(end);
+                if let Some(step) = step {
+                    fold_expr(step);
+                }
+                fold_block(body);
+            }
+            Stmt::GenericFor { exprs, body, .. } => {
+                exprs.iter_mut().for_each(fold_expr);
+                fold_block(body);
+            }
+            Stmt::Function { body, .. } | Stmt::LocalFunction { body, .. } => fold_block(body),
+            Stmt::Break | Stmt::Goto(_) | Stmt::Label(_) => {}
+        }
+    }
+}
+
+/// Constant fold `expr` bottom-up, replacing it in place.
+///
+/// `..` of two string or number literals becomes a single string literal,
+/// following Lua's number-to-string coercion, and arithmetic on number literals
+/// becomes its result, keeping Lua 5.3's integer and float subtypes: `1 + 1` is
+/// an integer, `1 + 1.0` and `3 / 2` floats.
+pub fn fold_expr(expr: &mut Spanned<Expr>) {
+    if let Expr::Function { body, .. } = &mut expr.node {
+        fold_block(body);
+    }
+    for_each_child_expr_mut(expr, fold_expr);
+    let Expr::Binary { op, lhs, rhs } = &expr.node else { return };
+    if *op == BinOp::Concat
+        && let (Some(lhs), Some(rhs)) = (concat_operand(lhs), concat_operand(rhs))
+    {
+        expr.node = Expr::String([lhs, rhs].concat());
+    } else if matches!(op, BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::FloorDiv | BinOp::Mod | BinOp::Pow) {
+        fold_number(expr);
+    }
+}
+
+/// Replace a numeric operation on literals with its value, leaving errors such
+/// as `1 % 0` to be raised at run time.
+fn fold_number(expr: &mut Spanned<Expr>) {
+    match eval_const(expr) {
+        Ok(LuaValue::Integer(value)) => expr.node = Expr::Integer(value, None),
+        Ok(LuaValue::Float(value)) => expr.node = Expr::Float(value, None),
+        _ => {}
+    }
+}
+
+/// The string a literal contributes to `..`, if it is a foldable literal.
+fn concat_operand(mut expr: &Spanned<Expr>) -> Option<Vec<u8>> {
+    while let Expr::Paren(inner) = &expr.node {
+        expr = inner;
+    }
+    match &expr.node {
+        Expr::String(value) => Some(value.clone()),
+        Expr::Integer(value, _) => Some(value.to_string().into_bytes()),
+        Expr::Float(value, _) => Some(float_to_string(*value).into_bytes()),
+        _ => None,
+    }
+}
+
+/// Format a float the way Lua's `tostring` does (`%.14g`, keeping a `.0` on integral values).
+pub(crate) fn float_to_string(value: f64) -> String {
+    if value.is_nan() {
+        return if value.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+
+    let sci = format!("{value:.13e}");
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    let mut out = if !(-4..14).contains(&exp) {
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{sign}{:02}", trim_fraction(mantissa), exp.abs())
+    } else {
+        let fixed = format!("{value:.*}", (13 - exp) as usize);
+        trim_fraction(&fixed).to_string()
+    };
+
+    if out.bytes().all(|b| b.is_ascii_digit() || b == b'-') {
+        out.push_str(".0");
+    }
+    out
+}
+
+fn trim_fraction(text: &str) -> &str {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+
+    fn fold(source: &str) -> Expr {
+        let mut expr = Parser::new(source).unwrap().expr().unwrap();
+        fold_expr(&mut expr);
+        expr.node
+    }
+
+    #[test]
+    fn fold_string_concat() {
+        assert_eq!(fold("\"a\" .. \"b\""), Expr::String(b"ab".to_vec()));
+        assert_eq!(fold("'a' .. ('b' .. 'c')"), Expr::String(b"abc".to_vec()));
+    }
+    #[test]
+    fn fold_number_concat() {
+        assert_eq!(fold("1 .. 2"), Expr::String(b"12".to_vec()));
+        assert_eq!(fold("1.0 .. 'x'"), Expr::String(b"1.0x".to_vec()));
+        assert_eq!(fold("0.1 .. ''"), Expr::String(b"0.1".to_vec()));
+    }
+    #[test]
+    fn fold_concat_leaves_names() {
+        let expr = fold("x .. \"b\"");
+        assert!(matches!(expr, Expr::Binary { op: BinOp::Concat, .. }), "{expr:?}");
+        let expr = fold("\"a\" .. \"b\" .. x");
+        assert!(matches!(expr, Expr::Binary { op: BinOp::Concat, .. }), "{expr:?}");
+    }
+    #[test]
+    fn fold_number_subtypes() {
+        assert_eq!(fold("1 + 1"), Expr::Integer(2, None));
+        assert_eq!(fold("1 + 1.0"), Expr::Float(2.0, None));
+        assert_eq!(fold("3 / 2"), Expr::Float(1.5, None));
+        assert_eq!(fold("4 / 2"), Expr::Float(2.0, None));
+        assert_eq!(fold("7 // 2"), Expr::Integer(3, None));
+        assert_eq!(fold("7.0 // 2"), Expr::Float(3.0, None));
+        assert_eq!(fold("2 ^ 2 * 3"), Expr::Float(12.0, None));
+        assert_eq!(fold("(1 + 2) * -3 .. ''"), Expr::String(b"-9".to_vec()));
+        let expr = fold("1 // 0");
+        assert!(matches!(expr, Expr::Binary { op: BinOp::FloorDiv, .. }), "{expr:?}");
+        let expr = fold("\"1--x\" + 1");
+        assert!(matches!(expr, Expr::Binary { op: BinOp::Add, .. }), "{expr:?}");
+        let expr = fold("x + 1 * 2");
+        assert!(matches!(&expr, Expr::Binary { rhs, .. } if matches!(rhs.node, Expr::Integer(2, _))), "{expr:?}");
+    }
+    #[test]
+    fn fold_floored_modulo() {
+        assert_eq!(fold("-5 % 3"), Expr::Integer(1, None));
+        assert_eq!(fold("5 % -3"), Expr::Integer(-1, None));
+        assert_eq!(fold("-5.5 % 2"), Expr::Float(0.5, None));
+        assert_eq!(fold("5.5 % -2"), Expr::Float(-0.5, None));
+        let expr = fold("1 % 0");
+        assert!(matches!(expr, Expr::Binary { op: BinOp::Mod, .. }), "{expr:?}");
+    }
+    #[test]
+    fn fold_float_to_string() {
+        assert_eq!(float_to_string(1e100), "1e+100");
+        assert_eq!(float_to_string(-2.5), "-2.5");
+        assert_eq!(float_to_string(1e15), "1e+15");
+        assert_eq!(float_to_string(123456.0), "123456.0");
+        assert_eq!(float_to_string(0.0001), "0.0001");
+    }
+}
@@ -0,0 +1,93 @@
+use std::ops::Range;
+
+use crate::lex::{lexer_with_comments, long_bracket_end, long_bracket_level, LexError, TokenKind};
+
+/// What a line inherits from the lines before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineState {
+    #[default]
+    Normal,
+    /// Inside a long string opened with a bracket of this level.
+    LongString(usize),
+    /// Inside a long comment opened with a bracket of this level.
+    LongComment(usize),
+}
+
+/// Tokenize one line for highlighting, starting in `state` and returning the
+/// state the next line starts in.
+///
+/// Ranges are byte offsets into `text`. Text the lexer rejects is left unclassified.
+pub fn classify_line(text: &str, state: LineState) -> (Vec<(TokenKind, Range<usize>)>, LineState) {
+    let mut tokens = Vec::new();
+    let (kind, level) = match state {
+        LineState::Normal => return classify_from(text, 0, tokens),
+        LineState::LongString(level) => (TokenKind::LongString, level),
+        LineState::LongComment(level) => (TokenKind::Comment, level),
+    };
+    let Some(end) = long_bracket_end(text, level) else {
+        tokens.push((kind, 0..text.len()));
+        return (tokens, state);
+    };
+    tokens.push((kind, 0..end));
+    classify_from(text, end, tokens)
+}
+
+fn classify_from(
+    text: &str,
+    start: usize,
+    mut tokens: Vec<(TokenKind, Range<usize>)>,
+) -> (Vec<(TokenKind, Range<usize>)>, LineState) {
+    let rest = &text[start..];
+    let shift = |range: Range<usize>| range.start + start..range.end + start;
+    for (token, range) in lexer_with_comments(rest).spanned() {
+        match token {
+            Ok(token) => tokens.push((token.kind(), shift(range))),
+            // An unclosed long bracket runs to the end of the line and beyond.
+            Err(LexError::UnterminatedComment { .. }) => {
+                let level = long_bracket_level(&rest[range.start + 2..]).unwrap_or_default();
+                tokens.push((TokenKind::Comment, shift(range)));
+                return (tokens, LineState::LongComment(level));
+            }
+            Err(LexError::UnterminatedString { .. }) => {
+                let level = long_bracket_level(&rest[range.start..]).unwrap_or_default();
+                tokens.push((TokenKind::LongString, shift(range)));
+                return (tokens, LineState::LongString(level));
+            }
+            Err(_) => {}
+        }
+    }
+    (tokens, LineState::Normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_long_string_across_lines() {
+        let (tokens, state) = classify_line("local s = [==[first", LineState::Normal);
+        assert_eq!(
+            tokens,
+            vec![(TokenKind::Local, 0..5), (TokenKind::Identifier, 6..7), (TokenKind::Equal, 8..9), (TokenKind::LongString, 10..19)]
+        );
+        assert_eq!(state, LineState::LongString(2));
+
+        let (tokens, state) = classify_line("middle ]] still", state);
+        assert_eq!(tokens, vec![(TokenKind::LongString, 0..15)]);
+        assert_eq!(state, LineState::LongString(2));
+
+        let (tokens, state) = classify_line("last]==] .. x", state);
+        assert_eq!(tokens, vec![(TokenKind::LongString, 0..8), (TokenKind::Concatenate, 9..11), (TokenKind::Identifier, 12..13)]);
+        assert_eq!(state, LineState::Normal);
+    }
+    #[test]
+    fn highlight_long_comment_across_lines() {
+        let (tokens, state) = classify_line("x = 1 --[[ note", LineState::Normal);
+        assert_eq!(tokens.last(), Some(&(TokenKind::Comment, 6..15)));
+        assert_eq!(state, LineState::LongComment(0));
+
+        let (tokens, state) = classify_line("]] return", state);
+        assert_eq!(tokens, vec![(TokenKind::Comment, 0..2), (TokenKind::Return, 3..9)]);
+        assert_eq!(state, LineState::Normal);
+    }
+}
@@ -0,0 +1,30 @@
+//! The AST as JSON, for tools written in other languages.
+//!
+//! Every node keeps its span as `{"start": .., "end": ..}` byte offsets. Enum
+//! nodes are tagged, `{"type": "LocalDecl", "value": {..}}`, with `value` left out
+//! of nodes that carry nothing, like `{"type": "Break"}`.
+
+use crate::ast::Block;
+
+/// Serialize `block` and everything in it to a JSON string.
+pub fn to_json(block: &Block) -> String {
+    serde_json::to_string(block).expect("AST nodes always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn json_local() {
+        let json: serde_json::Value = serde_json::from_str(&to_json(&parse("local x = 1").unwrap())).unwrap();
+        let stmt = &json["stmts"][0];
+        assert_eq!(stmt["span"], serde_json::json!({"start": 0, "end": 11}));
+        assert_eq!(stmt["node"]["type"], "LocalDecl");
+        let local = &stmt["node"]["value"];
+        assert_eq!(local["names"][0], serde_json::json!({"node": "x", "span": {"start": 6, "end": 7}}));
+        assert_eq!(local["values"][0]["node"], serde_json::json!({"type": "Integer", "value": [1, "1"]}));
+        assert_eq!(to_json(&parse("break").unwrap()).matches(r#"{"type":"Break"}"#).count(), 1);
+    }
+}
@@ -1,15 +1,45 @@
-use logos::{Logos, Lexer};
-use lexical_core;
+use std::fmt;
+use std::ops::ControlFlow;
+
+use logos::{FilterResult, Lexer, Logos, Source};
+
+use crate::ast::{BinOp, UNARY_PRECEDENCE};
+use crate::span::Span;
+
+mod bytes;
+mod chunked;
+mod count;
+mod escape;
+mod hash;
+mod intern;
+mod lines;
+mod markdown;
+mod owned;
+mod range;
+mod require;
+mod timing;
+
+pub use bytes::ByteToken;
+pub use chunked::ChunkLexer;
+pub use count::{count_tokens, identifier_frequencies};
+pub use escape::{decode_escapes, decode_escapes_into, normalize_newlines, EscapeError};
+pub use hash::token_hash;
+pub use intern::{Interner, Symbol};
+pub use lines::tokens_by_line;
+pub use markdown::{lex_markdown_lua, LuaFence};
+pub use owned::{to_owned_tokens, OwnedLuaToken};
+pub use range::{token_at, tokens_in_range};
+pub use require::extract_requires;
+pub use timing::lex_and_time;
 
 /// Lua language tokens.
 ///
-/// No support for nested comments or nested multiline strings.
-///
-/// `--[==[This is a nested comment--]==]`
-#[derive(Logos, Debug, PartialEq)]
-#[logos(skip r"[ \t\n\f]+")]
-#[logos(skip r"--[^\n]*")]
-#[logos(skip r"--\[\[(.|\n)--\]\]")]
+/// Comments, including long comments like `--[==[ comment ]==]`, are skipped
+/// unless [`LexExtras::retain_comments`] is set.
+#[derive(Logos, Debug, Clone, PartialEq)]
+#[logos(error = LexError)]
+#[logos(extras = LexExtras)]
+#[logos(skip r"[ \t\n\r\x0b\f]+")]
 #[logos(skip r"\#\![^\n]*")]
 pub enum LuaToken<'source> {
     //==--------
@@ -35,7 +65,8 @@ pub enum LuaToken<'source> {
     For,
     #[token("function")]
     Function,
-    #[token("goto")]
+    /// Only a keyword since Lua 5.2, an identifier before.
+    #[token("goto", goto_keyword)]
     Goto,
     #[token("if")]
     If,
@@ -64,18 +95,30 @@ pub enum LuaToken<'source> {
     //==---------
     // Operators
     //==---------
-    #[token("+")]
+    #[token("+", compound_assign)]
     Plus,
-    #[token("-")]
+    #[token("-", compound_assign)]
     Minus,
-    #[token("*")]
+    #[token("*", compound_assign)]
     Multiply,
-    #[token("/")]
+    #[token("/", compound_assign)]
     Divide,
-    #[token("%")]
+    #[token("//", compound_assign)]
+    FloorDivide,
+    #[token("%", compound_assign)]
     Modulus,
-    #[token("^")]
+    #[token("^", compound_assign)]
     Exponent,
+    #[token("&", bitwise)]
+    Ampersand,
+    #[token("|", bitwise)]
+    Pipe,
+    #[token("~", bitwise)]
+    Tilde,
+    #[token("<<", bitwise)]
+    ShiftLeft,
+    #[token(">>", bitwise)]
+    ShiftRight,
     #[token("==")]
     DoubleEqual,
     #[token("=")]
@@ -90,18 +133,50 @@ pub enum LuaToken<'source> {
     GreaterEqual,
     #[token("<=")]
     LessEqual,
-    #[token("..")]
+    #[token(".")]
+    Dot,
+    #[token("..", compound_assign)]
     Concatenate,
+    #[token("...")]
+    Ellipsis,
     #[token("#")]
     Length,
     #[token("[")]
     LBracket,
     #[token("]")]
     RBracket,
+    #[token("(")]
+    LParen,
+    #[token(")")]
+    RParen,
+    #[token("{")]
+    LBrace,
+    #[token("}")]
+    RBrace,
+    #[token(",")]
+    Comma,
+    #[token(";")]
+    Semicolon,
+    #[token(":")]
+    Colon,
+    #[token("::", label_delimiter)]
+    DoubleColon,
+    /// Luau's `op=`, e.g. `+=` or `..=`. Other dialects lex the operator then `=`.
+    CompoundAssign(BinOp),
+    /// Luau's `@name` attribute, e.g. `@native`, holding the name without the `@`.
+    /// Other dialects reject `@` as an unexpected character.
+    #[token("@", annotation)]
+    Annotation(&'source str),
+    //==---------
+    // Comments
+    //==---------
+    /// Only produced when comments are retained, holds the whole comment including `--`.
+    #[token("--", comment)]
+    Comment(&'source str),
     //==----------
     // Identifier
     //==----------
-    #[regex("[a-zA-Z_][a-zA-Z_0-9]*", |text| text.slice())]
+    #[regex("[a-zA-Z_][a-zA-Z_0-9]*", identifier)]
     Identifier(&'source str),
     /*
     #[regex(r"[a-zA-Z_][a-zA-Z_0-9]*\[[a-zA-Z_][a-zA-Z_]*\]")]
@@ -112,8 +187,19 @@ pub enum LuaToken<'source> {
     //==---------------
     // String literals
     //==---------------
-    #[regex("\"[^\"\n]*\"|'[^'\n]*'", |text| text.slice()[1..text.slice().len()-1].trim())]
+    /// The raw body between the quotes, escapes still undecoded. A newline may
+    /// only appear escaped by a backslash.
+    #[regex(r#""([^"\\\n]|\\(.|\n|\r\n))*"|'([^'\\\n]|\\(.|\n|\r\n))*'"#, |text| &text.slice()[1..text.slice().len()-1])]
     String(&'source str),
+    /// The contents of `[[...]]` or `[==[...]==]`, minus a newline right after the opening
+    /// bracket. Line breaks are as in the source, see [`normalize_newlines`].
+    #[regex(r"\[=*\[", long_string)]
+    LongString(&'source str),
+    /// Luau's backtick string, the raw body between the backticks with its
+    /// escapes and `{expr}` holes as written. Other dialects reject `` ` `` as
+    /// an unexpected character.
+    #[token("`", interpolated_string)]
+    InterpolatedString(&'source str),
     /*
     #[regex("'([^'\n]*)'", |text| text.slice())]
     SingleQuoteString(&'source str),
@@ -125,38 +211,1382 @@ pub enum LuaToken<'source> {
     //==---------------
     // Number literals
     //==---------------
-    #[regex(r"[0-9][0-9_]*|0[xX][0-9a-fA-F][0-9a-fA-F_]*", as_int)]
+    /// Decimal integers too big for 64 bits lex as [`LuaToken::Float`], as in
+    /// reference Lua. Hex integers wrap around instead.
+    #[regex(r"0[xX][0-9a-fA-F][0-9a-fA-F_]*", as_int)]
     Integer(i64),
-    #[regex(r"([0-9][0-9_]*\.[0-9][0-9_]*)|(0[xX][0-9a-fA-F][0-9a-fA-F_]*\.[0-9a-fA-F][0-9a-fA-F_]*)", as_float)]
+    /// Never produced: holds the decimal integer pattern, whose callback gives
+    /// `Integer` or, past `i64::MAX`, `Float`.
+    #[doc(hidden)]
+    #[regex(r"[0-9][0-9_]*", decimal_integer)]
+    DecimalInteger,
+    #[regex(r"[0-9][0-9_]*(\.([0-9][0-9_]*)?([eE][+-]?[0-9]+)?|[eE][+-]?[0-9]+)|0[xX][0-9a-fA-F][0-9a-fA-F_]*(\.([0-9a-fA-F][0-9a-fA-F_]*)?([pP][+-]?[0-9]+)?|[pP][+-]?[0-9]+)", as_float)]
     Float(f64),
 }
 
-fn as_int<'source>(text: &mut Lexer<'source, LuaToken<'source>>) -> Option<i64> {
-    let s: String = text.slice().trim_start_matches("0x").trim_start_matches("0X").replace("_","");
+/// The kind of a [`LuaToken`], without its payload.
+///
+/// Each kind has a fixed `u16` discriminant, see [`TokenKind::as_u16`]. Discriminants
+/// are stable within a major version: new kinds only take unused numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u16)]
+pub enum TokenKind {
+    // Keywords, from 0.
+    And = 0,
+    Break = 1,
+    Continue = 2,
+    Do = 3,
+    Else = 4,
+    Elseif = 5,
+    End = 6,
+    False = 7,
+    For = 8,
+    Function = 9,
+    Goto = 10,
+    If = 11,
+    In = 12,
+    Local = 13,
+    Nil = 14,
+    Not = 15,
+    Or = 16,
+    Repeat = 17,
+    Return = 18,
+    Then = 19,
+    True = 20,
+    Until = 21,
+    While = 22,
+    // Operators and punctuation, from 64.
+    Plus = 64,
+    Minus = 65,
+    Multiply = 66,
+    Divide = 67,
+    FloorDivide = 68,
+    Modulus = 69,
+    Exponent = 70,
+    Ampersand = 71,
+    Pipe = 72,
+    Tilde = 73,
+    ShiftLeft = 74,
+    ShiftRight = 75,
+    DoubleEqual = 76,
+    Equal = 77,
+    NotEqual = 78,
+    Greater = 79,
+    Less = 80,
+    GreaterEqual = 81,
+    LessEqual = 82,
+    Dot = 83,
+    Concatenate = 84,
+    Ellipsis = 85,
+    Length = 86,
+    LBracket = 87,
+    RBracket = 88,
+    LParen = 89,
+    RParen = 90,
+    LBrace = 91,
+    RBrace = 92,
+    Comma = 93,
+    Semicolon = 94,
+    Colon = 95,
+    DoubleColon = 96,
+    // Kinds with a payload, from 128.
+    Comment = 128,
+    Identifier = 129,
+    String = 130,
+    LongString = 131,
+    Integer = 132,
+    Float = 133,
+    CompoundAssign = 134,
+    Annotation = 135,
+    InterpolatedString = 136,
+}
 
-    // Add scientific notation handling (e & p).
+impl TokenKind {
+    /// Every kind, in discriminant order.
+    pub const ALL: [TokenKind; 65] = [
+        TokenKind::And,
+        TokenKind::Break,
+        TokenKind::Continue,
+        TokenKind::Do,
+        TokenKind::Else,
+        TokenKind::Elseif,
+        TokenKind::End,
+        TokenKind::False,
+        TokenKind::For,
+        TokenKind::Function,
+        TokenKind::Goto,
+        TokenKind::If,
+        TokenKind::In,
+        TokenKind::Local,
+        TokenKind::Nil,
+        TokenKind::Not,
+        TokenKind::Or,
+        TokenKind::Repeat,
+        TokenKind::Return,
+        TokenKind::Then,
+        TokenKind::True,
+        TokenKind::Until,
+        TokenKind::While,
+        TokenKind::Plus,
+        TokenKind::Minus,
+        TokenKind::Multiply,
+        TokenKind::Divide,
+        TokenKind::FloorDivide,
+        TokenKind::Modulus,
+        TokenKind::Exponent,
+        TokenKind::Ampersand,
+        TokenKind::Pipe,
+        TokenKind::Tilde,
+        TokenKind::ShiftLeft,
+        TokenKind::ShiftRight,
+        TokenKind::DoubleEqual,
+        TokenKind::Equal,
+        TokenKind::NotEqual,
+        TokenKind::Greater,
+        TokenKind::Less,
+        TokenKind::GreaterEqual,
+        TokenKind::LessEqual,
+        TokenKind::Dot,
+        TokenKind::Concatenate,
+        TokenKind::Ellipsis,
+        TokenKind::Length,
+        TokenKind::LBracket,
+        TokenKind::RBracket,
+        TokenKind::LParen,
+        TokenKind::RParen,
+        TokenKind::LBrace,
+        TokenKind::RBrace,
+        TokenKind::Comma,
+        TokenKind::Semicolon,
+        TokenKind::Colon,
+        TokenKind::DoubleColon,
+        TokenKind::Comment,
+        TokenKind::Identifier,
+        TokenKind::String,
+        TokenKind::LongString,
+        TokenKind::Integer,
+        TokenKind::Float,
+        TokenKind::CompoundAssign,
+        TokenKind::Annotation,
+        TokenKind::InterpolatedString,
+    ];
+
+    /// This kind's stable discriminant.
+    pub fn as_u16(self) -> u16 {
+        self as u16
+    }
 
-    let as_int: Result<i64, lexical_core::Error> = lexical_core::parse(s.as_bytes());
+    /// The kind with discriminant `value`, if there is one.
+    pub fn from_u16(value: u16) -> Option<TokenKind> {
+        Some(match value {
+            0 => TokenKind::And,
+            1 => TokenKind::Break,
+            2 => TokenKind::Continue,
+            3 => TokenKind::Do,
+            4 => TokenKind::Else,
+            5 => TokenKind::Elseif,
+            6 => TokenKind::End,
+            7 => TokenKind::False,
+            8 => TokenKind::For,
+            9 => TokenKind::Function,
+            10 => TokenKind::Goto,
+            11 => TokenKind::If,
+            12 => TokenKind::In,
+            13 => TokenKind::Local,
+            14 => TokenKind::Nil,
+            15 => TokenKind::Not,
+            16 => TokenKind::Or,
+            17 => TokenKind::Repeat,
+            18 => TokenKind::Return,
+            19 => TokenKind::Then,
+            20 => TokenKind::True,
+            21 => TokenKind::Until,
+            22 => TokenKind::While,
+            64 => TokenKind::Plus,
+            65 => TokenKind::Minus,
+            66 => TokenKind::Multiply,
+            67 => TokenKind::Divide,
+            68 => TokenKind::FloorDivide,
+            69 => TokenKind::Modulus,
+            70 => TokenKind::Exponent,
+            71 => TokenKind::Ampersand,
+            72 => TokenKind::Pipe,
+            73 => TokenKind::Tilde,
+            74 => TokenKind::ShiftLeft,
+            75 => TokenKind::ShiftRight,
+            76 => TokenKind::DoubleEqual,
+            77 => TokenKind::Equal,
+            78 => TokenKind::NotEqual,
+            79 => TokenKind::Greater,
+            80 => TokenKind::Less,
+            81 => TokenKind::GreaterEqual,
+            82 => TokenKind::LessEqual,
+            83 => TokenKind::Dot,
+            84 => TokenKind::Concatenate,
+            85 => TokenKind::Ellipsis,
+            86 => TokenKind::Length,
+            87 => TokenKind::LBracket,
+            88 => TokenKind::RBracket,
+            89 => TokenKind::LParen,
+            90 => TokenKind::RParen,
+            91 => TokenKind::LBrace,
+            92 => TokenKind::RBrace,
+            93 => TokenKind::Comma,
+            94 => TokenKind::Semicolon,
+            95 => TokenKind::Colon,
+            96 => TokenKind::DoubleColon,
+            128 => TokenKind::Comment,
+            129 => TokenKind::Identifier,
+            130 => TokenKind::String,
+            131 => TokenKind::LongString,
+            132 => TokenKind::Integer,
+            133 => TokenKind::Float,
+            134 => TokenKind::CompoundAssign,
+            135 => TokenKind::Annotation,
+            136 => TokenKind::InterpolatedString,
+            _ => return None,
+        })
+    }
 
-    match as_int {
-        Ok(val) => Some(val),
-        Err(_) => None,
+    /// Source spelling of a keyword or operator kind, `None` for kinds carrying a payload.
+    pub fn text(self) -> Option<&'static str> {
+        Some(match self {
+            TokenKind::And => "and",
+            TokenKind::Break => "break",
+            TokenKind::Continue => "continue",
+            TokenKind::Do => "do",
+            TokenKind::Else => "else",
+            TokenKind::Elseif => "elseif",
+            TokenKind::End => "end",
+            TokenKind::False => "false",
+            TokenKind::For => "for",
+            TokenKind::Function => "function",
+            TokenKind::Goto => "goto",
+            TokenKind::If => "if",
+            TokenKind::In => "in",
+            TokenKind::Local => "local",
+            TokenKind::Nil => "nil",
+            TokenKind::Not => "not",
+            TokenKind::Or => "or",
+            TokenKind::Repeat => "repeat",
+            TokenKind::Return => "return",
+            TokenKind::Then => "then",
+            TokenKind::True => "true",
+            TokenKind::Until => "until",
+            TokenKind::While => "while",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Multiply => "*",
+            TokenKind::Divide => "/",
+            TokenKind::FloorDivide => "//",
+            TokenKind::Modulus => "%",
+            TokenKind::Exponent => "^",
+            TokenKind::Ampersand => "&",
+            TokenKind::Pipe => "|",
+            TokenKind::Tilde => "~",
+            TokenKind::ShiftLeft => "<<",
+            TokenKind::ShiftRight => ">>",
+            TokenKind::DoubleEqual => "==",
+            TokenKind::Equal => "=",
+            TokenKind::NotEqual => "~=",
+            TokenKind::Greater => ">",
+            TokenKind::Less => "<",
+            TokenKind::GreaterEqual => ">=",
+            TokenKind::LessEqual => "<=",
+            TokenKind::Dot => ".",
+            TokenKind::Concatenate => "..",
+            TokenKind::Ellipsis => "...",
+            TokenKind::Length => "#",
+            TokenKind::LBracket => "[",
+            TokenKind::RBracket => "]",
+            TokenKind::LParen => "(",
+            TokenKind::RParen => ")",
+            TokenKind::LBrace => "{",
+            TokenKind::RBrace => "}",
+            TokenKind::Comma => ",",
+            TokenKind::Semicolon => ";",
+            TokenKind::Colon => ":",
+            TokenKind::DoubleColon => "::",
+            TokenKind::Comment
+            | TokenKind::Identifier
+            | TokenKind::String
+            | TokenKind::LongString
+            | TokenKind::Integer
+            | TokenKind::Float
+            | TokenKind::CompoundAssign
+            | TokenKind::Annotation
+            | TokenKind::InterpolatedString => return None,
+        })
     }
+}
 
+/// The flavour of Lua being read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Dialect {
+    /// Lua 5.1, where `goto` is an ordinary name and `//`, bitwise operators
+    /// and `::` labels are errors.
+    Lua51,
+    /// Reference Lua 5.4.
+    #[default]
+    Lua54,
+    /// Roblox's Luau, which adds type annotations, compound assignment and
+    /// `@` attributes.
+    Luau,
 }
 
-fn as_float<'source>(text: &mut Lexer<'source, LuaToken<'source>>) -> Option<f64> {
+/// Lexer state threaded through logos callbacks.
+#[derive(Debug, Clone, Default)]
+pub struct LexExtras {
+    /// Emit comments as [`LuaToken::Comment`] rather than skipping them.
+    pub retain_comments: bool,
+    /// Lex `3abc` as `3` then `abc` instead of failing on a malformed number,
+    /// recording the would-be error in `notes`.
+    pub lenient_numbers: bool,
+    /// Errors a lenient mode downgraded, in source order.
+    pub notes: Vec<LexError>,
+    pub dialect: Dialect,
+    /// Longest identifier, long string or long comment allowed, in bytes, so
+    /// hostile input can't make the lexer scan or hold an unbounded token.
+    /// `None`, the default, sets no limit.
+    pub max_token_len: Option<usize>,
+}
+
+impl<'source> LuaToken<'source> {
+    /// Lex raw bytes, for sources that aren't valid UTF-8.
+    pub fn lexer_bytes(source: &'source [u8]) -> Lexer<'source, ByteToken<'source>> {
+        LexerBuilder::new().build_bytes(source)
+    }
 
-    let s= text.slice().trim_start_matches("0x").trim_start_matches("0X").replace("_","");
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            LuaToken::And => TokenKind::And,
+            LuaToken::Break => TokenKind::Break,
+            LuaToken::Continue => TokenKind::Continue,
+            LuaToken::Do => TokenKind::Do,
+            LuaToken::Else => TokenKind::Else,
+            LuaToken::Elseif => TokenKind::Elseif,
+            LuaToken::End => TokenKind::End,
+            LuaToken::False => TokenKind::False,
+            LuaToken::For => TokenKind::For,
+            LuaToken::Function => TokenKind::Function,
+            LuaToken::Goto => TokenKind::Goto,
+            LuaToken::If => TokenKind::If,
+            LuaToken::In => TokenKind::In,
+            LuaToken::Local => TokenKind::Local,
+            LuaToken::Nil => TokenKind::Nil,
+            LuaToken::Not => TokenKind::Not,
+            LuaToken::Or => TokenKind::Or,
+            LuaToken::Repeat => TokenKind::Repeat,
+            LuaToken::Return => TokenKind::Return,
+            LuaToken::Then => TokenKind::Then,
+            LuaToken::True => TokenKind::True,
+            LuaToken::Until => TokenKind::Until,
+            LuaToken::While => TokenKind::While,
+            LuaToken::Plus => TokenKind::Plus,
+            LuaToken::Minus => TokenKind::Minus,
+            LuaToken::Multiply => TokenKind::Multiply,
+            LuaToken::Divide => TokenKind::Divide,
+            LuaToken::FloorDivide => TokenKind::FloorDivide,
+            LuaToken::Modulus => TokenKind::Modulus,
+            LuaToken::Exponent => TokenKind::Exponent,
+            LuaToken::Ampersand => TokenKind::Ampersand,
+            LuaToken::Pipe => TokenKind::Pipe,
+            LuaToken::Tilde => TokenKind::Tilde,
+            LuaToken::ShiftLeft => TokenKind::ShiftLeft,
+            LuaToken::ShiftRight => TokenKind::ShiftRight,
+            LuaToken::DoubleEqual => TokenKind::DoubleEqual,
+            LuaToken::Equal => TokenKind::Equal,
+            LuaToken::NotEqual => TokenKind::NotEqual,
+            LuaToken::Greater => TokenKind::Greater,
+            LuaToken::Less => TokenKind::Less,
+            LuaToken::GreaterEqual => TokenKind::GreaterEqual,
+            LuaToken::LessEqual => TokenKind::LessEqual,
+            LuaToken::Dot => TokenKind::Dot,
+            LuaToken::Concatenate => TokenKind::Concatenate,
+            LuaToken::Ellipsis => TokenKind::Ellipsis,
+            LuaToken::Length => TokenKind::Length,
+            LuaToken::LBracket => TokenKind::LBracket,
+            LuaToken::RBracket => TokenKind::RBracket,
+            LuaToken::LParen => TokenKind::LParen,
+            LuaToken::RParen => TokenKind::RParen,
+            LuaToken::LBrace => TokenKind::LBrace,
+            LuaToken::RBrace => TokenKind::RBrace,
+            LuaToken::Comma => TokenKind::Comma,
+            LuaToken::Semicolon => TokenKind::Semicolon,
+            LuaToken::Colon => TokenKind::Colon,
+            LuaToken::DoubleColon => TokenKind::DoubleColon,
+            LuaToken::Comment(_) => TokenKind::Comment,
+            LuaToken::Identifier(_) => TokenKind::Identifier,
+            LuaToken::String(_) => TokenKind::String,
+            LuaToken::LongString(_) => TokenKind::LongString,
+            LuaToken::Integer(_) | LuaToken::DecimalInteger => TokenKind::Integer,
+            LuaToken::Float(_) => TokenKind::Float,
+            LuaToken::CompoundAssign(_) => TokenKind::CompoundAssign,
+            LuaToken::Annotation(_) => TokenKind::Annotation,
+            LuaToken::InterpolatedString(_) => TokenKind::InterpolatedString,
+        }
+    }
+
+    /// Binding precedence of an operator token, higher binds tighter.
+    ///
+    /// Tokens that are both binary and unary (`-`, `~`) report their binary
+    /// precedence, unary-only tokens (`not`, `#`) report the unary precedence.
+    pub fn precedence(&self) -> Option<u8> {
+        match self {
+            LuaToken::Not | LuaToken::Length => Some(UNARY_PRECEDENCE),
+            _ => BinOp::from_token(self).map(BinOp::precedence),
+        }
+    }
+
+    /// `==`, `~=`, `<`, `>`, `<=` or `>=`.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            LuaToken::DoubleEqual
+                | LuaToken::NotEqual
+                | LuaToken::Less
+                | LuaToken::Greater
+                | LuaToken::LessEqual
+                | LuaToken::GreaterEqual
+        )
+    }
 
+    /// `+`, `-`, `*`, `/`, `//`, `%` or `^`, with `-` also unary negation.
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(
+            self,
+            LuaToken::Plus
+                | LuaToken::Minus
+                | LuaToken::Multiply
+                | LuaToken::Divide
+                | LuaToken::FloorDivide
+                | LuaToken::Modulus
+                | LuaToken::Exponent
+        )
+    }
+
+    /// `&`, `|`, `~`, `<<` or `>>`, with `~` also unary bitwise not.
+    pub fn is_bitwise(&self) -> bool {
+        matches!(
+            self,
+            LuaToken::Ampersand | LuaToken::Pipe | LuaToken::Tilde | LuaToken::ShiftLeft | LuaToken::ShiftRight
+        )
+    }
+
+    /// The keyword operators `and`, `or` and `not`.
+    pub fn is_logical(&self) -> bool {
+        matches!(self, LuaToken::And | LuaToken::Or | LuaToken::Not)
+    }
+
+    pub fn as_identifier(&self) -> Option<&'source str> {
+        match self {
+            LuaToken::Identifier(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The raw body of a short string or the contents of a long one.
+    pub fn as_string(&self) -> Option<&'source str> {
+        match self {
+            LuaToken::String(value) | LuaToken::LongString(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            LuaToken::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            LuaToken::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// How documentation tools like LDoc read this comment, `None` for other tokens.
+    pub fn comment_kind(&self) -> Option<CommentKind> {
+        let LuaToken::Comment(text) = self else {
+            return None;
+        };
+        let body = &text[2..];
+        Some(match long_bracket_level(body) {
+            Some(level) if body[level + 2..].starts_with("--") => CommentKind::Doc,
+            Some(_) => CommentKind::Block,
+            None if body.starts_with('-') && !body[1..].starts_with('-') => CommentKind::Doc,
+            None => CommentKind::Line,
+        })
+    }
+}
+
+/// Compares the kind only, so `LuaToken::Identifier("x") == TokenKind::Identifier`.
+impl PartialEq<TokenKind> for LuaToken<'_> {
+    fn eq(&self, kind: &TokenKind) -> bool {
+        self.kind() == *kind
+    }
+}
+
+impl PartialEq<LuaToken<'_>> for TokenKind {
+    fn eq(&self, token: &LuaToken<'_>) -> bool {
+        *self == token.kind()
+    }
+}
+
+/// The flavour of a retained comment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommentKind {
+    /// `-- text`, running to the end of the line.
+    Line,
+    /// `--[[ text ]]`, at any bracket level.
+    Block,
+    /// Documentation: a `---` line comment or a `--[[--` block comment.
+    Doc,
+}
+
+/// Lex `source` with comments emitted as [`LuaToken::Comment`] tokens.
+pub fn lexer_with_comments(source: &str) -> Lexer<'_, LuaToken<'_>> {
+    LexerBuilder::new().retain_comments(true).build(source)
+}
+
+/// Lexer options in one place, [`LexerBuilder::default`] lexing reference Lua 5.4.
+#[derive(Debug, Clone, Default)]
+pub struct LexerBuilder {
+    extras: LexExtras,
+}
+
+impl LexerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.extras.dialect = dialect;
+        self
+    }
+
+    /// See [`LexExtras::retain_comments`].
+    pub fn retain_comments(mut self, retain: bool) -> Self {
+        self.extras.retain_comments = retain;
+        self
+    }
+
+    /// See [`LexExtras::lenient_numbers`].
+    pub fn lenient_numbers(mut self, lenient: bool) -> Self {
+        self.extras.lenient_numbers = lenient;
+        self
+    }
+
+    /// See [`LexExtras::max_token_len`].
+    pub fn max_token_len(mut self, max: Option<usize>) -> Self {
+        self.extras.max_token_len = max;
+        self
+    }
+
+    /// Start lexing `source`, skipping a UTF-8 byte order mark at its start.
+    /// Spans stay offsets into `source`, BOM included.
+    pub fn build<'source>(&self, source: &'source str) -> Lexer<'source, LuaToken<'source>> {
+        let mut lexer = LuaToken::lexer_with_extras(source, self.extras.clone());
+        if source.starts_with('\u{FEFF}') {
+            lexer.bump('\u{FEFF}'.len_utf8());
+        }
+        lexer
+    }
+
+    /// Start lexing raw bytes like [`LexerBuilder::build`], for sources that
+    /// aren't valid UTF-8.
+    pub fn build_bytes<'source>(&self, source: &'source [u8]) -> Lexer<'source, ByteToken<'source>> {
+        let mut lexer = ByteToken::lexer_with_extras(source, self.extras.clone());
+        if source.starts_with("\u{FEFF}".as_bytes()) {
+            lexer.bump('\u{FEFF}'.len_utf8());
+        }
+        lexer
+    }
+
+    /// Lex `source` into spanned tokens, see [`SpannedTokens`].
+    pub fn spanned<'source>(&self, source: &'source str, tolerant: bool) -> SpannedTokens<'source> {
+        SpannedTokens::new(source, self.build(source), tolerant)
+    }
+
+    /// Lex all of `source` like [`tokenize`], with these options.
+    pub fn tokenize<'source>(&self, source: &'source str) -> Result<Vec<(LuaToken<'source>, Span)>, LexError> {
+        self.spanned(source, false).collect()
+    }
+
+    /// Lex all of `source` like [`tokenize_recovering`], with these options.
+    pub fn tokenize_recovering<'source>(&self, source: &'source str) -> (Vec<(LuaToken<'source>, Span)>, Vec<LexError>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in self.spanned(source, true) {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(err) => errors.push(err),
+            }
+        }
+        (tokens, errors)
+    }
+}
+
+/// The variants callbacks choose between, so [`LuaToken`] and [`ByteToken`] can
+/// share them.
+trait CallbackToken<'s>: Logos<'s, Extras = LexExtras, Error = LexError> {
+    fn integer(value: i64) -> Self;
+    fn float(value: f64) -> Self;
+    fn identifier(name: <Self::Source as Source>::Slice<'s>) -> Self;
+    fn goto() -> Self;
+    /// The token for `op`, one of the operators with a Luau `op=` form.
+    fn operator(op: BinOp) -> Self;
+    fn compound_assign(op: BinOp) -> Self;
+    fn is_lbrace(&self) -> bool;
+    fn is_rbrace(&self) -> bool;
+}
+
+impl<'s> CallbackToken<'s> for LuaToken<'s> {
+    fn integer(value: i64) -> Self {
+        LuaToken::Integer(value)
+    }
+
+    fn float(value: f64) -> Self {
+        LuaToken::Float(value)
+    }
+
+    fn identifier(name: &'s str) -> Self {
+        LuaToken::Identifier(name)
+    }
+
+    fn goto() -> Self {
+        LuaToken::Goto
+    }
+
+    fn operator(op: BinOp) -> Self {
+        match op {
+            BinOp::Add => LuaToken::Plus,
+            BinOp::Sub => LuaToken::Minus,
+            BinOp::Mul => LuaToken::Multiply,
+            BinOp::Div => LuaToken::Divide,
+            BinOp::FloorDiv => LuaToken::FloorDivide,
+            BinOp::Mod => LuaToken::Modulus,
+            BinOp::Pow => LuaToken::Exponent,
+            _ => LuaToken::Concatenate,
+        }
+    }
+
+    fn compound_assign(op: BinOp) -> Self {
+        LuaToken::CompoundAssign(op)
+    }
+
+    fn is_lbrace(&self) -> bool {
+        *self == LuaToken::LBrace
+    }
+
+    fn is_rbrace(&self) -> bool {
+        *self == LuaToken::RBrace
+    }
+}
+
+fn goto_keyword<'s, T>(text: &mut Lexer<'s, T>) -> T
+where
+    T: CallbackToken<'s>,
+{
+    match text.extras.dialect {
+        Dialect::Lua51 => T::identifier(text.slice()),
+        Dialect::Lua54 | Dialect::Luau => T::goto(),
+    }
+}
+
+/// The operator just matched, or in Luau the compound assignment if `=` follows.
+fn compound_assign<'s, T>(text: &mut Lexer<'s, T>) -> Result<T, LexError>
+where
+    T: CallbackToken<'s>,
+    T::Source: LexSource,
+{
+    let op = match &text.source().as_bytes()[text.span()] {
+        b"+" => BinOp::Add,
+        b"-" => BinOp::Sub,
+        b"*" => BinOp::Mul,
+        b"/" => BinOp::Div,
+        b"//" => BinOp::FloorDiv,
+        b"%" => BinOp::Mod,
+        b"^" => BinOp::Pow,
+        _ => BinOp::Concat,
+    };
+    if op == BinOp::FloorDiv {
+        not_in_lua51(text, "floor division requires Lua 5.3+")?;
+    }
+    if text.extras.dialect == Dialect::Luau && remainder_bytes(text).starts_with(b"=") {
+        text.bump(1);
+        return Ok(T::compound_assign(op));
+    }
+    Ok(T::operator(op))
+}
+
+fn bitwise<'s, T>(text: &mut Lexer<'s, T>) -> Result<(), LexError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+{
+    not_in_lua51(text, "bitwise operators require Lua 5.3+")
+}
+
+fn label_delimiter<'s, T>(text: &mut Lexer<'s, T>) -> Result<(), LexError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+{
+    not_in_lua51(text, "labels require Lua 5.2+")
+}
+
+/// Reject the token just matched in Lua 5.1, which predates it.
+fn not_in_lua51<'s, T>(text: &mut Lexer<'s, T>, hint: &'static str) -> Result<(), LexError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+{
+    match text.extras.dialect {
+        Dialect::Lua51 => Err(LexError::NotInDialect { dialect: Dialect::Lua51, hint, span: text.span().into() }),
+        Dialect::Lua54 | Dialect::Luau => Ok(()),
+    }
+}
+
+/// In Luau, `@` and the name after it.
+fn annotation<'s, T>(text: &mut Lexer<'s, T>) -> Result<<T::Source as Source>::Slice<'s>, LexError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+    T::Source: LexSource,
+{
+    let rest = remainder_bytes(text);
+    if text.extras.dialect != Dialect::Luau || !rest.first().is_some_and(|&b| b.is_ascii_alphabetic() || b == b'_') {
+        return Err(unexpected_token(text));
+    }
+    text.bump(rest.iter().take_while(|b| b.is_ascii_alphanumeric() || **b == b'_').count());
+    Ok(source_slice(text, text.span().start + 1, text.span().end))
+}
+
+/// In Luau, a backtick string up to its closing backtick. Like a quoted string,
+/// one left open is an unexpected character.
+fn interpolated_string<'s, T>(text: &mut Lexer<'s, T>) -> Result<<T::Source as Source>::Slice<'s>, LexError>
+where
+    T: CallbackToken<'s>,
+    T::Source: LexSource,
+{
+    if text.extras.dialect != Dialect::Luau {
+        return Err(unexpected_token(text));
+    }
+    let start = text.span().end;
+    match interpolated_end::<T>(text.source(), start, &text.extras)? {
+        Some(end) => {
+            text.bump(end + 1 - start);
+            Ok(source_slice(text, start, end))
+        }
+        None => Err(unexpected_token(text)),
+    }
+}
+
+/// The offset of the backtick closing the interpolated string whose body
+/// starts at `start` in `source`, skipping escapes and `{expr}` holes. `None`
+/// if a line ends first.
+fn interpolated_end<'s, T>(source: &'s T::Source, start: usize, extras: &LexExtras) -> Result<Option<usize>, LexError>
+where
+    T: CallbackToken<'s>,
+    T::Source: LexSource,
+{
+    let bytes = source.as_bytes();
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'`' => return Ok(Some(i)),
+            b'\\' => i += 2,
+            b'\n' | b'\r' => return Ok(None),
+            b'{' => match hole_end::<T>(source, i + 1, extras)? {
+                Some(end) => i = end + 1,
+                None => return Ok(None),
+            },
+            _ => i += 1,
+        }
+    }
+    Ok(None)
+}
+
+/// The offset of the `}` closing the interpolation hole `rest` starts in, just
+/// past its `{`, lexing the expression so braces in tables and strings nest.
+pub(crate) fn interpolation_hole_end(rest: &str, extras: &LexExtras) -> Result<Option<usize>, LexError> {
+    hole_end::<LuaToken>(rest, 0, extras)
+}
+
+/// The offset of the `}` closing the interpolation hole starting at `start` in
+/// `source`, see [`interpolation_hole_end`].
+fn hole_end<'s, T>(source: &'s T::Source, start: usize, extras: &LexExtras) -> Result<Option<usize>, LexError>
+where
+    T: CallbackToken<'s>,
+    T::Source: LexSource,
+{
+    let mut lexer = T::lexer_with_extras(source, extras.clone());
+    lexer.bump(start);
+    let mut depth = 0usize;
+    while let Some(token) = lexer.next() {
+        match token {
+            Ok(token) if token.is_lbrace() => depth += 1,
+            Ok(token) if token.is_rbrace() && depth == 0 => return Ok(Some(lexer.span().start)),
+            Ok(token) if token.is_rbrace() => depth -= 1,
+            Ok(_) => {}
+            Err(LexError::UnexpectedChar { .. }) => return Err(unexpected_token(&lexer)),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(None)
+}
+
+/// The text or bytes a lexer reads, so [`LuaToken`] and [`ByteToken`] can
+/// share their callbacks.
+trait LexSource: Source {
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl LexSource for str {
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+}
+
+impl LexSource for [u8] {
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+/// The part of the source a lexer has yet to read, as bytes.
+fn remainder_bytes<'s, T>(text: &Lexer<'s, T>) -> &'s [u8]
+where
+    T: Logos<'s>,
+    T::Source: LexSource,
+{
+    &text.source().as_bytes()[text.span().end..]
+}
+
+/// The source from `start` to `end`, both offsets on ASCII bytes.
+fn source_slice<'s, T>(text: &Lexer<'s, T>, start: usize, end: usize) -> <T::Source as Source>::Slice<'s>
+where
+    T: Logos<'s>,
+    T::Source: LexSource,
+{
+    text.source().slice(start..end).expect("token bounds lie on ASCII bytes")
+}
+
+fn comment<'s, T>(text: &mut Lexer<'s, T>) -> FilterResult<<T::Source as Source>::Slice<'s>, LexError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+    T::Source: LexSource,
+{
+    let rest = remainder_bytes(text);
+    let len = match long_bracket_level(rest) {
+        Some(level) => match bounded_bracket_end(text, level) {
+            Ok(end) => end,
+            Err(LongBracketError::Unterminated) => {
+                text.bump(rest.len());
+                return FilterResult::Error(LexError::UnterminatedComment { span: text.span().into() });
+            }
+            Err(LongBracketError::TooLong(err)) => return FilterResult::Error(err),
+        },
+        None => rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len()),
+    };
+    text.bump(len);
+
+    if text.extras.retain_comments {
+        FilterResult::Emit(text.slice())
+    } else {
+        FilterResult::Skip
+    }
+}
+
+fn long_string<'s, T>(text: &mut Lexer<'s, T>) -> Result<<T::Source as Source>::Slice<'s>, LexError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+    T::Source: LexSource,
+{
+    let level = text.span().len() - 2;
+    let rest = remainder_bytes(text);
+    let end = match bounded_bracket_end(text, level) {
+        Ok(end) => end,
+        Err(LongBracketError::Unterminated) => {
+            text.bump(rest.len());
+            return Err(LexError::UnterminatedString { span: text.span().into() });
+        }
+        Err(LongBracketError::TooLong(err)) => return Err(err),
+    };
+    let start = text.span().end;
+    text.bump(end);
+    let contents = &rest[..end - level - 2];
+    let newline = [&b"\r\n"[..], b"\n\r", b"\n", b"\r"].into_iter().find(|newline| contents.starts_with(newline));
+    Ok(source_slice(text, start + newline.map_or(0, <[u8]>::len), start + contents.len()))
+}
+
+enum LongBracketError {
+    Unterminated,
+    TooLong(LexError),
+}
+
+/// Find the close of a long bracket of `level` in the remainder, like
+/// [`long_bracket_end`], but only looking as far as [`LexExtras::max_token_len`]
+/// allows. Past the limit, the rest of the source is skipped.
+fn bounded_bracket_end<'s, T>(text: &mut Lexer<'s, T>, level: usize) -> Result<usize, LongBracketError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+    T::Source: LexSource,
+{
+    let rest = remainder_bytes(text);
+    let Some(max) = text.extras.max_token_len else {
+        return long_bracket_end(rest, level).ok_or(LongBracketError::Unterminated);
+    };
+    let start = text.span().start;
+    let window = max.saturating_sub(text.span().len()).min(rest.len());
+    match long_bracket_end(&rest[..window], level) {
+        Some(end) => Ok(end),
+        None if window == rest.len() => Err(LongBracketError::Unterminated),
+        None => {
+            text.bump(rest.len());
+            Err(LongBracketError::TooLong(LexError::TokenTooLong { span: Span::new(start, start + max) }))
+        }
+    }
+}
+
+fn identifier<'s, T>(text: &mut Lexer<'s, T>) -> Result<<T::Source as Source>::Slice<'s>, LexError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+    T::Source: LexSource,
+{
+    match text.extras.max_token_len {
+        Some(max) if text.span().len() > max => {
+            let start = text.span().start;
+            Err(LexError::TokenTooLong { span: Span::new(start, start + max) })
+        }
+        _ => Ok(text.slice()),
+    }
+}
+
+/// Level of a long bracket opening `text`, `[[` is level 0 and `[==[` level 2.
+pub(crate) fn long_bracket_level(text: &(impl AsRef<[u8]> + ?Sized)) -> Option<usize> {
+    let text = text.as_ref();
+    let level = text.strip_prefix(b"[")?.iter().take_while(|&&b| b == b'=').count();
+    (text.get(level + 1) == Some(&b'[')).then_some(level)
+}
+
+/// Offset just past the `]=*]` closing a long bracket of `level` in `rest`.
+pub(crate) fn long_bracket_end(rest: &(impl AsRef<[u8]> + ?Sized), level: usize) -> Option<usize> {
+    let close = format!("]{}]", "=".repeat(level));
+    rest.as_ref().windows(close.len()).position(|window| window == close.as_bytes()).map(|end| end + close.len())
+}
+
+fn as_int<'s, T>(text: &mut Lexer<'s, T>) -> Result<i64, LexError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+    T::Source: LexSource,
+{
+    check_number_end(text)?;
+    parse_int(&text.source().as_bytes()[text.span()]).ok_or(LexError::InvalidNumber { span: text.span().into() })
+}
+
+fn as_float<'s, T>(text: &mut Lexer<'s, T>) -> Result<f64, LexError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+    T::Source: LexSource,
+{
+    check_number_end(text)?;
+    parse_float(&text.source().as_bytes()[text.span()]).ok_or(LexError::InvalidNumber { span: text.span().into() })
+}
+
+/// A decimal integer, or the float it reads as if it's too big for 64 bits.
+fn decimal_integer<'s, T>(text: &mut Lexer<'s, T>) -> Result<T, LexError>
+where
+    T: CallbackToken<'s>,
+    T::Source: LexSource,
+{
+    check_number_end(text)?;
+    let slice = &text.source().as_bytes()[text.span()];
+    match parse_int(slice) {
+        Some(value) => Ok(T::integer(value)),
+        None => parse_float(slice).map(T::float).ok_or(LexError::InvalidNumber { span: text.span().into() }),
+    }
+}
+
+fn check_number_end<'s, T>(text: &mut Lexer<'s, T>) -> Result<(), LexError>
+where
+    T: Logos<'s, Extras = LexExtras>,
+    T::Source: LexSource,
+{
+    let run = malformed_run(remainder_bytes(text));
+    if run == 0 {
+        return Ok(());
+    }
+    let span = Span::new(text.span().start, text.span().end + run);
+    if text.extras.lenient_numbers {
+        text.extras.notes.push(LexError::InvalidNumber { span });
+        return Ok(());
+    }
+    text.bump(run);
+    Err(LexError::InvalidNumber { span })
+}
+
+/// Strip the hex prefix and digit separators from a numeral.
+fn number_digits(slice: &[u8]) -> Vec<u8> {
+    let slice = slice.strip_prefix(b"0x").or_else(|| slice.strip_prefix(b"0X")).unwrap_or(slice);
+    slice.iter().copied().filter(|&b| b != b'_').collect()
+}
+
+pub(crate) fn parse_int(slice: &[u8]) -> Option<i64> {
+    if let Some(hex) = slice.strip_prefix(b"0x").or_else(|| slice.strip_prefix(b"0X")) {
+        // As in reference Lua, a hex integer too big for 64 bits wraps around.
+        return hex.iter().filter(|&&b| b != b'_').try_fold(0i64, |value, &b| {
+            let digit = (b as char).to_digit(16)?;
+            Some(value.wrapping_mul(16).wrapping_add(digit.into()))
+        });
+    }
     // Add scientific notation handling (e & p).
+    lexical_core::parse(&number_digits(slice)).ok()
+}
 
-    let as_float: Result<f64, lexical_core::Error> = lexical_core::parse(s.as_bytes());
+pub(crate) fn parse_float(slice: &[u8]) -> Option<f64> {
+    if slice.starts_with(b"0x") || slice.starts_with(b"0X") {
+        return parse_hex_float(&number_digits(slice));
+    }
+    lexical_core::parse(&number_digits(slice)).ok()
+}
 
-    match as_float {
-        Ok(val) => Some(val),
-        Err(_) => None,
+/// The value of hex float digits like `A.8p1`: a hex mantissa, optionally
+/// scaled by a `p` exponent, a power of two written in decimal.
+fn parse_hex_float(digits: &[u8]) -> Option<f64> {
+    let (mantissa, mut exponent) = match digits.iter().position(|&b| b == b'p' || b == b'P') {
+        Some(p) => (&digits[..p], std::str::from_utf8(&digits[p + 1..]).ok()?.parse::<i32>().ok()?),
+        None => (digits, 0),
+    };
+    let mut value = 0.0;
+    let mut fraction = false;
+    for &b in mantissa {
+        if b == b'.' {
+            fraction = true;
+            continue;
+        }
+        value = value * 16.0 + f64::from((b as char).to_digit(16)?);
+        if fraction {
+            exponent = exponent.saturating_sub(4);
+        }
     }
+    Some(value * 2f64.powi(exponent))
+}
+
+/// Reference Lua reads a numeral greedily, so a literal running straight into more
+/// digits, letters or dots (`1.2.3`, `1e2e3`) is one malformed number, not several tokens.
+///
+/// Returns the length of that trailing run in `rest`, zero if the literal ends cleanly.
+pub(crate) fn malformed_run(rest: &[u8]) -> usize {
+    rest.iter()
+        .position(|&b| !(b.is_ascii_alphanumeric() || b == b'_' || b == b'.'))
+        .unwrap_or(rest.len())
+}
+
+/// Errors produced while lexing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    /// A character that doesn't begin any Lua token, like `$` or `?`. This
+    /// includes a NUL byte outside a string, which is an error rather than
+    /// whitespace so that binary data passed as source is caught early. The
+    /// byte lexer reports a byte that isn't UTF-8 as U+FFFD.
+    UnexpectedChar { ch: char, span: Span },
+    /// A numeric literal that can't be read, e.g. `1.2.3`.
+    InvalidNumber { span: Span },
+    /// A long comment missing its closing bracket.
+    UnterminatedComment { span: Span },
+    /// A long string missing its closing bracket.
+    UnterminatedString { span: Span },
+    /// An identifier, long string or long comment longer than
+    /// [`LexExtras::max_token_len`]. The span covers the allowed length.
+    TokenTooLong { span: Span },
+    /// Syntax `dialect` lacks, e.g. `//` in Lua 5.1, with a `hint` naming the
+    /// version that added it.
+    NotInDialect { dialect: Dialect, hint: &'static str, span: Span },
+}
+
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnexpectedChar { span, .. }
+            | LexError::InvalidNumber { span }
+            | LexError::UnterminatedComment { span }
+            | LexError::UnterminatedString { span }
+            | LexError::TokenTooLong { span }
+            | LexError::NotInDialect { span, .. } => *span,
+        }
+    }
+
+    /// The dialect that rejected the input, when that's why it failed.
+    pub fn dialect(&self) -> Option<Dialect> {
+        match self {
+            LexError::NotInDialect { dialect, .. } => Some(*dialect),
+            _ => None,
+        }
+    }
+}
+
+impl LexError {
+    /// The same error with its span moved `offset` bytes later.
+    pub(crate) fn offset_by(self, offset: usize) -> LexError {
+        let shift = |span: Span| Span::new(span.start + offset, span.end + offset);
+        match self {
+            LexError::UnexpectedChar { ch, span } => LexError::UnexpectedChar { ch, span: shift(span) },
+            LexError::InvalidNumber { span } => LexError::InvalidNumber { span: shift(span) },
+            LexError::UnterminatedComment { span } => LexError::UnterminatedComment { span: shift(span) },
+            LexError::UnterminatedString { span } => LexError::UnterminatedString { span: shift(span) },
+            LexError::TokenTooLong { span } => LexError::TokenTooLong { span: shift(span) },
+            LexError::NotInDialect { dialect, hint, span } => LexError::NotInDialect { dialect, hint, span: shift(span) },
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::UnexpectedChar { ch, .. } => write!(f, "unexpected character {ch:?}"),
+            LexError::InvalidNumber { .. } => write!(f, "malformed number"),
+            LexError::UnterminatedComment { .. } => write!(f, "unfinished long comment"),
+            LexError::UnterminatedString { .. } => write!(f, "unfinished long string"),
+            LexError::TokenTooLong { .. } => write!(f, "token too long"),
+            LexError::NotInDialect { hint, .. } => f.write_str(hint),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Why a string isn't exactly one token, see `LuaToken::try_from`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SingleTokenError {
+    Lex(LexError),
+    /// Nothing but whitespace or comments.
+    Empty,
+    /// A second token, at `span`, after the first.
+    Trailing { span: Span },
+}
+
+impl fmt::Display for SingleTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SingleTokenError::Lex(err) => err.fmt(f),
+            SingleTokenError::Empty => write!(f, "expected a token"),
+            SingleTokenError::Trailing { .. } => write!(f, "expected a single token"),
+        }
+    }
+}
+
+impl std::error::Error for SingleTokenError {}
+
+/// Lex `source` as exactly one token, e.g. `"+"` as [`LuaToken::Plus`].
+impl<'source> TryFrom<&'source str> for LuaToken<'source> {
+    type Error = SingleTokenError;
+
+    fn try_from(source: &'source str) -> Result<Self, Self::Error> {
+        let mut tokens = LexerBuilder::new().spanned(source, false);
+        let (token, _) = tokens.next().ok_or(SingleTokenError::Empty)?.map_err(SingleTokenError::Lex)?;
+        match tokens.next() {
+            None => Ok(token),
+            Some(Ok((_, span))) => Err(SingleTokenError::Trailing { span }),
+            Some(Err(err)) => Err(SingleTokenError::Lex(err)),
+        }
+    }
+}
+
+/// Logos requires a default error for unmatched input. It is only a placeholder,
+/// `tokenize` and friends replace it using [`unexpected_char`].
+impl Default for LexError {
+    fn default() -> Self {
+        LexError::UnexpectedChar { ch: '\0', span: Span::default() }
+    }
+}
+
+/// The precise error for unmatched input starting at `offset`.
+pub fn unexpected_char(source: &str, offset: usize) -> LexError {
+    unexpected_byte(source.as_bytes(), offset)
+}
+
+/// Like [`unexpected_char`] for raw bytes, where a byte that starts no UTF-8
+/// character is reported as U+FFFD.
+fn unexpected_byte(source: &[u8], offset: usize) -> LexError {
+    let (ch, len) = match source[offset..].utf8_chunks().next() {
+        Some(chunk) => chunk.valid().chars().next().map_or((char::REPLACEMENT_CHARACTER, 1), |ch| (ch, ch.len_utf8())),
+        None => ('\0', 1),
+    };
+    LexError::UnexpectedChar { ch, span: Span::new(offset, offset + len) }
+}
+
+/// The precise error for the unmatched input the lexer stopped at, the error
+/// callback of [`ByteToken`].
+fn unexpected_token<'s, T>(text: &Lexer<'s, T>) -> LexError
+where
+    T: Logos<'s>,
+    T::Source: LexSource,
+{
+    unexpected_byte(text.source().as_bytes(), text.span().start)
+}
+
+/// Lex all of `source`, stopping at the first error.
+pub fn tokenize(source: &str) -> Result<Vec<(LuaToken<'_>, Span)>, LexError> {
+    LexerBuilder::new().tokenize(source)
+}
+
+/// Call `f` on each token of `source` in turn without collecting them, until
+/// it breaks or the first lex error. Returns the value `f` broke with, if any.
+pub fn walk_tokens<'source, B>(
+    source: &'source str,
+    mut f: impl FnMut(LuaToken<'source>, Span) -> ControlFlow<B>,
+) -> Result<Option<B>, LexError> {
+    for result in LexerBuilder::new().spanned(source, false) {
+        let (token, span) = result?;
+        if let ControlFlow::Break(value) = f(token, span) {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+/// Lex all of `source`, skipping over errors and collecting them separately.
+pub fn tokenize_recovering(source: &str) -> (Vec<(LuaToken<'_>, Span)>, Vec<LexError>) {
+    LexerBuilder::new().tokenize_recovering(source)
+}
+
+/// The tokens of a source, like the logos [`Lexer`] but with the
+/// [`LexError`] for a stray character naming it.
+///
+/// Carries on past errors. [`TokenIter::span`] gives the span of the last item.
+pub struct TokenIter<'source> {
+    lexer: Lexer<'source, LuaToken<'source>>,
+}
+
+impl<'source> TokenIter<'source> {
+    pub fn new(source: &'source str) -> Self {
+        TokenIter { lexer: LexerBuilder::new().build(source) }
+    }
+
+    /// Span of the token or error last returned.
+    pub fn span(&self) -> Span {
+        self.lexer.span().into()
+    }
+}
+
+impl<'source> From<Lexer<'source, LuaToken<'source>>> for TokenIter<'source> {
+    fn from(lexer: Lexer<'source, LuaToken<'source>>) -> Self {
+        TokenIter { lexer }
+    }
+}
+
+impl<'source> Iterator for TokenIter<'source> {
+    type Item = Result<LuaToken<'source>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.lexer.next()?.map_err(|err| match err {
+            LexError::UnexpectedChar { .. } => unexpected_char(self.lexer.source(), self.lexer.span().start),
+            err => err,
+        }))
+    }
+
+    /// At most one token per byte left, as every token or error takes at least one.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.lexer.remainder().len()))
+    }
+}
+
+/// Tokens of `source` with their spans, carrying on past errors.
+fn spanned(source: &str) -> SpannedTokens<'_> {
+    LexerBuilder::new().spanned(source, true)
+}
+
+/// Tokens with their spans, and errors with the character they stopped at.
+///
+/// A tolerant iterator yields each error and carries on after it, a strict one
+/// ends after its first error.
+pub struct SpannedTokens<'source> {
+    source: &'source str,
+    inner: logos::SpannedIter<'source, LuaToken<'source>>,
+    tolerant: bool,
+    failed: bool,
+}
+
+impl<'source> SpannedTokens<'source> {
+    pub fn new(source: &'source str, lexer: Lexer<'source, LuaToken<'source>>, tolerant: bool) -> Self {
+        SpannedTokens { source, inner: lexer.spanned(), tolerant, failed: false }
+    }
+}
+
+impl<'source> Iterator for SpannedTokens<'source> {
+    type Item = Result<(LuaToken<'source>, Span), LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let (token, range) = self.inner.next()?;
+        let item = match token {
+            Ok(token) => return Some(Ok((token, range.into()))),
+            Err(LexError::UnexpectedChar { .. }) => unexpected_char(self.source, range.start),
+            Err(err) => err,
+        };
+        self.failed = !self.tolerant;
+        Some(Err(item))
+    }
+
+    /// At most one token per byte left, as every token or error takes at least one.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(if self.failed { 0 } else { self.inner.remainder().len() }))
+    }
+}
+
+/// Why a set of token spans doesn't tile the source they were lexed from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoverageError {
+    /// A token starting before the previous one ended, or running past the source.
+    Overlap { span: Span },
+    /// Text between tokens that the lexer would not have skipped.
+    Gap { span: Span },
+}
+
+impl fmt::Display for CoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoverageError::Overlap { span } => write!(f, "token at {}..{} overlaps its neighbours", span.start, span.end),
+            CoverageError::Gap { span } => write!(f, "unlexed text at {}..{}", span.start, span.end),
+        }
+    }
+}
+
+impl std::error::Error for CoverageError {}
+
+/// Stitch the token slices of `source` and the whitespace and comments between
+/// them back together, checking that nothing else went missing.
+///
+/// For the tokens `tokenize` produced this returns `source` unchanged.
+pub fn reconstruct(source: &str, tokens: &[(LuaToken<'_>, Span)]) -> Result<String, CoverageError> {
+    let mut out = String::with_capacity(source.len());
+    let mut pos = 0;
+    for &(_, span) in tokens {
+        let Some(text) = source.get(span.range()).filter(|_| span.start >= pos) else {
+            return Err(CoverageError::Overlap { span });
+        };
+        push_gap(&mut out, source, Span::new(pos, span.start))?;
+        out.push_str(text);
+        pos = span.end;
+    }
+    push_gap(&mut out, source, Span::new(pos, source.len()))?;
+    Ok(out)
+}
+
+fn push_gap(out: &mut String, source: &str, span: Span) -> Result<(), CoverageError> {
+    let gap = &source[span.range()];
+    // Only a byte order mark at the very start of the source is trivia.
+    let mut lexer = LuaToken::lexer_with_extras(gap, LexExtras { retain_comments: true, ..LexExtras::default() });
+    if span.start == 0 && gap.starts_with('\u{FEFF}') {
+        lexer.bump('\u{FEFF}'.len_utf8());
+    }
+    if !lexer.all(|token| matches!(token, Ok(LuaToken::Comment(_)))) {
+        return Err(CoverageError::Gap { span });
+    }
+    out.push_str(gap);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -192,19 +1622,415 @@ mod tests {
         assert_eq!(lex.next(), Some(Ok(LuaToken::Integer(10))));
         assert_eq!(lex.next(), Some(Ok(LuaToken::Integer(99))));
         assert_eq!(lex.next(), Some(Ok(LuaToken::Integer(1_000))));
+        let mut lex = LuaToken::lexer("0x1F 0XfF 0xffffffffffffffff");
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Integer(31))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Integer(255))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Integer(-1))));
+        let mut lex = LuaToken::lexer("9223372036854775807 9223372036854775808 1_0000000000000000000");
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Integer(i64::MAX))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Float(9223372036854775808.0))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Float(1e19))));
+        assert_eq!(tokenize("x = -9223372036854775808").unwrap()[3], (LuaToken::Float(9223372036854775808.0), Span::new(5, 24)));
+        assert_eq!(LuaToken::lexer_bytes(b"99999999999999999999").next(), Some(Ok(ByteToken::Float(1e20))));
     }
     #[test]
     fn lex_float() {
         let mut lex = LuaToken::lexer("0.0 1.0 0x1.1 9.0 10.123_4 0_.99 1_000.0000_000");
         assert_eq!(lex.next(), Some(Ok(LuaToken::Float(0.0))));
         assert_eq!(lex.next(), Some(Ok(LuaToken::Float(1.0))));
-        assert_eq!(lex.next(), Some(Ok(LuaToken::Float(1.1))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Float(1.0625))));
         assert_eq!(lex.next(), Some(Ok(LuaToken::Float(9.0))));
         assert_eq!(lex.next(), Some(Ok(LuaToken::Float(10.1234))));
         assert_eq!(lex.next(), Some(Ok(LuaToken::Float(0.99))));
         assert_eq!(lex.next(), Some(Ok(LuaToken::Float(1000.00000000))));
     }
     #[test]
+    fn lex_exponent() {
+        let mut lex = LuaToken::lexer("1e2 2.5E-1 3e+1");
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Float(100.0))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Float(0.25))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Float(30.0))));
+    }
+    #[test]
+    fn lex_trailing_dot_and_hex_exponent() {
+        let floats = |source| tokenize(source).unwrap().into_iter().map(|(token, _)| token).collect::<Vec<_>>();
+        assert_eq!(floats("3. 3.e2 0x1p4 0xA.8P1 0x10p-2 0xA."), [
+            LuaToken::Float(3.0),
+            LuaToken::Float(300.0),
+            LuaToken::Float(16.0),
+            LuaToken::Float(21.0),
+            LuaToken::Float(4.0),
+            LuaToken::Float(10.0),
+        ]);
+        assert_eq!(tokenize("x = 3.").unwrap()[2], (LuaToken::Float(3.0), Span::new(4, 6)));
+        assert_eq!(tokenize("3..x"), Err(LexError::InvalidNumber { span: Span::new(0, 4) }));
+        assert_eq!(tokenize("0x1p4q"), Err(LexError::InvalidNumber { span: Span::new(0, 6) }));
+    }
+    #[test]
+    fn lex_malformed_number() {
+        assert_eq!(tokenize("1.2.3"), Err(LexError::InvalidNumber { span: Span::new(0, 5) }));
+        assert_eq!(tokenize("0x1.2.3"), Err(LexError::InvalidNumber { span: Span::new(0, 7) }));
+        assert_eq!(tokenize("x = 1e2e3"), Err(LexError::InvalidNumber { span: Span::new(4, 9) }));
+    }
+    #[test]
+    fn lex_lenient_numbers() {
+        let lenient = LexExtras { lenient_numbers: true, ..LexExtras::default() };
+        let mut lexer = LuaToken::lexer_with_extras("3abc", lenient.clone());
+        assert_eq!(lexer.next(), Some(Ok(LuaToken::Integer(3))));
+        assert_eq!(lexer.next(), Some(Ok(LuaToken::Identifier("abc"))));
+        assert_eq!(lexer.next(), None);
+        assert_eq!(lexer.extras.notes, vec![LexError::InvalidNumber { span: Span::new(0, 4) }]);
+
+        let mut lexer = LuaToken::lexer_with_extras("0x1p", lenient);
+        assert_eq!(lexer.next(), Some(Ok(LuaToken::Integer(1))));
+        assert_eq!(lexer.next(), Some(Ok(LuaToken::Identifier("p"))));
+        assert_eq!(lexer.extras.notes, vec![LexError::InvalidNumber { span: Span::new(0, 4) }]);
+
+        // Strict by default, like reference Lua.
+        assert_eq!(tokenize("3abc"), Err(LexError::InvalidNumber { span: Span::new(0, 4) }));
+        assert_eq!(tokenize("0x1p"), Err(LexError::InvalidNumber { span: Span::new(0, 4) }));
+    }
+    #[test]
+    fn lex_precedence() {
+        assert_eq!(LuaToken::Or.precedence(), Some(1));
+        assert_eq!(LuaToken::Exponent.precedence(), Some(12));
+        assert!(LuaToken::Multiply.precedence() > LuaToken::Plus.precedence());
+        assert!(LuaToken::Not.precedence() > LuaToken::Multiply.precedence());
+        assert_eq!(LuaToken::Identifier("x").precedence(), None);
+    }
+    #[test]
+    fn lex_operator_groups() {
+        let groups = |token: LuaToken| [token.is_comparison(), token.is_arithmetic(), token.is_bitwise(), token.is_logical()];
+        assert_eq!(groups(LuaToken::Less), [true, false, false, false]);
+        assert_eq!(groups(LuaToken::Plus), [false, true, false, false]);
+        assert_eq!(groups(LuaToken::Ampersand), [false, false, true, false]);
+        assert_eq!(groups(LuaToken::And), [false, false, false, true]);
+        assert_eq!(groups(LuaToken::Not), [false, false, false, true]);
+        assert_eq!(groups(LuaToken::Concatenate), [false; 4]);
+        assert_eq!(groups(LuaToken::Identifier("and")), [false; 4]);
+        // Every binary operator is in exactly one group but `..`.
+        for kind in TokenKind::ALL {
+            let Some(token) = kind.text().and_then(|text| LuaToken::try_from(text).ok()) else { continue };
+            if BinOp::from_token(&token).is_some_and(|op| op != BinOp::Concat) {
+                assert_eq!(groups(token).iter().filter(|&&group| group).count(), 1, "{kind:?}");
+            }
+        }
+    }
+    #[test]
+    fn lex_skip_comments() {
+        let mut lex = LuaToken::lexer("a -- line\nb --[[ long\n comment ]] c --[==[ ]] ]==] d");
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("a"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("b"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("c"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("d"))));
+        assert_eq!(lex.next(), None);
+    }
+    #[test]
+    fn lex_code_touching_long_comments() {
+        assert_eq!(tokenize("--[[c]]x").unwrap(), [(LuaToken::Identifier("x"), Span::new(7, 8))]);
+        assert_eq!(tokenize("--[==[ ]] ]==]y").unwrap(), [(LuaToken::Identifier("y"), Span::new(14, 15))]);
+        assert_eq!(
+            tokenize("x--[[c]]y").unwrap(),
+            [(LuaToken::Identifier("x"), Span::new(0, 1)), (LuaToken::Identifier("y"), Span::new(8, 9))]
+        );
+        // The comment ends at its first matching bracket, leaving `]]` as code.
+        let kinds = tokenize("--[=[ ]=]]]z").unwrap().into_iter().map(|(token, span)| (token.kind(), span));
+        assert_eq!(
+            kinds.collect::<Vec<_>>(),
+            [(TokenKind::RBracket, Span::new(9, 10)), (TokenKind::RBracket, Span::new(10, 11)), (TokenKind::Identifier, Span::new(11, 12))]
+        );
+        assert_eq!(tokenize("--[[]]--[[]]1").unwrap(), [(LuaToken::Integer(1), Span::new(12, 13))]);
+        let tokens = LexerBuilder::new().retain_comments(true).tokenize("x--[==[ ]] ]==]y").unwrap();
+        assert_eq!(tokens[1], (LuaToken::Comment("--[==[ ]] ]==]"), Span::new(1, 15)));
+        assert_eq!(tokens[2], (LuaToken::Identifier("y"), Span::new(15, 16)));
+    }
+    #[test]
+    fn lex_retain_comments() {
+        let mut lex = lexer_with_comments("a -- line\n--[[ long ]]b");
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("a"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Comment("-- line"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Comment("--[[ long ]]"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("b"))));
+    }
+    #[test]
+    fn lex_dash_run_comments() {
+        let mut lex = LuaToken::lexer("--- three\na ---- four\nb --[[ long\n]] c ---[[ line\nd");
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("a"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("b"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("c"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("d"))));
+        assert_eq!(lex.next(), None);
+
+        let mut lex = lexer_with_comments("---[[ not long\n--[[ long ]]");
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Comment("---[[ not long"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::Comment("--[[ long ]]"))));
+    }
+    #[test]
+    fn lex_tokenize_recovering() {
+        let (tokens, errors) = tokenize_recovering("a $ 1.2.3 b");
+        assert_eq!(tokens, vec![(LuaToken::Identifier("a"), Span::new(0, 1)), (LuaToken::Identifier("b"), Span::new(10, 11))]);
+        assert_eq!(errors, vec![
+            LexError::UnexpectedChar { ch: '$', span: Span::new(2, 3) },
+            LexError::InvalidNumber { span: Span::new(4, 9) },
+        ]);
+    }
+    #[test]
+    fn lex_unexpected_char() {
+        for ch in ['$', '@', '`', '?', '\\'] {
+            let source = format!("x {ch} y");
+            assert_eq!(tokenize(&source), Err(LexError::UnexpectedChar { ch, span: Span::new(2, 3) }));
+        }
+        assert_eq!(tokenize("é"), Err(LexError::UnexpectedChar { ch: 'é', span: Span::new(0, 2) }));
+        assert_eq!(unexpected_char("a ?", 2).to_string(), "unexpected character '?'");
+    }
+    #[test]
+    fn lex_unterminated_comment() {
+        assert_eq!(tokenize("x --[=[ ]] "), Err(LexError::UnterminatedComment { span: Span::new(2, 11) }));
+    }
+    #[test]
+    fn lex_backslash_newline() {
+        let tokens = tokenize("s = \"line1\\\nline2\" 'a\\\r\nb'").unwrap();
+        assert_eq!(tokens[2], (LuaToken::String("line1\\\nline2"), Span::new(4, 18)));
+        assert_eq!(tokens[3].0, LuaToken::String("a\\\r\nb"));
+        assert!(tokenize("\"line1\nline2\"").is_err());
+    }
+    #[test]
+    fn lex_long_string() {
+        let tokens = tokenize("x = [[\nline one\nline ]=] two]] .. [==[a]]b]==]").unwrap();
+        assert_eq!(tokens[2], (LuaToken::LongString("line one\nline ]=] two"), Span::new(4, 30)));
+        assert_eq!(tokens[4].0, LuaToken::LongString("a]]b"));
+        assert_eq!(tokenize("[=[ ]]"), Err(LexError::UnterminatedString { span: Span::new(0, 6) }));
+        assert_eq!(tokenize("t[1]").unwrap()[1].0, LuaToken::LBracket);
+    }
+    #[test]
+    fn lex_accessors() {
+        assert_eq!(LuaToken::Identifier("x").as_identifier(), Some("x"));
+        assert_eq!(LuaToken::String("x").as_identifier(), None);
+        assert_eq!(LuaToken::String("s").as_string(), Some("s"));
+        assert_eq!(LuaToken::LongString("l").as_string(), Some("l"));
+        assert_eq!(LuaToken::Identifier("x").as_string(), None);
+        assert_eq!(LuaToken::Integer(3).as_integer(), Some(3));
+        assert_eq!(LuaToken::Float(3.0).as_integer(), None);
+        assert_eq!(LuaToken::Float(2.5).as_float(), Some(2.5));
+        assert_eq!(LuaToken::Integer(2).as_float(), None);
+    }
+    #[test]
+    fn lex_spanned_tolerance() {
+        let source = "a $ b 0x c";
+        let tolerant: Vec<_> = LexerBuilder::new().spanned(source, true).collect();
+        assert_eq!(
+            tolerant,
+            vec![
+                Ok((LuaToken::Identifier("a"), Span::new(0, 1))),
+                Err(LexError::UnexpectedChar { ch: '$', span: Span::new(2, 3) }),
+                Ok((LuaToken::Identifier("b"), Span::new(4, 5))),
+                Err(LexError::InvalidNumber { span: Span::new(6, 8) }),
+                Ok((LuaToken::Identifier("c"), Span::new(9, 10))),
+            ]
+        );
+        let strict: Vec<_> = LexerBuilder::new().spanned(source, false).collect();
+        assert_eq!(strict, tolerant[..2]);
+    }
+    #[test]
+    fn lex_size_hint() {
+        let mut tokens = LexerBuilder::new().spanned("a $ bc", false);
+        assert_eq!(tokens.size_hint(), (0, Some(6)));
+        tokens.next();
+        assert_eq!(tokens.size_hint(), (0, Some(5)));
+        assert!(tokens.next().unwrap().is_err());
+        assert_eq!(tokens.size_hint(), (0, Some(0)));
+        let mut tolerant = LexerBuilder::new().spanned("a $ bc", true);
+        tolerant.by_ref().take(2).for_each(drop);
+        assert_eq!(tolerant.size_hint(), (0, Some(3)));
+        let mut iter = TokenIter::new("x = 10");
+        iter.next();
+        assert_eq!(iter.size_hint(), (0, Some(5)));
+        // The bound holds for a source of nothing but one-byte tokens.
+        assert_eq!(tokenize("(((;)))").unwrap().len(), TokenIter::new("(((;)))").size_hint().1.unwrap());
+    }
+    #[test]
+    fn lex_comment_kinds() {
+        let source = "--- doc\n-- plain\n--[[-- block doc ]]\n--[==[ block ]==]\n---- rule\n--";
+        let kinds: Vec<_> = lexer_with_comments(source).map(|token| token.unwrap().comment_kind().unwrap()).collect();
+        assert_eq!(
+            kinds,
+            [CommentKind::Doc, CommentKind::Line, CommentKind::Doc, CommentKind::Block, CommentKind::Line, CommentKind::Line]
+        );
+        assert_eq!(LuaToken::Identifier("x").comment_kind(), None);
+    }
+    #[test]
+    fn lex_token_iter() {
+        let tokens: Result<Vec<_>, _> = TokenIter::new("x = 'a'").collect();
+        assert_eq!(tokens, Ok(vec![LuaToken::Identifier("x"), LuaToken::Equal, LuaToken::String("a")]));
+        let tokens: Result<Vec<_>, _> = TokenIter::new("x = @ 1").collect();
+        assert_eq!(tokens, Err(LexError::UnexpectedChar { ch: '@', span: Span::new(4, 5) }));
+
+        let mut iter = TokenIter::from(lexer_with_comments("a -- b\n1a"));
+        assert_eq!(iter.nth(1), Some(Ok(LuaToken::Comment("-- b"))));
+        assert_eq!(iter.span(), Span::new(2, 6));
+        assert_eq!(iter.next(), Some(Err(LexError::InvalidNumber { span: Span::new(7, 9) })));
+        assert_eq!(iter.next(), None);
+    }
+    #[test]
+    fn lex_max_token_len() {
+        let builder = LexerBuilder::new().max_token_len(Some(8));
+        let source = format!("x = [[{}]] y", "a".repeat(100));
+        let (tokens, errors) = builder.tokenize_recovering(&source);
+        assert_eq!(tokens, vec![(LuaToken::Identifier("x"), Span::new(0, 1)), (LuaToken::Equal, Span::new(2, 3))]);
+        assert_eq!(errors, vec![LexError::TokenTooLong { span: Span::new(4, 12) }]);
+
+        assert_eq!(builder.tokenize("[[abcd]] --[[ ]]").unwrap().len(), 1);
+        assert_eq!(builder.tokenize("--[[ long comment ]]"), Err(LexError::TokenTooLong { span: Span::new(0, 8) }));
+        assert_eq!(builder.tokenize("abcdefgh abcdefghi"), Err(LexError::TokenTooLong { span: Span::new(9, 17) }));
+        assert_eq!(builder.tokenize("[[ unfinished"), Err(LexError::TokenTooLong { span: Span::new(0, 8) }));
+        assert_eq!(builder.tokenize("[[ab"), Err(LexError::UnterminatedString { span: Span::new(0, 4) }));
+        assert_eq!(LexerBuilder::new().tokenize(&source).unwrap().len(), 4);
+    }
+    #[test]
+    fn lex_token_eq_kind() {
+        assert!(LuaToken::Identifier("x") == TokenKind::Identifier);
+        assert!(LuaToken::Identifier("x") != TokenKind::String);
+        assert!(TokenKind::Integer == LuaToken::Integer(7));
+        assert!(TokenKind::Then != LuaToken::Do);
+    }
+    #[test]
+    fn lex_byte_order_mark() {
+        let source = "\u{FEFF}local x";
+        let tokens = tokenize(source).unwrap();
+        assert_eq!(tokens, vec![(LuaToken::Local, Span::new(3, 8)), (LuaToken::Identifier("x"), Span::new(9, 10))]);
+        assert_eq!(tokenize("\u{FEFF}#!/usr/bin/lua\nx").unwrap(), vec![(LuaToken::Identifier("x"), Span::new(18, 19))]);
+        assert_eq!(TokenIter::new(source).next(), Some(Ok(LuaToken::Local)));
+        // Only a leading BOM is skipped.
+        assert_eq!(tokenize("x \u{FEFF}"), Err(LexError::UnexpectedChar { ch: '\u{FEFF}', span: Span::new(2, 5) }));
+    }
+    #[test]
+    fn lex_builder() {
+        let source = "goto = 1 -- note\ngoto next";
+        let builder = LexerBuilder::new().dialect(Dialect::Lua51).retain_comments(true);
+        let tokens: Vec<LuaToken> = builder.build(source).map(Result::unwrap).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                LuaToken::Identifier("goto"),
+                LuaToken::Equal,
+                LuaToken::Integer(1),
+                LuaToken::Comment("-- note"),
+                LuaToken::Identifier("goto"),
+                LuaToken::Identifier("next"),
+            ]
+        );
+        let tokens = LexerBuilder::default().tokenize(source).unwrap();
+        assert_eq!(tokens[0], (LuaToken::Goto, Span::new(0, 4)));
+        assert_eq!(tokens.len(), 5);
+    }
+    #[test]
+    fn lex_newer_syntax_in_lua51() {
+        let lua51 = LexerBuilder::new().dialect(Dialect::Lua51);
+        let err = lua51.tokenize("7//2").unwrap_err();
+        assert_eq!(err.to_string(), "floor division requires Lua 5.3+");
+        assert_eq!((err.dialect(), err.span()), (Some(Dialect::Lua51), Span::new(1, 3)));
+        assert_eq!(lua51.tokenize("a << 1").unwrap_err().to_string(), "bitwise operators require Lua 5.3+");
+        assert_eq!(lua51.tokenize("~x").unwrap_err().to_string(), "bitwise operators require Lua 5.3+");
+        assert_eq!(lua51.tokenize("::top::").unwrap_err().to_string(), "labels require Lua 5.2+");
+        assert!(lua51.tokenize("a ~= b / 2").is_ok());
+        assert!(tokenize("7//2 & 1 ::top::").is_ok());
+        assert_eq!(tokenize("1 $").unwrap_err().dialect(), None);
+    }
+    #[test]
+    fn lex_compound_assign() {
+        let source = "x += 1 s ..= t n //= 2 a = -1";
+        let kinds = |dialect| {
+            let tokens = LexerBuilder::new().dialect(dialect).tokenize(source).unwrap();
+            tokens.into_iter().map(|(token, _)| token.kind()).collect::<Vec<_>>()
+        };
+        use TokenKind::*;
+        assert_eq!(
+            kinds(Dialect::Lua54),
+            [Identifier, Plus, Equal, Integer, Identifier, Concatenate, Equal, Identifier, Identifier, FloorDivide, Equal, Integer, Identifier, Equal, Minus, Integer]
+        );
+        let tokens = LexerBuilder::new().dialect(Dialect::Luau).tokenize(source).unwrap();
+        assert_eq!(tokens[1], (LuaToken::CompoundAssign(BinOp::Add), Span::new(2, 4)));
+        assert_eq!(tokens[4], (LuaToken::CompoundAssign(BinOp::Concat), Span::new(9, 12)));
+        assert_eq!(tokens[7], (LuaToken::CompoundAssign(BinOp::FloorDiv), Span::new(17, 20)));
+        assert_eq!(tokens[10..].iter().map(|(token, _)| token.kind()).collect::<Vec<_>>(), [Equal, Minus, Integer]);
+    }
+    #[test]
+    fn lex_walk_tokens() {
+        let mut visited = 0;
+        let found = walk_tokens("print('a', 'b') $", |token, span| {
+            visited += 1;
+            match token {
+                LuaToken::String(_) => ControlFlow::Break(span),
+                _ => ControlFlow::Continue(()),
+            }
+        });
+        // Stopping at the first string never reaches the bad `$`.
+        assert_eq!((found, visited), (Ok(Some(Span::new(6, 9))), 3));
+        assert_eq!(walk_tokens("a b", |_, _| ControlFlow::<()>::Continue(())), Ok(None));
+        assert!(walk_tokens("a $", |_, _| ControlFlow::<()>::Continue(())).is_err());
+    }
+    #[test]
+    fn lex_single_token() {
+        assert_eq!(LuaToken::try_from("+"), Ok(LuaToken::Plus));
+        assert_eq!(LuaToken::try_from(" 123 -- one"), Ok(LuaToken::Integer(123)));
+        assert_eq!(LuaToken::try_from("a b"), Err(SingleTokenError::Trailing { span: Span::new(2, 3) }));
+        assert_eq!(LuaToken::try_from(""), Err(SingleTokenError::Empty));
+        assert_eq!(LuaToken::try_from("a $").unwrap_err().to_string(), "unexpected character '$'");
+    }
+    #[test]
+    fn lex_nul_byte() {
+        let err = tokenize("x = 1\0 y").unwrap_err();
+        assert_eq!(err, LexError::UnexpectedChar { ch: '\0', span: Span::new(5, 6) });
+        assert_eq!(err.to_string(), "unexpected character '\\0'");
+        let (tokens, errors) = tokenize_recovering("x\0\0y");
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(errors.iter().map(LexError::span).collect::<Vec<_>>(), [Span::new(1, 2), Span::new(2, 3)]);
+        // Strings may hold any byte, NUL included.
+        assert_eq!(tokenize("'a\0b'").unwrap(), [(LuaToken::String("a\0b"), Span::new(0, 5))]);
+    }
+    #[test]
+    fn lex_annotation() {
+        let luau = LexerBuilder::new().dialect(Dialect::Luau);
+        let tokens = luau.tokenize("@deprecated function f() end").unwrap();
+        assert_eq!(tokens[0], (LuaToken::Annotation("deprecated"), Span::new(0, 11)));
+        assert_eq!(tokens[1].0, LuaToken::Function);
+        assert_eq!(luau.tokenize("@ x"), Err(LexError::UnexpectedChar { ch: '@', span: Span::new(0, 1) }));
+        assert_eq!(tokenize("@deprecated"), Err(LexError::UnexpectedChar { ch: '@', span: Span::new(0, 1) }));
+        // Only the `@` is rejected, the name after it still lexes.
+        let (tokens, errors) = tokenize_recovering("@deprecated");
+        assert_eq!((tokens, errors.len()), (vec![(LuaToken::Identifier("deprecated"), Span::new(1, 11))], 1));
+    }
+    #[test]
+    fn lex_interpolated_string() {
+        let luau = LexerBuilder::new().dialect(Dialect::Luau);
+        let tokens = luau.tokenize("x = `a {t[\"`\"]} \\` {{}}` .. y").unwrap();
+        assert_eq!(tokens[2], (LuaToken::InterpolatedString("a {t[\"`\"]} \\` {{}}"), Span::new(4, 24)));
+        assert_eq!(tokens[3].0, LuaToken::Concatenate);
+        assert_eq!(luau.tokenize("`a\nb`"), Err(LexError::UnexpectedChar { ch: '`', span: Span::new(0, 1) }));
+        assert_eq!(luau.tokenize("`{ 1.2.3 }`"), Err(LexError::InvalidNumber { span: Span::new(3, 8) }));
+        assert_eq!(tokenize("`a`"), Err(LexError::UnexpectedChar { ch: '`', span: Span::new(0, 1) }));
+    }
+    #[test]
+    fn lex_token_kind_u16() {
+        for kind in TokenKind::ALL {
+            assert_eq!(TokenKind::from_u16(kind.as_u16()), Some(kind));
+        }
+        assert!(TokenKind::ALL.is_sorted());
+        assert_eq!(TokenKind::And.as_u16(), 0);
+        assert_eq!(TokenKind::Plus.as_u16(), 64);
+        assert_eq!(TokenKind::Comment.as_u16(), 128);
+        assert_eq!(TokenKind::from_u16(63), None);
+    }
+    #[test]
+    fn lex_token_kind() {
+        assert_eq!(LuaToken::Identifier("x").kind(), TokenKind::Identifier);
+        assert_eq!(LuaToken::Integer(1).kind(), TokenKind::Integer);
+        assert_eq!(LuaToken::Then.kind(), TokenKind::Then);
+        assert_eq!(TokenKind::Then.text(), Some("then"));
+        assert_eq!(TokenKind::DoubleColon.text(), Some("::"));
+        assert_eq!(TokenKind::Identifier.text(), None);
+    }
+    #[test]
     fn lex_array_and_table() {
         let mut lex = LuaToken::lexer("my_array[1] other_array[\"x\"]");
         assert_eq!(lex.next(), Some(Ok(LuaToken::Identifier("my_array"))));
@@ -225,16 +2051,78 @@ mod tests {
         assert_eq!(lex.next(), Some(Ok(LuaToken::TableIndex(("other_args",10)))));
     }
     */
-    /*
     #[test]
     fn lex_multiple_line_str() {
         let mut lex = LuaToken::lexer("[[This is a multiple line,\n string :) ]]");
-        lex.next();
-        println!("Token: {}",lex.slice());
-        assert_eq!(lex.next(), Some(Ok(LuaToken::MultipleLineString("[[This is a multiple line,\n string :) ]]"))));
+        assert_eq!(lex.next(), Some(Ok(LuaToken::LongString("This is a multiple line,\n string :) "))));
         assert_eq!(lex.slice(), "[[This is a multiple line,\n string :) ]]");
     }
-    */ // No support for multiline strings currently
+    #[test]
+    fn lex_tokenize_spans() {
+        let tokens = tokenize("local x = 10").unwrap();
+        assert_eq!(tokens[0], (LuaToken::Local, Span::new(0, 5)));
+        assert_eq!(tokens[1], (LuaToken::Identifier("x"), Span::new(6, 7)));
+        assert_eq!(tokens[3], (LuaToken::Integer(10), Span::new(10, 12)));
+        assert_eq!(tokenize("x = $"), Err(LexError::UnexpectedChar { ch: '$', span: Span::new(4, 5) }));
+    }
+    #[test]
+    fn lex_crlf_file() {
+        let source = "#!/usr/bin/lua\r\nlocal x = 1\r\n\x0bif x then\r\n\treturn x -- done\r\nend\r\n";
+        let tokens = tokenize(source).unwrap();
+        assert_eq!(tokens.len(), 10);
+        assert_eq!(tokens[4], (LuaToken::If, Span::new(30, 32)));
+        assert_eq!(reconstruct(source, &tokens).as_deref(), Ok(source));
+        assert_eq!(LuaToken::lexer_bytes(source.as_bytes()).count(), 10);
+    }
+    #[test]
+    fn lex_spelling_spans() {
+        for dialect in [Dialect::Lua54, Dialect::Luau] {
+            for kind in TokenKind::ALL {
+                let Some(text) = kind.text() else { continue };
+                for source in [text.to_string(), format!("{text} ")] {
+                    let tokens = LexerBuilder::new().dialect(dialect).tokenize(&source).unwrap();
+                    let tokens: Vec<_> = tokens.into_iter().map(|(token, span)| (token.kind(), span)).collect();
+                    assert_eq!(tokens, [(kind, Span::new(0, text.len()))], "{source:?} in {dialect:?}");
+                }
+            }
+        }
+        for text in ["+=", "-=", "*=", "/=", "//=", "%=", "^=", "..="] {
+            let tokens = LexerBuilder::new().dialect(Dialect::Luau).tokenize(text).unwrap();
+            assert!(matches!(tokens[..], [(LuaToken::CompoundAssign(_), span)] if span == Span::new(0, text.len())), "{text}");
+        }
+    }
+    #[test]
+    fn lex_reconstruct() {
+        let sources = [
+            "local x = 10",
+            "#!/usr/bin/env lua\nlocal t = {1, 2.5, 'three'} -- trailing\n",
+            "--[==[ long\ncomment ]==]\nwhile x >= 0x10 do x = x // 2 end\n\n",
+            "\t return a .. b, ... ;",
+            "",
+        ];
+        for source in sources {
+            let tokens = tokenize(source).unwrap();
+            assert_eq!(reconstruct(source, &tokens).as_deref(), Ok(source));
+        }
+    }
+    #[test]
+    fn lex_reconstruct_bad_span() {
+        let source = "local x = 10";
+        let mut tokens = tokenize(source).unwrap();
+        tokens[1].1 = Span::new(7, 7);
+        assert_eq!(reconstruct(source, &tokens), Err(CoverageError::Gap { span: Span::new(5, 7) }));
+        tokens[1].1 = Span::new(4, 7);
+        assert_eq!(reconstruct(source, &tokens), Err(CoverageError::Overlap { span: Span::new(4, 7) }));
+        tokens[1].1 = Span::new(6, 7);
+        tokens.pop();
+        assert_eq!(reconstruct(source, &tokens), Err(CoverageError::Gap { span: Span::new(9, 12) }));
+        let source = "\u{FEFF}x = 1";
+        let tokens = tokenize(source).unwrap();
+        assert_eq!(reconstruct(source, &tokens).as_deref(), Ok(source));
+        let source = "x =\u{FEFF} 1";
+        let tokens = [(LuaToken::Identifier("x"), Span::new(0, 1)), (LuaToken::Equal, Span::new(2, 3)), (LuaToken::Integer(1), Span::new(7, 8))];
+        assert_eq!(reconstruct(source, &tokens), Err(CoverageError::Gap { span: Span::new(3, 7) }));
+    }
 
 }
 
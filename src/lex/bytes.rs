@@ -0,0 +1,305 @@
+use logos::Logos;
+
+use super::{
+    annotation, as_float, as_int, bitwise, comment, compound_assign, decimal_integer, goto_keyword, identifier,
+    interpolated_string, label_delimiter, long_string, unexpected_token, CallbackToken, LexError, LexExtras,
+};
+use crate::ast::BinOp;
+
+/// Byte-oriented mirror of [`LuaToken`](super::LuaToken).
+///
+/// Lua strings are binary-safe, so files may hold bytes that aren't valid UTF-8.
+/// Identifier and string payloads are raw `&[u8]` slices of the source. The
+/// callbacks, and so the [`LexExtras`] options and dialect, are shared with
+/// `LuaToken`.
+#[derive(Logos, Debug, Clone, PartialEq)]
+#[logos(source = [u8])]
+#[logos(error(LexError, unexpected_token))]
+#[logos(extras = LexExtras)]
+#[logos(skip r"[ \t\n\r\x0b\f]+")]
+#[logos(skip r"\#\![^\n]*")]
+pub enum ByteToken<'source> {
+    //==--------
+    // Keywords
+    //==--------
+    #[token("and")]
+    And,
+    #[token("break")]
+    Break,
+    #[token("continue")]
+    Continue,
+    #[token("do")]
+    Do,
+    #[token("else")]
+    Else,
+    #[token("elseif")]
+    Elseif,
+    #[token("end")]
+    End,
+    #[token("false")]
+    False,
+    #[token("for")]
+    For,
+    #[token("function")]
+    Function,
+    #[token("goto", goto_keyword)]
+    Goto,
+    #[token("if")]
+    If,
+    #[token("in")]
+    In,
+    #[token("local")]
+    Local,
+    #[token("nil")]
+    Nil,
+    #[token("not")]
+    Not,
+    #[token("or")]
+    Or,
+    #[token("repeat")]
+    Repeat,
+    #[token("return")]
+    Return,
+    #[token("then")]
+    Then,
+    #[token("true")]
+    True,
+    #[token("until")]
+    Until,
+    #[token("while")]
+    While,
+    //==---------
+    // Operators
+    //==---------
+    #[token("+", compound_assign)]
+    Plus,
+    #[token("-", compound_assign)]
+    Minus,
+    #[token("*", compound_assign)]
+    Multiply,
+    #[token("/", compound_assign)]
+    Divide,
+    #[token("//", compound_assign)]
+    FloorDivide,
+    #[token("%", compound_assign)]
+    Modulus,
+    #[token("^", compound_assign)]
+    Exponent,
+    #[token("&", bitwise)]
+    Ampersand,
+    #[token("|", bitwise)]
+    Pipe,
+    #[token("~", bitwise)]
+    Tilde,
+    #[token("<<", bitwise)]
+    ShiftLeft,
+    #[token(">>", bitwise)]
+    ShiftRight,
+    #[token("==")]
+    DoubleEqual,
+    #[token("=")]
+    Equal,
+    #[token("~=")]
+    NotEqual,
+    #[token(">")]
+    Greater,
+    #[token("<")]
+    Less,
+    #[token(">=")]
+    GreaterEqual,
+    #[token("<=")]
+    LessEqual,
+    #[token(".")]
+    Dot,
+    #[token("..", compound_assign)]
+    Concatenate,
+    #[token("...")]
+    Ellipsis,
+    #[token("#")]
+    Length,
+    #[token("[")]
+    LBracket,
+    #[token("]")]
+    RBracket,
+    #[token("(")]
+    LParen,
+    #[token(")")]
+    RParen,
+    #[token("{")]
+    LBrace,
+    #[token("}")]
+    RBrace,
+    #[token(",")]
+    Comma,
+    #[token(";")]
+    Semicolon,
+    #[token(":")]
+    Colon,
+    #[token("::", label_delimiter)]
+    DoubleColon,
+    /// Luau's `op=`, as in `LuaToken::CompoundAssign`.
+    CompoundAssign(BinOp),
+    /// Luau's `@name` attribute, holding the name without the `@`.
+    #[token("@", annotation)]
+    Annotation(&'source [u8]),
+    //==---------
+    // Comments
+    //==---------
+    /// Only produced when comments are retained, holds the whole comment including `--`.
+    #[token("--", comment)]
+    Comment(&'source [u8]),
+    //==----------
+    // Identifier
+    //==----------
+    #[regex("[a-zA-Z_][a-zA-Z_0-9]*", identifier)]
+    Identifier(&'source [u8]),
+    //==---------------
+    // String literals
+    //==---------------
+    #[regex(br#""(?-u:[^"\\\n]|\\(.|\n|\r\n))*"|'(?-u:[^'\\\n]|\\(.|\n|\r\n))*'"#, |text| &text.slice()[1..text.slice().len()-1])]
+    String(&'source [u8]),
+    /// The contents of `[[...]]` or `[==[...]==]`, as in `LuaToken::LongString`.
+    #[regex(r"\[=*\[", long_string)]
+    LongString(&'source [u8]),
+    /// Luau's backtick string, as in `LuaToken::InterpolatedString`.
+    #[token("`", interpolated_string)]
+    InterpolatedString(&'source [u8]),
+    //==---------------
+    // Number literals
+    //==---------------
+    #[regex(r"0[xX][0-9a-fA-F][0-9a-fA-F_]*", as_int)]
+    Integer(i64),
+    /// Never produced, as in `LuaToken::DecimalInteger`.
+    #[doc(hidden)]
+    #[regex(r"[0-9][0-9_]*", decimal_integer)]
+    DecimalInteger,
+    #[regex(r"[0-9][0-9_]*(\.([0-9][0-9_]*)?([eE][+-]?[0-9]+)?|[eE][+-]?[0-9]+)|0[xX][0-9a-fA-F][0-9a-fA-F_]*(\.([0-9a-fA-F][0-9a-fA-F_]*)?([pP][+-]?[0-9]+)?|[pP][+-]?[0-9]+)", as_float)]
+    Float(f64),
+}
+
+impl<'s> CallbackToken<'s> for ByteToken<'s> {
+    fn integer(value: i64) -> Self {
+        ByteToken::Integer(value)
+    }
+
+    fn float(value: f64) -> Self {
+        ByteToken::Float(value)
+    }
+
+    fn identifier(name: &'s [u8]) -> Self {
+        ByteToken::Identifier(name)
+    }
+
+    fn goto() -> Self {
+        ByteToken::Goto
+    }
+
+    fn operator(op: BinOp) -> Self {
+        match op {
+            BinOp::Add => ByteToken::Plus,
+            BinOp::Sub => ByteToken::Minus,
+            BinOp::Mul => ByteToken::Multiply,
+            BinOp::Div => ByteToken::Divide,
+            BinOp::FloorDiv => ByteToken::FloorDivide,
+            BinOp::Mod => ByteToken::Modulus,
+            BinOp::Pow => ByteToken::Exponent,
+            _ => ByteToken::Concatenate,
+        }
+    }
+
+    fn compound_assign(op: BinOp) -> Self {
+        ByteToken::CompoundAssign(op)
+    }
+
+    fn is_lbrace(&self) -> bool {
+        *self == ByteToken::LBrace
+    }
+
+    fn is_rbrace(&self) -> bool {
+        *self == ByteToken::RBrace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::{Dialect, LexerBuilder, LuaToken};
+    use crate::span::Span;
+
+    #[test]
+    fn lex_bytes_high_byte_string() {
+        let source: &[u8] = b"local s = \"caf\xe9\" -- \xff\n";
+        let mut lex = LuaToken::lexer_bytes(source);
+        assert_eq!(lex.next(), Some(Ok(ByteToken::Local)));
+        assert_eq!(lex.next(), Some(Ok(ByteToken::Identifier(b"s"))));
+        assert_eq!(lex.next(), Some(Ok(ByteToken::Equal)));
+        assert_eq!(lex.next(), Some(Ok(ByteToken::String(b"caf\xe9"))));
+        assert_eq!(lex.next(), None);
+    }
+    #[test]
+    fn lex_bytes_long_comment() {
+        let tokens: Vec<_> = LuaToken::lexer_bytes(b"--[[ a\n b ]] x --[==[ \xff ]] ]==] y -- z").collect();
+        assert_eq!(tokens, [Ok(ByteToken::Identifier(b"x")), Ok(ByteToken::Identifier(b"y"))]);
+        let mut lex = LexerBuilder::new().retain_comments(true).build_bytes(b"--[[ \xff ]]--");
+        assert_eq!(lex.next(), Some(Ok(ByteToken::Comment(b"--[[ \xff ]]"))));
+        assert_eq!(lex.next(), Some(Ok(ByteToken::Comment(b"--"))));
+        let mut lex = LuaToken::lexer_bytes(b"x --[[ a");
+        assert_eq!(lex.nth(1), Some(Err(LexError::UnterminatedComment { span: Span::new(2, 8) })));
+    }
+    #[test]
+    fn lex_bytes_long_string() {
+        let tokens: Vec<_> = LuaToken::lexer_bytes(b"x = [[a\nb]] .. [==[\n]] \xff]==]").collect();
+        assert_eq!(tokens, [
+            Ok(ByteToken::Identifier(b"x")),
+            Ok(ByteToken::Equal),
+            Ok(ByteToken::LongString(b"a\nb")),
+            Ok(ByteToken::Concatenate),
+            Ok(ByteToken::LongString(b"]] \xff")),
+        ]);
+        let mut lex = LuaToken::lexer_bytes(b"[=[ a ]]");
+        assert_eq!(lex.next(), Some(Err(LexError::UnterminatedString { span: Span::new(0, 8) })));
+    }
+    #[test]
+    fn lex_bytes_shares_options() {
+        let mut lex = LuaToken::lexer_bytes(b"\xef\xbb\xbfx = 3.");
+        assert_eq!(lex.next(), Some(Ok(ByteToken::Identifier(b"x"))));
+        assert_eq!(lex.span(), 3..4);
+        assert_eq!(lex.nth(1), Some(Ok(ByteToken::Float(3.0))));
+        let limited = LexerBuilder::new().max_token_len(Some(3));
+        let mut lex = limited.build_bytes(b"abcd");
+        assert_eq!(lex.next(), Some(Err(LexError::TokenTooLong { span: Span::new(0, 3) })));
+        let mut lex = LexerBuilder::new().lenient_numbers(true).build_bytes(b"3abc");
+        assert_eq!(lex.next(), Some(Ok(ByteToken::Integer(3))));
+        assert_eq!(lex.extras.notes, [LexError::InvalidNumber { span: Span::new(0, 4) }]);
+    }
+    #[test]
+    fn lex_bytes_dialect() {
+        let lua51 = LexerBuilder::new().dialect(Dialect::Lua51);
+        let mut lex = lua51.build_bytes(b"goto a // b");
+        assert_eq!(lex.next(), Some(Ok(ByteToken::Identifier(b"goto"))));
+        assert_eq!(lex.nth(1), Some(Err(LexError::NotInDialect { dialect: Dialect::Lua51, hint: "floor division requires Lua 5.3+", span: Span::new(7, 9) })));
+        assert!(matches!(lua51.build_bytes(b"a & b").nth(1), Some(Err(LexError::NotInDialect { .. }))));
+        let luau = LexerBuilder::new().dialect(Dialect::Luau);
+        let tokens: Vec<_> = luau.build_bytes(b"x += 1 @native `a{ {b} }\xff`").collect();
+        assert_eq!(tokens, [
+            Ok(ByteToken::Identifier(b"x")),
+            Ok(ByteToken::CompoundAssign(BinOp::Add)),
+            Ok(ByteToken::Integer(1)),
+            Ok(ByteToken::Annotation(b"native")),
+            Ok(ByteToken::InterpolatedString(b"a{ {b} }\xff")),
+        ]);
+        let mut lex = LuaToken::lexer_bytes(b"x += 1");
+        assert_eq!(lex.nth(1), Some(Ok(ByteToken::Plus)));
+        assert_eq!(lex.next(), Some(Ok(ByteToken::Equal)));
+    }
+    #[test]
+    fn lex_bytes_unexpected_char() {
+        let mut lex = LuaToken::lexer_bytes(b"x $ \xff \xc3\xa9");
+        assert_eq!(lex.nth(1), Some(Err(LexError::UnexpectedChar { ch: '$', span: Span::new(2, 3) })));
+        assert_eq!(lex.next(), Some(Err(LexError::UnexpectedChar { ch: char::REPLACEMENT_CHARACTER, span: Span::new(4, 5) })));
+        assert_eq!(lex.next(), Some(Err(LexError::UnexpectedChar { ch: '\u{e9}', span: Span::new(6, 8) })));
+        let mut lex = LexerBuilder::new().dialect(Dialect::Luau).build_bytes(b"`a{ $ }`");
+        assert_eq!(lex.next(), Some(Err(LexError::UnexpectedChar { ch: '$', span: Span::new(4, 5) })));
+        assert_eq!(LuaToken::lexer_bytes(b"@x").next(), Some(Err(LexError::UnexpectedChar { ch: '@', span: Span::new(0, 1) })));
+    }
+}
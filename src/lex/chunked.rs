@@ -0,0 +1,140 @@
+use super::{spanned, LexError, LuaToken};
+use crate::span::Span;
+
+/// A push-based lexer for source that arrives in pieces, e.g. over a network.
+///
+/// Tokens the next chunk could still change, such as an identifier or an
+/// unclosed long string at the end of the text so far, are held back until
+/// more text arrives or [`ChunkLexer::finish`] is called. Spans are offsets
+/// into the whole source.
+#[derive(Debug, Default)]
+pub struct ChunkLexer {
+    buffer: String,
+    /// Offset of `buffer` in the whole source.
+    offset: usize,
+    /// Bytes at the start of `buffer` already returned as tokens.
+    consumed: usize,
+}
+
+impl ChunkLexer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `chunk` and return the tokens it completed.
+    pub fn feed(&mut self, chunk: &str) -> Vec<Result<(LuaToken<'_>, Span), LexError>> {
+        self.compact();
+        self.buffer.push_str(chunk);
+        self.lex(false)
+    }
+
+    /// End the input, returning every token still held back.
+    pub fn finish(&mut self) -> Vec<Result<(LuaToken<'_>, Span), LexError>> {
+        self.compact();
+        self.lex(true)
+    }
+
+    /// Drop text already returned, which the previous call's tokens borrowed.
+    fn compact(&mut self) {
+        self.buffer.drain(..self.consumed);
+        self.offset += self.consumed;
+        self.consumed = 0;
+    }
+
+    fn lex(&mut self, last: bool) -> Vec<Result<(LuaToken<'_>, Span), LexError>> {
+        let limit = if last { self.buffer.len() } else { stable_len(&self.buffer) };
+        let offset = self.offset;
+        let mut items = Vec::new();
+        for item in spanned(&self.buffer[..limit]) {
+            let span = match &item {
+                Ok((_, span)) => *span,
+                Err(err) => err.span(),
+            };
+            let may_grow = span.end == limit && !matches!(&item, Ok((token, _)) if is_closed(token));
+            // A quote without its closing partner may just be waiting for it.
+            let open_quote = matches!(item, Err(LexError::UnexpectedChar { ch: '"' | '\'', .. }));
+            if !last && (may_grow || open_quote) {
+                break;
+            }
+            self.consumed = span.end;
+            items.push(match item {
+                Ok((token, span)) => Ok((token, Span::new(span.start + offset, span.end + offset))),
+                Err(err) => Err(err.offset_by(offset)),
+            });
+        }
+        if last {
+            self.consumed = self.buffer.len();
+        }
+        items
+    }
+}
+
+/// Whether no following text could extend `token` into a longer one.
+fn is_closed(token: &LuaToken) -> bool {
+    matches!(
+        token,
+        LuaToken::String(_)
+            | LuaToken::LongString(_)
+            | LuaToken::InterpolatedString(_)
+            | LuaToken::LParen
+            | LuaToken::RParen
+            | LuaToken::LBrace
+            | LuaToken::RBrace
+            | LuaToken::RBracket
+            | LuaToken::Comma
+            | LuaToken::Semicolon
+            | LuaToken::Plus
+            | LuaToken::Multiply
+            | LuaToken::FloorDivide
+            | LuaToken::Modulus
+            | LuaToken::Exponent
+            | LuaToken::Ampersand
+            | LuaToken::Pipe
+            | LuaToken::Length
+            | LuaToken::ShiftLeft
+            | LuaToken::ShiftRight
+            | LuaToken::DoubleEqual
+            | LuaToken::NotEqual
+            | LuaToken::LessEqual
+            | LuaToken::GreaterEqual
+            | LuaToken::Ellipsis
+            | LuaToken::DoubleColon
+    )
+}
+
+/// Length of `text` the next chunk can't change the lexing of: a trailing `[`
+/// or `[==` might still be opening a long bracket.
+fn stable_len(text: &str) -> usize {
+    match text.rfind('[') {
+        Some(start) if text[start + 1..].bytes().all(|b| b == b'=') => start,
+        _ => text.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_chunked_long_string() {
+        let mut lexer = ChunkLexer::new();
+        assert_eq!(lexer.feed("[[ab"), vec![]);
+        assert_eq!(lexer.feed("cd]]"), vec![Ok((LuaToken::LongString("abcd"), Span::new(0, 8)))]);
+        assert_eq!(lexer.finish(), vec![]);
+    }
+    #[test]
+    fn lex_chunked_split_tokens() {
+        let mut lexer = ChunkLexer::new();
+        assert_eq!(lexer.feed("loc"), vec![]);
+        let tokens = vec![
+            Ok((LuaToken::Local, Span::new(0, 5))),
+            Ok((LuaToken::Identifier("s"), Span::new(6, 7))),
+            Ok((LuaToken::Equal, Span::new(8, 9))),
+        ];
+        assert_eq!(lexer.feed("al s = 'a"), tokens);
+        assert_eq!(lexer.feed("b' ["), vec![Ok((LuaToken::String("ab"), Span::new(10, 14)))]);
+        assert_eq!(lexer.feed("=[x]=] -"), vec![Ok((LuaToken::LongString("x"), Span::new(15, 22)))]);
+        assert_eq!(lexer.feed("- note\n$"), vec![]);
+        assert_eq!(lexer.finish(), vec![Err(LexError::UnexpectedChar { ch: '$', span: Span::new(31, 32) })]);
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use logos::{Lexer, Logos, Skip};
+
+use super::{long_bracket_end, long_bracket_level, LexError, LexerBuilder, LuaToken};
+
+/// [`super::LuaToken`] without payloads, so nothing is sliced or parsed.
+/// Keywords are covered by `Word` and all operators by `Punct`.
+#[derive(Logos)]
+#[logos(error = LexError)]
+#[logos(skip r"[ \t\n\r\x0b\f]+")]
+#[logos(skip r"\#\![^\n]*")]
+enum CountToken {
+    #[regex("[a-zA-Z_][a-zA-Z_0-9]*")]
+    Word,
+    #[token("+")]
+    #[token("-")]
+    #[token("*")]
+    #[token("/")]
+    #[token("//")]
+    #[token("%")]
+    #[token("^")]
+    #[token("&")]
+    #[token("|")]
+    #[token("~")]
+    #[token("<<")]
+    #[token(">>")]
+    #[token("==")]
+    #[token("=")]
+    #[token("~=")]
+    #[token(">")]
+    #[token("<")]
+    #[token(">=")]
+    #[token("<=")]
+    #[token(".")]
+    #[token("..")]
+    #[token("...")]
+    #[token("#")]
+    #[token("[")]
+    #[token("]")]
+    #[token("(")]
+    #[token(")")]
+    #[token("{")]
+    #[token("}")]
+    #[token(",")]
+    #[token(";")]
+    #[token(":")]
+    #[token("::")]
+    Punct,
+    #[token("--", skip_comment)]
+    Comment,
+    #[regex(r#""([^"\\\n]|\\(.|\n|\r\n))*"|'([^'\\\n]|\\(.|\n|\r\n))*'"#)]
+    String,
+    #[regex(r"\[=*\[", skip_long_string)]
+    LongString,
+    #[regex(r"[0-9][0-9_]*|0[xX][0-9a-fA-F][0-9a-fA-F_]*")]
+    #[regex(r"[0-9][0-9_]*(\.([0-9][0-9_]*)?([eE][+-]?[0-9]+)?|[eE][+-]?[0-9]+)|0[xX][0-9a-fA-F][0-9a-fA-F_]*(\.([0-9a-fA-F][0-9a-fA-F_]*)?([pP][+-]?[0-9]+)?|[pP][+-]?[0-9]+)")]
+    Number,
+}
+
+/// Count the tokens in `source` without decoding any of them, for a quick size
+/// estimate. Comments aren't counted.
+///
+/// For source that lexes without errors this equals `tokenize(source)?.len()`.
+/// Errors aren't counted either, but a bad number like `3abc` may count as two
+/// tokens rather than an error.
+pub fn count_tokens(source: &str) -> usize {
+    CountToken::lexer(source).filter(Result::is_ok).count()
+}
+
+/// How often each name appears in `source`, for a quick "most used names"
+/// report. Keywords, strings and comments don't count, and lex errors are
+/// skipped over.
+pub fn identifier_frequencies(source: &str) -> HashMap<String, usize> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for token in LexerBuilder::new().build(source).flatten() {
+        if let LuaToken::Identifier(name) = token {
+            *counts.entry(name).or_default() += 1;
+        }
+    }
+    counts.into_iter().map(|(name, count)| (name.to_string(), count)).collect()
+}
+
+fn skip_comment(text: &mut Lexer<CountToken>) -> Result<Skip, LexError> {
+    let rest = text.remainder();
+    let len = match long_bracket_level(rest) {
+        Some(level) => long_bracket_end(rest, level).ok_or_else(|| LexError::UnterminatedComment { span: text.span().into() })?,
+        None => rest.find('\n').unwrap_or(rest.len()),
+    };
+    text.bump(len);
+    Ok(Skip)
+}
+
+fn skip_long_string(text: &mut Lexer<CountToken>) -> Result<(), LexError> {
+    let level = text.slice().len() - 2;
+    let end = long_bracket_end(text.remainder(), level)
+        .ok_or_else(|| LexError::UnterminatedString { span: text.span().into() })?;
+    text.bump(end);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::tokenize;
+
+    #[test]
+    fn lex_count_matches_tokenize() {
+        let sources = [
+            "",
+            "local x = 1 + 2.5e3 // 17 ^ 1_000",
+            "#!/usr/bin/lua\nprint('a\\'b', \"c\") -- trailing",
+            "s = [==[ long ]] still ]==] .. [[x]] --[[ long\ncomment ]] y",
+            "t = {a.b, c:d(...), [1] = e >> 2 ~= f, ::label::}",
+            "goto x; while a <= b do a = a .. #b end",
+            "x = 3. + 0x1p4 - 0xA.8P1 * 3.e2",
+            "local x = 1\r\n\x0breturn x\r\n",
+        ];
+        for source in sources {
+            assert_eq!(count_tokens(source), tokenize(source).unwrap().len(), "{source}");
+        }
+    }
+    #[test]
+    fn lex_identifier_frequencies() {
+        let source = "local x = y + x -- x\nprint(x, 'x y', [[y]], t.y) $ y";
+        let counts = identifier_frequencies(source);
+        let expected = [("x", 3), ("y", 3), ("print", 1), ("t", 1)];
+        assert_eq!(counts, expected.into_iter().map(|(name, count)| (name.to_string(), count)).collect());
+        assert!(identifier_frequencies("local function end").is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::span::Span;
+
+/// Errors in the escape sequences of a short string.
+///
+/// Spans are relative to the start of the string body, just past the opening quote.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EscapeError {
+    /// A backslash followed by a character that starts no escape, e.g. `\q`.
+    InvalidEscape { span: Span },
+    /// A `\ddd` escape above 255.
+    EscapeTooLarge { span: Span },
+    /// A `\x` escape without two hex digits. The span ends after the first non-digit.
+    InvalidHexEscape { span: Span },
+}
+
+impl EscapeError {
+    pub fn span(&self) -> Span {
+        match self {
+            EscapeError::InvalidEscape { span }
+            | EscapeError::EscapeTooLarge { span }
+            | EscapeError::InvalidHexEscape { span } => *span,
+        }
+    }
+}
+
+impl fmt::Display for EscapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EscapeError::InvalidEscape { .. } => write!(f, "invalid escape sequence"),
+            EscapeError::EscapeTooLarge { .. } => write!(f, "decimal escape too large"),
+            EscapeError::InvalidHexEscape { .. } => write!(f, "hexadecimal digit expected"),
+        }
+    }
+}
+
+impl std::error::Error for EscapeError {}
+
+/// Decode the escapes in `raw`, the body of a short string between its quotes.
+///
+/// Lua strings are bytes, and escapes like `\255` or `\xff` needn't form UTF-8.
+pub fn decode_escapes(raw: &str) -> Result<Vec<u8>, EscapeError> {
+    let mut out = Vec::new();
+    decode_escapes_into(raw, &mut out)?;
+    Ok(out)
+}
+
+/// Decode like [`decode_escapes`] onto `out`, which is cleared first so one
+/// buffer can be reused across many strings.
+pub fn decode_escapes_into(raw: &str, out: &mut Vec<u8>) -> Result<(), EscapeError> {
+    out.clear();
+    let mut rest = raw;
+    while let Some(backslash) = rest.find('\\') {
+        out.extend_from_slice(&rest.as_bytes()[..backslash]);
+        let offset = raw.len() - rest.len() + backslash;
+        let Some(escape) = rest[backslash + 1..].chars().next() else {
+            return Err(EscapeError::InvalidEscape { span: Span::new(offset, offset + 1) });
+        };
+        let after = &rest[backslash + 1 + escape.len_utf8()..];
+        let decoded = match escape {
+            'a' => b'\x07',
+            'b' => b'\x08',
+            'f' => b'\x0c',
+            'n' => b'\n',
+            'r' => b'\r',
+            't' => b'\t',
+            'v' => b'\x0b',
+            // A backslash before a line break keeps it, whether `\n`, `\r`, `\r\n` or `\n\r`.
+            '\n' | '\r' => {
+                let pair = if escape == '\n' { '\r' } else { '\n' };
+                out.push(b'\n');
+                rest = after.strip_prefix(pair).unwrap_or(after);
+                continue;
+            }
+            '\\' | '"' | '\'' => escape as u8,
+            // `\z` skips the whitespace that follows it.
+            'z' => {
+                rest = after.trim_start_matches([' ', '\t', '\n', '\r', '\x0b', '\x0c']);
+                continue;
+            }
+            // `\xXX` takes exactly two hex digits.
+            'x' => {
+                let digits = after.bytes().take(2).take_while(u8::is_ascii_hexdigit).count();
+                if digits < 2 {
+                    let bad = after[digits..].chars().next().map_or(0, char::len_utf8);
+                    return Err(EscapeError::InvalidHexEscape { span: Span::new(offset, offset + 2 + digits + bad) });
+                }
+                out.push(u8::from_str_radix(&after[..2], 16).unwrap());
+                rest = &after[2..];
+                continue;
+            }
+            // `\ddd` takes at most three digits, so `\1234` is byte 123 then `4`.
+            '0'..='9' => {
+                let digits = rest[backslash + 1..].bytes().take(3).take_while(u8::is_ascii_digit).count();
+                let end = backslash + 1 + digits;
+                let value: u32 = rest[backslash + 1..end].parse().unwrap();
+                let Ok(byte) = u8::try_from(value) else {
+                    return Err(EscapeError::EscapeTooLarge { span: Span::new(offset, offset + 1 + digits) });
+                };
+                out.push(byte);
+                rest = &rest[end..];
+                continue;
+            }
+            _ => return Err(EscapeError::InvalidEscape { span: Span::new(offset, offset + 1 + escape.len_utf8()) }),
+        };
+        out.push(decoded);
+        rest = after;
+    }
+    out.extend_from_slice(rest.as_bytes());
+    Ok(())
+}
+
+/// Turn every line break in `text`, the contents of a long string, into `\n`,
+/// as Lua does. `\r\n` and `\n\r` count as one break.
+pub fn normalize_newlines(text: &str) -> Cow<'_, str> {
+    if !text.contains('\r') {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(newline) = rest.find(['\n', '\r']) {
+        out.push_str(&rest[..newline]);
+        out.push('\n');
+        let pair = if rest.as_bytes()[newline] == b'\n' { "\n\r" } else { "\r\n" };
+        let len = if rest[newline..].starts_with(pair) { 2 } else { 1 };
+        rest = &rest[newline + len..];
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_escape_simple() {
+        assert_eq!(decode_escapes(r#"a\tb\n\\\"\'"#), Ok(b"a\tb\n\\\"'".to_vec()));
+        assert_eq!(decode_escapes(r"\a\b\f\v\r"), Ok(b"\x07\x08\x0c\x0b\r".to_vec()));
+        assert_eq!(decode_escapes("plain"), Ok(b"plain".to_vec()));
+    }
+    #[test]
+    fn lex_escape_skip_whitespace() {
+        assert_eq!(decode_escapes("a\\z  \t b"), Ok(b"ab".to_vec()));
+    }
+    #[test]
+    fn lex_escape_line_continuation() {
+        assert_eq!(decode_escapes("line1\\\nline2"), Ok(b"line1\nline2".to_vec()));
+        assert_eq!(decode_escapes("a\\\r\nb\\\rc"), Ok(b"a\nb\nc".to_vec()));
+    }
+    #[test]
+    fn lex_escape_decimal() {
+        assert_eq!(decode_escapes(r"\65"), Ok(b"A".to_vec()));
+        assert_eq!(decode_escapes(r"\0653"), Ok(b"A3".to_vec()));
+        assert_eq!(decode_escapes(r"\1234"), Ok(b"{4".to_vec()));
+        assert_eq!(decode_escapes(r"a\0b\9"), Ok(b"a\0b\t".to_vec()));
+        assert_eq!(decode_escapes(r"\226\130\172"), Ok("\u{20ac}".into()));
+        assert_eq!(decode_escapes(r"x\300"), Err(EscapeError::EscapeTooLarge { span: Span::new(1, 5) }));
+        assert_eq!(decode_escapes(r"\255"), Ok(vec![255]));
+        assert_eq!(decode_escapes(r"\256"), Err(EscapeError::EscapeTooLarge { span: Span::new(0, 4) }));
+    }
+    #[test]
+    fn lex_escape_hex() {
+        assert_eq!(decode_escapes(r"\x41"), Ok(b"A".to_vec()));
+        assert_eq!(decode_escapes(r"\x4a\x4B1"), Ok(b"JK1".to_vec()));
+        assert_eq!(decode_escapes(r"\xe2\x82\xac"), Ok("\u{20ac}".into()));
+        assert_eq!(decode_escapes(r"\xFF\x80"), Ok(vec![0xff, 0x80]));
+        assert_eq!(decode_escapes(r"\x4"), Err(EscapeError::InvalidHexEscape { span: Span::new(0, 3) }));
+        assert_eq!(decode_escapes(r"a\x4z"), Err(EscapeError::InvalidHexEscape { span: Span::new(1, 5) }));
+        assert_eq!(decode_escapes(r"\xGG"), Err(EscapeError::InvalidHexEscape { span: Span::new(0, 3) }));
+        assert_eq!(decode_escapes(r"\xGG").unwrap_err().to_string(), "hexadecimal digit expected");
+    }
+    #[test]
+    fn lex_escape_invalid() {
+        assert_eq!(decode_escapes(r"ab\q"), Err(EscapeError::InvalidEscape { span: Span::new(2, 4) }));
+        assert_eq!(decode_escapes("\\"), Err(EscapeError::InvalidEscape { span: Span::new(0, 1) }));
+    }
+    #[test]
+    fn lex_escape_long_string_newlines() {
+        assert_eq!(normalize_newlines("a\r\nb\rc\n\rd\ne\n\nf"), "a\nb\nc\nd\ne\n\nf");
+        assert!(matches!(normalize_newlines("no\ncarriage returns"), Cow::Borrowed(_)));
+    }
+    #[test]
+    fn lex_escape_reuse_matches_fresh() {
+        let mut scratch = b"stale contents".to_vec();
+        for raw in [r"x\ty", r"\\", "", r#"say \"hi\"\n"#, r"a\z   b"] {
+            decode_escapes_into(raw, &mut scratch).unwrap();
+            assert_eq!(scratch, decode_escapes(raw).unwrap());
+        }
+        let capacity = scratch.capacity();
+        assert!(decode_escapes_into(r"\256", &mut scratch).is_err());
+        assert_eq!(scratch.capacity(), capacity);
+    }
+}
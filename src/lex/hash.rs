@@ -0,0 +1,74 @@
+use super::{spanned, LuaToken};
+
+/// A hash of the tokens of `source`, kinds and payloads, for caching work on it.
+///
+/// Whitespace and comments don't count, so reformatting leaves the hash alone.
+/// Uses FNV-1a rather than [`std::hash::DefaultHasher`], whose output may change
+/// between Rust releases. Text that fails to lex is hashed as written.
+pub fn token_hash(source: &str) -> u64 {
+    let mut hasher = Fnv1a::default();
+    for result in spanned(source) {
+        let (token, _) = match result {
+            Ok(token) => token,
+            Err(err) => {
+                hasher.write(&u16::MAX.to_le_bytes());
+                hasher.write_str(&source[err.span().start..err.span().end]);
+                continue;
+            }
+        };
+        hasher.write(&(token.kind() as u16).to_le_bytes());
+        match token {
+            LuaToken::Identifier(text)
+            | LuaToken::String(text)
+            | LuaToken::LongString(text)
+            | LuaToken::Annotation(text)
+            | LuaToken::InterpolatedString(text) => hasher.write_str(text),
+            LuaToken::Integer(value) => hasher.write(&value.to_le_bytes()),
+            LuaToken::Float(value) => hasher.write(&value.to_bits().to_le_bytes()),
+            LuaToken::CompoundAssign(op) => hasher.write(&[op as u8]),
+            _ => {}
+        }
+    }
+    hasher.0
+}
+
+/// The 64-bit Fowler–Noll–Vo hash, FNV-1a variant.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    /// Write `text` after its length, so `"ab" "c"` and `"a" "bc"` differ.
+    fn write_str(&mut self, text: &str) {
+        self.write(&(text.len() as u64).to_le_bytes());
+        self.write(text.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_token_hash() {
+        let hash = token_hash("local x = f(1, 'a')");
+        assert_eq!(token_hash("local   x=f( 1,\n  'a' ) -- call f"), hash);
+        assert_eq!(token_hash("--[[ header ]]\nlocal x = f(1, 'a')\n"), hash);
+        assert_ne!(token_hash("local y = f(1, 'a')"), hash);
+        assert_ne!(token_hash("local x = f(2, 'a')"), hash);
+        assert_ne!(token_hash("local x = f(1, 'b')"), hash);
+        assert_ne!(token_hash("local x = f(1.0, 'a')"), hash);
+        assert_ne!(token_hash("local x = f(1, 'a') $"), hash);
+        assert_ne!(token_hash("local x = f(1, 'a') ?"), token_hash("local x = f(1, 'a') $"));
+    }
+}
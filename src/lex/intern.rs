@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use super::{spanned, LexError, LuaToken};
+use crate::span::Span;
+
+/// An interned identifier, cheap to copy, compare and hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(pub u32);
+
+/// Maps identifier names to [`Symbol`]s, the same name always getting the same one.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    symbols: HashMap<String, Symbol>,
+    names: Vec<String>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.symbols.insert(name.to_string(), symbol);
+        symbol
+    }
+
+    /// The name `symbol` was interned from.
+    ///
+    /// Panics if `symbol` came from another interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+
+    /// Number of distinct names interned so far.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Lex all of `source` like [`super::tokenize`], interning every identifier
+    /// and pairing it with its symbol.
+    pub fn tokenize<'source>(
+        &mut self,
+        source: &'source str,
+    ) -> Result<Vec<(LuaToken<'source>, Span, Option<Symbol>)>, LexError> {
+        spanned(source)
+            .map(|item| {
+                let (token, span) = item?;
+                let symbol = token.as_identifier().map(|name| self.intern(name));
+                Ok((token, span, symbol))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_intern_repeated_names() {
+        let mut interner = Interner::new();
+        let tokens = interner.tokenize("local x = y + x\nx = y.z").unwrap();
+        let symbols: Vec<Symbol> = tokens.iter().filter_map(|(_, _, symbol)| *symbol).collect();
+        assert_eq!(symbols.len(), 6);
+        assert_eq!(interner.len(), 3);
+        assert_eq!(symbols[0], symbols[2]);
+        assert_eq!(symbols[0], symbols[3]);
+        assert_eq!(symbols[1], symbols[4]);
+        assert_ne!(symbols[0], symbols[1]);
+        assert_eq!(interner.resolve(symbols[5]), "z");
+        assert_eq!(interner.intern("y"), symbols[1]);
+        assert!(tokens.iter().all(|(token, _, symbol)| symbol.is_some() == token.as_identifier().is_some()));
+    }
+}
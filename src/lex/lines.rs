@@ -0,0 +1,43 @@
+use super::{spanned, LuaToken};
+use crate::span::Span;
+
+/// The tokens of `source` grouped by the line they start on, one group per
+/// line so blank and comment-only lines give an empty group.
+///
+/// A token spanning lines, such as a long string, belongs to the line it starts
+/// on. Lex errors are skipped over rather than ending the scan.
+pub fn tokens_by_line(source: &str) -> Vec<Vec<(LuaToken<'_>, Span)>> {
+    let mut lines = vec![Vec::new(); source.matches('\n').count() + 1];
+    let (mut line, mut last) = (0, 0);
+    for (token, span) in spanned(source).filter_map(Result::ok) {
+        line += source[last..span.start].matches('\n').count();
+        last = span.start;
+        lines[line].push((token, span));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_tokens_by_line() {
+        let source = "local a = 1\nb = [[x\ny]] c\n\n  d";
+        let lines = tokens_by_line(source);
+        let kinds: Vec<Vec<LuaToken>> = lines.iter().map(|line| line.iter().map(|(token, _)| token.clone()).collect()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                vec![LuaToken::Local, LuaToken::Identifier("a"), LuaToken::Equal, LuaToken::Integer(1)],
+                vec![LuaToken::Identifier("b"), LuaToken::Equal, LuaToken::LongString("x\ny")],
+                vec![LuaToken::Identifier("c")],
+                vec![],
+                vec![LuaToken::Identifier("d")],
+            ]
+        );
+        assert_eq!(lines[1][2].1, Span::new(16, 23));
+        assert_eq!(lines[4][0].1, Span::new(29, 30));
+        assert!(tokens_by_line("").iter().all(Vec::is_empty));
+    }
+}
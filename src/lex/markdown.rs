@@ -0,0 +1,113 @@
+use super::{spanned, LexError, LuaToken};
+use crate::span::Span;
+
+/// The tokens of one fenced Lua block in a Markdown document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LuaFence<'source> {
+    /// The code between the fences, excluding the fence lines themselves.
+    pub span: Span,
+    pub tokens: Vec<Result<(LuaToken<'source>, Span), LexError>>,
+}
+
+/// Lex the code in every ```` ```lua ```` or ```` ```luau ```` fence of `md`,
+/// skipping other fences and the prose around them.
+///
+/// Spans are offsets into `md`. A fence left open runs to the end of the document.
+pub fn lex_markdown_lua(md: &str) -> Vec<LuaFence<'_>> {
+    let mut fences = Vec::new();
+    let mut lines = lines_with_offsets(md);
+    while let Some((_, line)) = lines.next() {
+        let Some((marker, info)) = opening_fence(line) else {
+            continue;
+        };
+        let is_lua = matches!(info.split_whitespace().next(), Some("lua" | "luau"));
+        let start = lines.clone().next().map_or(md.len(), |(offset, _)| offset);
+        let mut end = md.len();
+        for (offset, line) in lines.by_ref() {
+            if is_closing_fence(line, marker) {
+                end = offset;
+                break;
+            }
+        }
+        if is_lua {
+            let tokens = spanned(&md[start..end])
+                .map(|item| match item {
+                    Ok((token, span)) => Ok((token, Span::new(span.start + start, span.end + start))),
+                    Err(err) => Err(err.offset_by(start)),
+                })
+                .collect();
+            fences.push(LuaFence { span: Span::new(start, end), tokens });
+        }
+    }
+    fences
+}
+
+/// Each line of `text` with its offset, keeping the line break.
+fn lines_with_offsets(text: &str) -> impl Iterator<Item = (usize, &str)> + Clone {
+    text.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line))
+    })
+}
+
+/// The fence marker, e.g. `` ``` `` or `~~~~`, and info string of an opening fence line.
+fn opening_fence(line: &str) -> Option<(&str, &str)> {
+    let trimmed = strip_indent(line)?;
+    let fence_char = trimmed.chars().next().filter(|ch| matches!(ch, '`' | '~'))?;
+    let len = trimmed.len() - trimmed.trim_start_matches(fence_char).len();
+    let (marker, info) = trimmed.split_at(len);
+    // Backtick fences can't have backticks in their info string.
+    if len < 3 || (fence_char == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((marker, info.trim()))
+}
+
+/// Whether `line` closes a fence opened with `marker`.
+fn is_closing_fence(line: &str, marker: &str) -> bool {
+    let Some(trimmed) = strip_indent(line) else {
+        return false;
+    };
+    let fence_char = marker.chars().next().unwrap_or('`');
+    let rest = trimmed.trim_start_matches(fence_char);
+    trimmed.len() - rest.len() >= marker.len() && rest.trim().is_empty()
+}
+
+/// `line` without the up to three spaces a fence may be indented by.
+fn strip_indent(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start_matches(' ');
+    (line.len() - trimmed.len() <= 3).then_some(trimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_markdown_fences() {
+        let md = "# Intro\n```lua\nlocal x = 1\n```\n\n```sh\nls -la\n```\n~~~~ luau\nreturn x\n~~~~\n";
+        let fences = lex_markdown_lua(md);
+        assert_eq!(fences.len(), 2);
+        assert_eq!(fences[0].span, Span::new(15, 27));
+        assert_eq!(
+            fences[0].tokens,
+            vec![
+                Ok((LuaToken::Local, Span::new(15, 20))),
+                Ok((LuaToken::Identifier("x"), Span::new(21, 22))),
+                Ok((LuaToken::Equal, Span::new(23, 24))),
+                Ok((LuaToken::Integer(1), Span::new(25, 26))),
+            ]
+        );
+        let (token, span) = fences[1].tokens[0].clone().unwrap();
+        assert_eq!(token, LuaToken::Return);
+        assert_eq!(&md[span.start..span.end], "return");
+    }
+    #[test]
+    fn lex_markdown_unclosed_fence() {
+        let fences = lex_markdown_lua("```lua\nx = [[\n``\n");
+        assert_eq!(fences.len(), 1);
+        assert_eq!(fences[0].span, Span::new(7, 17));
+        assert!(matches!(fences[0].tokens.last(), Some(Err(LexError::UnterminatedString { .. }))));
+    }
+}
@@ -0,0 +1,94 @@
+use super::{LuaToken, TokenKind};
+use crate::ast::BinOp;
+use crate::span::Span;
+
+/// A [`LuaToken`] owning its text, so it can outlive the source it was lexed from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedLuaToken {
+    /// A keyword or operator, which carries nothing but its kind.
+    Kind(TokenKind),
+    Comment(String),
+    Identifier(String),
+    /// The raw body between the quotes, as in [`LuaToken::String`].
+    String(String),
+    LongString(String),
+    Integer(i64),
+    Float(f64),
+    CompoundAssign(BinOp),
+    Annotation(String),
+    /// The raw body between the backticks, as in [`LuaToken::InterpolatedString`].
+    InterpolatedString(String),
+}
+
+impl OwnedLuaToken {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            OwnedLuaToken::Kind(kind) => *kind,
+            OwnedLuaToken::Comment(_) => TokenKind::Comment,
+            OwnedLuaToken::Identifier(_) => TokenKind::Identifier,
+            OwnedLuaToken::String(_) => TokenKind::String,
+            OwnedLuaToken::LongString(_) => TokenKind::LongString,
+            OwnedLuaToken::Integer(_) => TokenKind::Integer,
+            OwnedLuaToken::Float(_) => TokenKind::Float,
+            OwnedLuaToken::CompoundAssign(_) => TokenKind::CompoundAssign,
+            OwnedLuaToken::Annotation(_) => TokenKind::Annotation,
+            OwnedLuaToken::InterpolatedString(_) => TokenKind::InterpolatedString,
+        }
+    }
+}
+
+impl From<&LuaToken<'_>> for OwnedLuaToken {
+    fn from(token: &LuaToken<'_>) -> Self {
+        match token {
+            LuaToken::Comment(text) => OwnedLuaToken::Comment(text.to_string()),
+            LuaToken::Identifier(name) => OwnedLuaToken::Identifier(name.to_string()),
+            LuaToken::String(raw) => OwnedLuaToken::String(raw.to_string()),
+            LuaToken::LongString(text) => OwnedLuaToken::LongString(text.to_string()),
+            LuaToken::Integer(value) => OwnedLuaToken::Integer(*value),
+            LuaToken::Float(value) => OwnedLuaToken::Float(*value),
+            LuaToken::CompoundAssign(op) => OwnedLuaToken::CompoundAssign(*op),
+            LuaToken::Annotation(name) => OwnedLuaToken::Annotation(name.to_string()),
+            LuaToken::InterpolatedString(raw) => OwnedLuaToken::InterpolatedString(raw.to_string()),
+            token => OwnedLuaToken::Kind(token.kind()),
+        }
+    }
+}
+
+/// Copy spanned tokens, e.g. from [`super::tokenize`], into owned form free of
+/// the source's lifetime.
+pub fn to_owned_tokens<'source>(
+    tokens: impl IntoIterator<Item = (LuaToken<'source>, Span)>,
+) -> Vec<(OwnedLuaToken, Span)> {
+    tokens.into_iter().map(|(token, span)| (OwnedLuaToken::from(&token), span)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::tokenize;
+
+    #[test]
+    fn lex_to_owned_tokens() {
+        let source = String::from("local s = 'a' .. [[b]] + 0x10");
+        let borrowed = tokenize(&source).unwrap();
+        let tokens = to_owned_tokens(borrowed.clone());
+        assert!(tokens.iter().zip(&borrowed).all(|((owned, a), (token, b))| owned.kind() == token.kind() && a == b));
+        assert_eq!(tokens.len(), borrowed.len());
+        drop(source);
+        assert_eq!(
+            tokens,
+            vec![
+                (OwnedLuaToken::Kind(TokenKind::Local), Span::new(0, 5)),
+                (OwnedLuaToken::Identifier("s".to_string()), Span::new(6, 7)),
+                (OwnedLuaToken::Kind(TokenKind::Equal), Span::new(8, 9)),
+                (OwnedLuaToken::String("a".to_string()), Span::new(10, 13)),
+                (OwnedLuaToken::Kind(TokenKind::Concatenate), Span::new(14, 16)),
+                (OwnedLuaToken::LongString("b".to_string()), Span::new(17, 22)),
+                (OwnedLuaToken::Kind(TokenKind::Plus), Span::new(23, 24)),
+                (OwnedLuaToken::Integer(16), Span::new(25, 29)),
+            ]
+        );
+        fn is_static<T: 'static>(_: &T) {}
+        is_static(&tokens);
+    }
+}
@@ -0,0 +1,75 @@
+use super::{spanned, LuaToken};
+use crate::span::Span;
+
+/// The tokens of `source` overlapping `range`, e.g. an editor selection. An
+/// empty `range`, a cursor, gives the token it sits in, if any.
+///
+/// Lexing stops once past the end of `range`, and lex errors are skipped over.
+pub fn tokens_in_range(source: &str, range: Span) -> Vec<(LuaToken<'_>, Span)> {
+    let overlaps = |span: &Span| match range.is_empty() {
+        true => span.contains(range.start),
+        false => span.start < range.end && range.start < span.end,
+    };
+    spanned(source)
+        .filter_map(Result::ok)
+        .take_while(|(_, span)| span.start < range.end || (range.is_empty() && span.start <= range.start))
+        .filter(|(_, span)| overlaps(span))
+        .collect()
+}
+
+/// The token under a cursor at byte `offset`, e.g. for hover: the token
+/// containing `offset`, or failing that one ending right at it, so a cursor
+/// just after a name still finds it. `None` in whitespace, comments or past
+/// the end.
+pub fn token_at(source: &str, offset: usize) -> Option<(LuaToken<'_>, Span)> {
+    let mut before = None;
+    for (token, span) in spanned(source).filter_map(Result::ok) {
+        if span.start > offset {
+            break;
+        }
+        if span.contains(offset) {
+            return Some((token, span));
+        }
+        if span.end == offset {
+            before = Some((token, span));
+        }
+    }
+    before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_tokens_in_range() {
+        let source = "local total = price * count -- sum";
+        let tokens = tokens_in_range(source, Span::new(10, 17));
+        assert_eq!(
+            tokens,
+            vec![
+                (LuaToken::Identifier("total"), Span::new(6, 11)),
+                (LuaToken::Equal, Span::new(12, 13)),
+                (LuaToken::Identifier("price"), Span::new(14, 19)),
+            ]
+        );
+        assert_eq!(tokens_in_range(source, Span::empty(21)), vec![]);
+        assert_eq!(tokens_in_range(source, Span::empty(20)), vec![(LuaToken::Multiply, Span::new(20, 21))]);
+        assert_eq!(tokens_in_range(source, Span::new(28, 35)), vec![]);
+    }
+    #[test]
+    fn lex_token_at() {
+        let source = "local total = t.x  -- sum";
+        assert_eq!(token_at(source, 8), Some((LuaToken::Identifier("total"), Span::new(6, 11))));
+        assert_eq!(token_at(source, 6), Some((LuaToken::Identifier("total"), Span::new(6, 11))));
+        // Just after a name, with only whitespace following.
+        assert_eq!(token_at(source, 11), Some((LuaToken::Identifier("total"), Span::new(6, 11))));
+        // Between two touching tokens, the one starting there wins.
+        assert_eq!(token_at(source, 15), Some((LuaToken::Dot, Span::new(15, 16))));
+        assert_eq!(token_at(source, 17), Some((LuaToken::Identifier("x"), Span::new(16, 17))));
+        assert_eq!(token_at(source, 18), None);
+        assert_eq!(token_at(source, 22), None);
+        assert_eq!(token_at(source, 100), None);
+        assert_eq!(token_at("", 0), None);
+    }
+}
@@ -0,0 +1,66 @@
+use std::borrow::Cow;
+
+use super::{decode_escapes, normalize_newlines, spanned, LuaToken};
+use crate::span::Span;
+
+/// Find every `require "mod"` and `require("mod")` call in `source` from its
+/// tokens alone, returning each module name with the span of its string literal.
+///
+/// Only calls on the bare name `require` with a single literal argument count,
+/// so `m.require "x"` and `require(name)` are skipped, as are strings with bad
+/// escapes. Lex errors are skipped over rather than ending the scan.
+pub fn extract_requires(source: &str) -> Vec<(String, Span)> {
+    let tokens: Vec<(LuaToken, Span)> = spanned(source).filter_map(Result::ok).collect();
+    let mut requires = Vec::new();
+    for (i, (token, _)) in tokens.iter().enumerate() {
+        if token.as_identifier() != Some("require") {
+            continue;
+        }
+        if i > 0 && matches!(tokens[i - 1].0, LuaToken::Dot | LuaToken::Colon) {
+            continue;
+        }
+        let arg = match tokens.get(i + 1..i + 4) {
+            Some([(LuaToken::LParen, _), arg, (LuaToken::RParen, _)]) => arg,
+            _ => match tokens.get(i + 1) {
+                Some(arg) => arg,
+                None => continue,
+            },
+        };
+        let name = match arg.0 {
+            // Module names that aren't UTF-8 can't be reported, so skip them.
+            LuaToken::String(raw) => match decode_escapes(raw).map(String::from_utf8) {
+                Ok(Ok(name)) => name,
+                _ => continue,
+            },
+            LuaToken::LongString(text) => match normalize_newlines(text) {
+                Cow::Borrowed(text) => text.to_string(),
+                Cow::Owned(text) => text,
+            },
+            _ => continue,
+        };
+        requires.push((name, arg.1));
+    }
+    requires
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_require_forms() {
+        let source = "local a = require \"a.b\"\nlocal c = require('c')\nrequire[[d]]\n\
+                      local e = require ( \"e\\65\" ).field\nrequire(name)\nm.require 'no'\nobj:require'no'\n\
+                      print(\"require\"); require";
+        let names: Vec<(String, Span)> = extract_requires(source);
+        assert_eq!(
+            names,
+            vec![
+                ("a.b".to_string(), Span::new(18, 23)),
+                ("c".to_string(), Span::new(42, 45)),
+                ("d".to_string(), Span::new(54, 59)),
+                ("eA".to_string(), Span::new(80, 86)),
+            ]
+        );
+    }
+}
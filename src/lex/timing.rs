@@ -0,0 +1,33 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use super::LexerBuilder;
+
+/// Lex all of `source` and time it, for measuring throughput on your own input
+/// without a benchmark harness. Returns the token count, the time taken and the
+/// tokens lexed per second, 0 if it was too quick to measure.
+///
+/// Tokens are counted like [`super::count_tokens`], skipping comments and errors.
+pub fn lex_and_time(source: &str) -> (usize, Duration, f64) {
+    let start = Instant::now();
+    let tokens = LexerBuilder::new().build(source).filter(|token| black_box(token).is_ok()).count();
+    let elapsed = start.elapsed();
+    let per_sec = if elapsed.is_zero() { 0.0 } else { tokens as f64 / elapsed.as_secs_f64() };
+    (tokens, elapsed, per_sec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::tokenize;
+
+    #[test]
+    fn lex_timing() {
+        let source = "local t = {} for i = 1, 100 do t[i] = i * 2 .. 'x' end -- fill\n".repeat(1000);
+        let (tokens, elapsed, per_sec) = lex_and_time(&source);
+        assert_eq!(tokens, tokenize(&source).unwrap().len());
+        assert!(!elapsed.is_zero());
+        assert!(per_sec > 0.0);
+        assert_eq!(lex_and_time("").0, 0);
+    }
+}
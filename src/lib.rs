@@ -0,0 +1,19 @@
+//! Tom's Lua Compiler.
+
+pub mod ast;
+pub mod cst;
+pub mod diagnostic;
+pub mod eval;
+pub mod flow;
+pub mod fold;
+pub mod highlight;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod lex;
+pub mod parse;
+pub mod print;
+pub mod resolve;
+pub mod source;
+pub mod span;
+pub mod transform;
+pub mod validate;
@@ -1,5 +1,3 @@
-mod lex;
-
 fn main() {
     println!("Hello, world!");
 }
@@ -0,0 +1,1732 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{
+    Attrib, BinOp, Block, Expr, FuncName, FuncTypes, Stmt, StringPart, TableField, TypeExpr, UnOp, SUFFIX_PRECEDENCE, UNARY_PRECEDENCE,
+};
+use crate::diagnostic::Diagnostic;
+use crate::lex::{
+    decode_escapes, interpolation_hole_end, normalize_newlines, tokenize, Dialect, LexError, LexExtras, LexerBuilder, LuaToken,
+    TokenKind,
+};
+use crate::span::{Span, Spanned};
+
+/// A syntax error at `span`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+    /// The dialect that rejected the syntax, when that's why it failed.
+    pub dialect: Option<Dialect>,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        ParseError { message: message.into(), span, dialect: None }
+    }
+
+    /// The same error, blamed on `dialect` lacking the syntax.
+    pub fn in_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = Some(dialect);
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A lex error met while parsing, keeping its message, span and dialect.
+impl From<LexError> for ParseError {
+    fn from(err: LexError) -> Self {
+        ParseError { message: err.to_string(), span: err.span(), dialect: err.dialect() }
+    }
+}
+
+/// Lex and parse `source` into its main block, collecting every lex and parse
+/// diagnostic rather than stopping at the first.
+pub fn parse(source: &str) -> Result<Block, Vec<Diagnostic>> {
+    parse_dialect(source, Dialect::default())
+}
+
+/// Parse like [`parse`], accepting the syntax of `dialect`.
+pub fn parse_dialect(source: &str, dialect: Dialect) -> Result<Block, Vec<Diagnostic>> {
+    parse_limited(source, dialect, None)
+}
+
+/// Parse like [`parse_dialect`], giving up once `max_errors` diagnostics are
+/// found, so badly broken input doesn't bury the first errors in cascades of
+/// later ones. Giving up adds a final `too-many-errors` diagnostic, the only
+/// one when `max_errors` is 0.
+pub fn parse_with_max_errors(source: &str, dialect: Dialect, max_errors: usize) -> Result<Block, Vec<Diagnostic>> {
+    parse_limited(source, dialect, Some(max_errors))
+}
+
+fn parse_limited(source: &str, dialect: Dialect, max_errors: Option<usize>) -> Result<Block, Vec<Diagnostic>> {
+    let (tokens, lex_errors) = LexerBuilder::new().dialect(dialect).tokenize_recovering(source);
+    let mut parser = Parser::from_tokens(tokens, source).with_dialect(dialect).with_max_errors(max_errors);
+    let block = parser.program();
+
+    let mut diagnostics: Vec<Diagnostic> = lex_errors.into_iter().map(Diagnostic::from).collect();
+    let stopped = parser.stopped;
+    diagnostics.extend(parser.errors.into_iter().map(Diagnostic::from));
+    // With a cap of 0 the parser stops without keeping any error.
+    if diagnostics.is_empty() && !stopped {
+        return Ok(block);
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    if let Some(max) = max_errors
+        && (stopped || diagnostics.len() > max)
+    {
+        diagnostics.truncate(max);
+        let end = diagnostics.last().map_or(0, |diagnostic| diagnostic.span.end);
+        let message = format!("too many errors, stopped after {max}");
+        diagnostics.push(Diagnostic::new(message, Span::empty(end)).with_code("too-many-errors"));
+    }
+    Err(diagnostics)
+}
+
+/// The parameters, `...` flag, annotations and body shared by function
+/// expressions and statements.
+type FuncParts = (Vec<Spanned<String>>, bool, FuncTypes, Block);
+
+/// Recursive descent parser over a pre-lexed token buffer.
+pub struct Parser<'source> {
+    source: &'source str,
+    tokens: Vec<(LuaToken<'source>, Span)>,
+    /// The kind of each token, so dispatch needn't look at payloads.
+    kinds: Vec<TokenKind>,
+    pos: usize,
+    eof: Span,
+    /// Whether the function being parsed declares `...`, the main chunk always does.
+    vararg: bool,
+    table: Rc<ParserTable>,
+    dialect: Dialect,
+    /// Errors recovered from at statement boundaries.
+    errors: Vec<ParseError>,
+    /// How many errors to record before giving up, see [`Parser::with_max_errors`].
+    max_errors: Option<usize>,
+    /// Whether parsing gave up at `max_errors`, skipping the rest of the input.
+    stopped: bool,
+    /// How many statements and expressions enclose the current one, see [`MAX_DEPTH`].
+    depth: usize,
+    /// Whether expression dispatch peeks whole tokens, see [`Parser::with_payload_peeks`].
+    payload_peeks: bool,
+}
+
+/// A saved parser position to back out to, see [`Parser::checkpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pos: usize,
+    vararg: bool,
+    errors: usize,
+}
+
+/// Deepest nesting of statements and expressions parsed, as in reference Lua,
+/// so hostile input like a long run of `{` can't overflow the stack.
+pub const MAX_DEPTH: usize = 200;
+
+impl<'source> Parser<'source> {
+    pub fn new(source: &'source str) -> Result<Self, LexError> {
+        Self::with_table(source, LUA_TABLE.with(Rc::clone))
+    }
+
+    /// Parse expressions with a custom dispatch table instead of the Lua grammar.
+    pub fn with_table(source: &'source str, table: Rc<ParserTable>) -> Result<Self, LexError> {
+        let mut parser = Self::from_tokens(tokenize(source)?, source);
+        parser.table = table;
+        Ok(parser)
+    }
+
+    /// Parse already lexed tokens of `source`.
+    pub fn from_tokens(tokens: Vec<(LuaToken<'source>, Span)>, source: &'source str) -> Self {
+        Parser {
+            source,
+            kinds: tokens.iter().map(|(token, _)| token.kind()).collect(),
+            tokens,
+            pos: 0,
+            eof: Span::empty(source.len()),
+            vararg: true,
+            table: LUA_TABLE.with(Rc::clone),
+            dialect: Dialect::default(),
+            errors: Vec::new(),
+            max_errors: None,
+            stopped: false,
+            depth: 0,
+            payload_peeks: false,
+        }
+    }
+
+    /// Accept the syntax of `dialect`, standard Lua 5.4 by default.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Stop recording errors after `max`, skipping the rest of the input once
+    /// that many are found. `None`, the default, sets no limit.
+    pub fn with_max_errors(mut self, max: Option<usize>) -> Self {
+        self.max_errors = max;
+        self
+    }
+
+    /// Dispatch expressions on `peek().map(|token| token.kind())` instead of
+    /// [`Parser::peek_kind`], only so benchmarks can measure the difference.
+    #[doc(hidden)]
+    pub fn with_payload_peeks(mut self, payload_peeks: bool) -> Self {
+        self.payload_peeks = payload_peeks;
+        self
+    }
+
+    pub fn peek(&self) -> Option<&LuaToken<'source>> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    /// Kind of the next token, cheaper than [`Parser::peek`] when the payload doesn't matter.
+    pub fn peek_kind(&self) -> Option<TokenKind> {
+        self.kinds.get(self.pos).copied()
+    }
+
+    /// Kind of the next token, for looking it up in the [`ParserTable`].
+    fn dispatch_kind(&self) -> Option<TokenKind> {
+        if self.payload_peeks { self.peek().map(|token| token.kind()) } else { self.peek_kind() }
+    }
+
+    /// Span of the next token, or the zero-width end of input span.
+    pub fn peek_span(&self) -> Span {
+        self.tokens.get(self.pos).map_or(self.eof, |(_, span)| *span)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&LuaToken<'source>> {
+        self.tokens.get(self.pos + offset).map(|(token, _)| token)
+    }
+
+    /// Span of the last consumed token.
+    fn prev_span(&self) -> Span {
+        self.pos.checked_sub(1).map_or(Span::empty(0), |pos| self.tokens[pos].1)
+    }
+
+    fn eat(&mut self, expected: LuaToken) -> bool {
+        if self.peek() == Some(&expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn advance(&mut self) -> Option<(LuaToken<'source>, Span)> {
+        let token = self.tokens.get(self.pos).cloned();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// Save the current position, so a speculative parse can [`Parser::rewind`]
+    /// to it. Tokens are already buffered, so nothing is lexed again.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint { pos: self.pos, vararg: self.vararg, errors: self.errors.len() }
+    }
+
+    /// Back out to `checkpoint`, dropping any errors recorded since.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.pos = checkpoint.pos;
+        self.vararg = checkpoint.vararg;
+        self.errors.truncate(checkpoint.errors);
+    }
+
+    pub fn expect(&mut self, expected: LuaToken, what: &str) -> Result<Span, ParseError> {
+        match self.advance() {
+            Some((token, span)) if token == expected => Ok(span),
+            Some((_, span)) => Err(ParseError::new(format!("expected {what}"), span)),
+            None => Err(ParseError::new(format!("expected {what}"), self.eof)),
+        }
+    }
+
+    //==-----------
+    // Statements
+    //==-----------
+    /// Parse a whole chunk, which must consume all input, failing with the first error.
+    pub fn chunk(&mut self) -> Result<Block, ParseError> {
+        let block = self.program();
+        match self.errors.first() {
+            Some(err) => Err(err.clone()),
+            None => Ok(block),
+        }
+    }
+
+    /// Parse statements until the end of input, recording errors as it goes.
+    fn program(&mut self) -> Block {
+        let mut block = self.block();
+        while let Some((token, span)) = self.advance() {
+            self.record(ParseError::new(format!("unexpected {}", describe(&token)), span));
+            let rest = self.block();
+            block.span = block.span.merge(rest.span);
+            block.stmts.extend(rest.stmts);
+        }
+        block
+    }
+
+    /// Parse statements up to the end of the enclosing construct, recovering from
+    /// a bad statement by skipping to the start of the next one.
+    fn block(&mut self) -> Block {
+        let start = self.peek_span();
+        let mut stmts = Vec::new();
+        loop {
+            while self.eat(LuaToken::Semicolon) {}
+            if self.block_follow() {
+                break;
+            }
+            let stmt_pos = self.pos;
+            match self.statement() {
+                Ok(stmt) => {
+                    let is_return = matches!(stmt.node, Stmt::Return(_));
+                    stmts.push(stmt);
+                    if is_return && self.block_follow() {
+                        break;
+                    }
+                    if is_return {
+                        // Carry on with the block, so its closing keyword still matches.
+                        let message = "'return' must be the last statement in its block";
+                        self.record(ParseError::new(message, self.peek_span()));
+                    }
+                }
+                Err(err) => {
+                    self.record(err);
+                    if self.pos == stmt_pos {
+                        self.advance();
+                    }
+                    self.synchronize();
+                }
+            }
+        }
+        let span = match stmts.last() {
+            Some(last) => start.merge(last.span),
+            None => Span::empty(start.start),
+        };
+        Block { stmts, span }
+    }
+
+    /// Keep `err` to report, unless [`Parser::with_max_errors`] has been reached.
+    /// Reaching it skips to the end of input, so the parse winds down quickly.
+    fn record(&mut self, err: ParseError) {
+        if self.max_errors.is_some_and(|max| self.errors.len() >= max) {
+            self.stopped = true;
+            return;
+        }
+        self.errors.push(err);
+        if self.max_errors == Some(self.errors.len()) && self.pos < self.tokens.len() {
+            self.stopped = true;
+            self.pos = self.tokens.len();
+        }
+    }
+
+    /// Skip up to the next token that can begin a statement or end the block.
+    fn synchronize(&mut self) {
+        while let Some(token) = self.peek() {
+            if matches!(
+                token,
+                LuaToken::Local
+                    | LuaToken::Function
+                    | LuaToken::If
+                    | LuaToken::While
+                    | LuaToken::For
+                    | LuaToken::Do
+                    | LuaToken::Repeat
+                    | LuaToken::Return
+                    | LuaToken::Break
+                    | LuaToken::Goto
+                    | LuaToken::DoubleColon
+                    | LuaToken::Semicolon
+            ) || self.block_follow()
+            {
+                break;
+            }
+            self.advance();
+        }
+    }
+
+    /// Whether the next token closes the current block.
+    fn block_follow(&self) -> bool {
+        matches!(
+            self.peek(),
+            None | Some(LuaToken::Else | LuaToken::Elseif | LuaToken::End | LuaToken::Until)
+        )
+    }
+
+    /// Run `parse` one nesting level deeper, failing past [`MAX_DEPTH`].
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, ParseError>) -> Result<T, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ParseError::new("too many nested levels", self.peek_span()));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn statement(&mut self) -> Result<Spanned<Stmt>, ParseError> {
+        self.nested(Self::unnested_statement)
+    }
+
+    fn unnested_statement(&mut self) -> Result<Spanned<Stmt>, ParseError> {
+        let start = self.peek_span();
+        let node = match self.peek() {
+            Some(LuaToken::Local) => self.local(start)?,
+            Some(LuaToken::Do) => {
+                self.advance();
+                let body = self.block();
+                self.expect_closing(LuaToken::End, "end", "do", start)?;
+                Stmt::Do(body)
+            }
+            Some(LuaToken::While) => {
+                self.advance();
+                let cond = self.expr()?;
+                self.expect(LuaToken::Do, "'do'")?;
+                let body = self.block();
+                self.expect_closing(LuaToken::End, "end", "while", start)?;
+                Stmt::While { cond, body }
+            }
+            Some(LuaToken::Repeat) => {
+                self.advance();
+                let body = self.block();
+                self.expect_closing(LuaToken::Until, "until", "repeat", start)?;
+                Stmt::Repeat { body, cond: self.expr()? }
+            }
+            Some(LuaToken::If) => self.if_stmt(start)?,
+            Some(LuaToken::For) => self.for_stmt(start)?,
+            Some(LuaToken::Break) => {
+                self.advance();
+                Stmt::Break
+            }
+            Some(LuaToken::Goto) => {
+                self.advance();
+                Stmt::Goto(self.name()?)
+            }
+            Some(LuaToken::DoubleColon) => {
+                self.advance();
+                let name = self.name()?;
+                self.expect(LuaToken::DoubleColon, "'::'")?;
+                Stmt::Label(name)
+            }
+            Some(LuaToken::Return) => self.return_stmt()?,
+            Some(LuaToken::Function) => {
+                self.advance();
+                if self.peek() == Some(&LuaToken::LParen) {
+                    // An anonymous function is only an expression. Skip its body
+                    // so the error doesn't cascade into its `end`.
+                    let err = ParseError::new("expected name after 'function'", self.peek_span());
+                    self.params_and_body(start)?;
+                    return Err(err);
+                }
+                let (name, self_span) = self.func_name()?;
+                let (mut params, is_vararg, mut types, body) = self.params_and_body(start)?;
+                if let Some(span) = self_span {
+                    params.insert(0, Spanned::new("self".to_string(), span));
+                    types.params.insert(0, None);
+                }
+                Stmt::Function { name, params, is_vararg, types, body }
+            }
+            Some(LuaToken::Identifier(_) | LuaToken::LParen) => self.expr_stmt()?,
+            Some(LuaToken::In) => return Err(misplaced_in(start)),
+            _ => return Err(ParseError::new("expected statement", start)),
+        };
+        Ok(Spanned::new(node, start.merge(self.prev_span())))
+    }
+
+    /// Expect the `close` keyword ending a construct opened at `open_span`.
+    fn expect_closing(&mut self, close: LuaToken, close_name: &str, open_name: &str, open_span: Span) -> Result<Span, ParseError> {
+        self.expect(close, &format!("'{close_name}' to close '{open_name}' at byte {}", open_span.start))
+    }
+
+    fn name(&mut self) -> Result<Spanned<String>, ParseError> {
+        match self.advance() {
+            Some((LuaToken::Identifier(name), span)) => Ok(Spanned::new(name.to_string(), span)),
+            Some((_, span)) => Err(ParseError::new("expected name", span)),
+            None => Err(ParseError::new("expected name", self.eof)),
+        }
+    }
+
+    /// Parse `a.b.c:d`, returning the span of the method name if there is one.
+    fn func_name(&mut self) -> Result<(FuncName, Option<Span>), ParseError> {
+        let mut path = vec![self.name()?.node];
+        while self.eat(LuaToken::Dot) {
+            path.push(self.name()?.node);
+        }
+        if !self.eat(LuaToken::Colon) {
+            return Ok((FuncName { path, method: None }, None));
+        }
+        let method = self.name()?;
+        Ok((FuncName { path, method: Some(method.node) }, Some(method.span)))
+    }
+
+    fn local(&mut self, start: Span) -> Result<Stmt, ParseError> {
+        self.advance();
+        if self.eat(LuaToken::Function) {
+            let name = self.name()?;
+            let (params, is_vararg, types, body) = self.params_and_body(start)?;
+            return Ok(Stmt::LocalFunction { name, params, is_vararg, types, body });
+        }
+        let (mut names, mut types, mut attribs) = (Vec::new(), Vec::new(), Vec::new());
+        loop {
+            let name = self.name()?;
+            types.push(self.annotation()?);
+            attribs.push(self.attrib(&name)?);
+            names.push(name);
+            if !self.eat(LuaToken::Comma) {
+                break;
+            }
+        }
+        let values = if self.eat(LuaToken::Equal) { self.expr_list()? } else { Vec::new() };
+        Ok(Stmt::Local { names, types, attribs, values })
+    }
+
+    /// Parse an optional `<const>` or `<close>` after the local `name`, which
+    /// only Lua 5.4 allows.
+    fn attrib(&mut self, name: &Spanned<String>) -> Result<Option<Spanned<Attrib>>, ParseError> {
+        if self.peek() != Some(&LuaToken::Less) {
+            return Ok(None);
+        }
+        let start = self.peek_span();
+        if self.dialect != Dialect::Lua54 {
+            return Err(ParseError::new("attributes need Lua 5.4", start).in_dialect(self.dialect));
+        }
+        self.advance();
+        let attrib = self.name()?;
+        let attrib = match attrib.node.as_str() {
+            "const" => Attrib::Const,
+            "close" => Attrib::Close,
+            other => return Err(ParseError::new(format!("unknown attribute '{other}'"), attrib.span)),
+        };
+        let end = self.expect(LuaToken::Greater, "'>'")?;
+        if self.peek() == Some(&LuaToken::Less) {
+            return Err(ParseError::new(format!("multiple attributes on '{}'", name.node), self.peek_span()));
+        }
+        Ok(Some(Spanned::new(attrib, start.merge(end))))
+    }
+
+    fn if_stmt(&mut self, start: Span) -> Result<Stmt, ParseError> {
+        self.advance();
+        let mut arms = Vec::new();
+        let mut else_block = None;
+        loop {
+            let cond = self.expr()?;
+            self.expect(LuaToken::Then, "'then'")?;
+            arms.push((cond, self.block()));
+            if self.eat(LuaToken::Elseif) {
+                continue;
+            }
+            if self.eat(LuaToken::Else) {
+                else_block = Some(self.block());
+            }
+            break;
+        }
+        self.expect_closing(LuaToken::End, "end", "if", start)?;
+        Ok(Stmt::If { arms, else_block })
+    }
+
+    fn for_stmt(&mut self, start: Span) -> Result<Stmt, ParseError> {
+        self.advance();
+        let first = self.name()?;
+        if self.eat(LuaToken::Equal) {
+            let from = self.expr()?;
+            self.expect(LuaToken::Comma, "','")?;
+            let end = self.expr()?;
+            let step = if self.eat(LuaToken::Comma) { Some(self.expr()?) } else { None };
+            self.expect(LuaToken::Do, "'do'")?;
+            let body = self.block();
+            self.expect_closing(LuaToken::End, "end", "for", start)?;
+            return Ok(Stmt::NumericFor { var: first, start: from, end, step, body });
+        }
+
+        let mut names = vec![first];
+        while self.eat(LuaToken::Comma) {
+            names.push(self.name()?);
+        }
+        self.expect(LuaToken::In, "'=' or 'in'")?;
+        let exprs = self.expr_list()?;
+        self.expect(LuaToken::Do, "'do'")?;
+        let body = self.block();
+        self.expect_closing(LuaToken::End, "end", "for", start)?;
+        Ok(Stmt::GenericFor { names, exprs, body })
+    }
+
+    /// Parse an assignment or a call statement, which both start with a suffixed expression.
+    fn expr_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let first = self.suffixed_expr()?;
+        if let Some(&LuaToken::CompoundAssign(op)) = self.peek() {
+            check_assignable(&first)?;
+            self.advance();
+            return Ok(Stmt::CompoundAssign { target: first, op, value: self.expr()? });
+        }
+        if !matches!(self.peek(), Some(LuaToken::Equal | LuaToken::Comma)) {
+            return match first.node {
+                Expr::Call { .. } | Expr::MethodCall { .. } => Ok(Stmt::Call(first)),
+                _ if self.peek() == Some(&LuaToken::In) => Err(misplaced_in(self.peek_span())),
+                _ => Err(ParseError::new("expected '='", self.peek_span())),
+            };
+        }
+        let mut targets = vec![first];
+        while self.eat(LuaToken::Comma) {
+            targets.push(self.suffixed_expr()?);
+        }
+        for target in &targets {
+            check_assignable(target)?;
+        }
+        self.expect(LuaToken::Equal, "'='")?;
+        Ok(Stmt::Assign { targets, values: self.expr_list()? })
+    }
+
+    fn return_stmt(&mut self) -> Result<Stmt, ParseError> {
+        self.advance();
+        let values = if self.block_follow() || self.peek() == Some(&LuaToken::Semicolon) {
+            Vec::new()
+        } else {
+            self.expr_list()?
+        };
+        self.eat(LuaToken::Semicolon);
+        Ok(Stmt::Return(values))
+    }
+
+    //==------------
+    // Expressions
+    //==------------
+    fn expr_list(&mut self) -> Result<Vec<Spanned<Expr>>, ParseError> {
+        let mut exprs = vec![self.expr()?];
+        while self.eat(LuaToken::Comma) {
+            exprs.push(self.expr()?);
+        }
+        Ok(exprs)
+    }
+
+    pub fn expr(&mut self) -> Result<Spanned<Expr>, ParseError> {
+        self.subexpr(0)
+    }
+
+    /// Parse an expression and its call and index suffixes, but no operators.
+    fn suffixed_expr(&mut self) -> Result<Spanned<Expr>, ParseError> {
+        self.subexpr(SUFFIX_PRECEDENCE - 1)
+    }
+
+    /// Parse call arguments after their opening `token`: `(list)`, a string or a table.
+    fn call_args(&mut self, token: LuaToken<'source>, span: Span) -> Result<Vec<Spanned<Expr>>, ParseError> {
+        match token {
+            LuaToken::LParen if self.eat(LuaToken::RParen) => Ok(Vec::new()),
+            LuaToken::LParen => {
+                let mut args = vec![self.expr()?];
+                while self.eat(LuaToken::Comma) {
+                    // Unlike a table constructor, an argument list can't end in a separator.
+                    if self.peek() == Some(&LuaToken::RParen) {
+                        return Err(ParseError::new("trailing ',' in arguments", self.prev_span()));
+                    }
+                    args.push(self.expr()?);
+                }
+                self.expect(LuaToken::RParen, "')'")?;
+                Ok(args)
+            }
+            LuaToken::String(_) | LuaToken::LongString(_) | LuaToken::LBrace => Ok(vec![primary(self, token, span)?]),
+            _ => Err(ParseError::new("expected function arguments", span)),
+        }
+    }
+
+    /// Parse an expression whose infix operators all bind tighter than `limit`,
+    /// dispatching through the parser's [`ParserTable`].
+    pub fn subexpr(&mut self, limit: u8) -> Result<Spanned<Expr>, ParseError> {
+        self.nested(|parser| parser.unnested_subexpr(limit))
+    }
+
+    fn unnested_subexpr(&mut self, limit: u8) -> Result<Spanned<Expr>, ParseError> {
+        let prefix = self.dispatch_kind().and_then(|kind| self.table.prefix.get(&kind).cloned());
+        let Some(prefix) = prefix else {
+            return Err(ParseError::new("expected expression", self.peek_span()));
+        };
+        let (token, span) = self.advance().unwrap();
+        let mut lhs = prefix(self, token, span)?;
+
+        while let Some(rule) = self.dispatch_kind().and_then(|kind| self.table.infix.get(&kind).cloned()) {
+            if rule.precedence <= limit {
+                break;
+            }
+            let (token, span) = self.advance().unwrap();
+            lhs = (rule.handler)(self, lhs, token, span)?;
+        }
+        Ok(lhs)
+    }
+
+    /// Parse a function expression after the `function` keyword at `start`.
+    fn function_body(&mut self, start: Span) -> Result<Spanned<Expr>, ParseError> {
+        let (params, is_vararg, types, body) = self.params_and_body(start)?;
+        Ok(Spanned::new(Expr::Function { params, is_vararg, types, body }, start.merge(self.prev_span())))
+    }
+
+    /// Parse `(params) block end`, shared by function expressions and statements.
+    fn params_and_body(&mut self, start: Span) -> Result<FuncParts, ParseError> {
+        self.expect(LuaToken::LParen, "'('")?;
+        let mut params = Vec::new();
+        let mut types = FuncTypes::default();
+        let mut is_vararg = false;
+        if self.peek() != Some(&LuaToken::RParen) {
+            loop {
+                match self.advance() {
+                    Some((LuaToken::Identifier(name), span)) => {
+                        params.push(Spanned::new(name.to_string(), span));
+                        types.params.push(self.annotation()?);
+                    }
+                    Some((LuaToken::Ellipsis, _)) => {
+                        is_vararg = true;
+                        types.vararg = self.annotation()?;
+                        break;
+                    }
+                    Some((_, span)) => return Err(ParseError::new("expected parameter name", span)),
+                    None => return Err(ParseError::new("expected parameter name", self.eof)),
+                }
+                if !self.eat(LuaToken::Comma) {
+                    break;
+                }
+            }
+        }
+        self.expect(LuaToken::RParen, "')'")?;
+        types.ret = self.annotation()?;
+
+        let outer = std::mem::replace(&mut self.vararg, is_vararg);
+        let body = self.block();
+        self.vararg = outer;
+
+        self.expect_closing(LuaToken::End, "end", "function", start)?;
+        Ok((params, is_vararg, types, body))
+    }
+
+    //==-----------------
+    // Luau annotations
+    //==-----------------
+    /// Parse an optional `: type` annotation, which only Luau allows.
+    fn annotation(&mut self) -> Result<Option<Spanned<TypeExpr>>, ParseError> {
+        if self.peek() != Some(&LuaToken::Colon) {
+            return Ok(None);
+        }
+        if self.dialect != Dialect::Luau {
+            let err = ParseError::new("type annotations need the Luau dialect", self.peek_span());
+            return Err(err.in_dialect(self.dialect));
+        }
+        self.advance();
+        let start = self.pos;
+        self.type_expr()?;
+        let text = self.tokens[start..self.pos].iter().enumerate().fold(String::new(), |mut text, (i, (token, span))| {
+            // Keep the source's spacing between tokens, collapsed to one space.
+            if i > 0 && self.tokens[start + i - 1].1.end < span.start {
+                text.push(' ');
+            }
+            text.push_str(&type_token_text(token));
+            text
+        });
+        let span = self.tokens[start].1.merge(self.prev_span());
+        Ok(Some(Spanned::new(TypeExpr(text), span)))
+    }
+
+    /// Skip over a type: simple types joined by `|`, `&` or `->`.
+    fn type_expr(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.simple_type()?;
+            let arrow = self.peek() == Some(&LuaToken::Minus) && self.peek_at(1) == Some(&LuaToken::Greater);
+            if arrow {
+                self.pos += 2;
+            } else if !self.eat(LuaToken::Pipe) && !self.eat(LuaToken::Ampersand) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn simple_type(&mut self) -> Result<(), ParseError> {
+        match self.advance() {
+            Some((LuaToken::Identifier(name), _)) => {
+                while self.eat(LuaToken::Dot) {
+                    self.name()?;
+                }
+                if self.peek() == Some(&LuaToken::Less) {
+                    self.skip_type_args()?;
+                } else if name == "typeof" && self.peek() == Some(&LuaToken::LParen) {
+                    self.skip_balanced()?;
+                }
+            }
+            Some((LuaToken::Nil | LuaToken::True | LuaToken::False | LuaToken::String(_), _)) => {}
+            Some((LuaToken::Ellipsis, _)) => self.simple_type()?,
+            Some((LuaToken::LParen | LuaToken::LBrace, _)) => {
+                self.pos -= 1;
+                self.skip_balanced()?;
+            }
+            Some((_, span)) => return Err(ParseError::new("expected type", span)),
+            None => return Err(ParseError::new("expected type", self.eof)),
+        }
+        Ok(())
+    }
+
+    /// Skip a bracketed group starting at the next token, with any brackets nested in it.
+    fn skip_balanced(&mut self) -> Result<(), ParseError> {
+        let (_, open_span) = self.advance().unwrap();
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                Some((LuaToken::LParen | LuaToken::LBrace | LuaToken::LBracket, _)) => depth += 1,
+                Some((LuaToken::RParen | LuaToken::RBrace | LuaToken::RBracket, _)) => depth -= 1,
+                Some(_) => {}
+                None => return Err(ParseError::new("unclosed bracket in type", open_span)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Skip generic arguments `<...>`, where a `>>` closes two levels.
+    fn skip_type_args(&mut self) -> Result<(), ParseError> {
+        let (_, open_span) = self.advance().unwrap();
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                Some((LuaToken::Less, _)) => depth += 1,
+                Some((LuaToken::Greater, _)) => depth -= 1,
+                Some((LuaToken::ShiftRight, _)) if depth >= 2 => depth -= 2,
+                Some(_) => {}
+                None => return Err(ParseError::new("unclosed '<' in type", open_span)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse a table constructor after the `{` at `start`.
+    fn table(&mut self, start: Span) -> Result<Spanned<Expr>, ParseError> {
+        let mut fields = Vec::new();
+        while self.peek() != Some(&LuaToken::RBrace) {
+            fields.push(self.table_field()?);
+            if !self.eat(LuaToken::Comma) && !self.eat(LuaToken::Semicolon) {
+                break;
+            }
+        }
+        let end = self.expect(LuaToken::RBrace, "'}'")?;
+        Ok(Spanned::new(Expr::Table(fields), start.merge(end)))
+    }
+
+    fn table_field(&mut self) -> Result<TableField, ParseError> {
+        match (self.peek(), self.peek_at(1)) {
+            (Some(LuaToken::LBracket), _) => {
+                self.advance();
+                let key = self.expr()?;
+                self.expect(LuaToken::RBracket, "']'")?;
+                self.expect(LuaToken::Equal, "'='")?;
+                Ok(TableField::Keyed(key, self.expr()?))
+            }
+            (Some(LuaToken::Identifier(name)), Some(LuaToken::Equal)) => {
+                let name = Spanned::new(name.to_string(), self.peek_span());
+                self.pos += 2;
+                Ok(TableField::Named(name, self.expr()?))
+            }
+            _ => Ok(TableField::Positional(self.expr()?)),
+        }
+    }
+
+    /// Split the `raw` body of a Luau backtick string at `span` into its text
+    /// and the expressions of its `{expr}` holes.
+    fn interpolated_string(&mut self, raw: &'source str, span: Span) -> Result<Spanned<Expr>, ParseError> {
+        let body = span.start + 1;
+        let extras = LexExtras { dialect: self.dialect, ..LexExtras::default() };
+        let bytes = raw.as_bytes();
+        let mut parts = Vec::new();
+        let mut text = Vec::new();
+        let (mut chunk, mut i) = (0, 0);
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' if matches!(bytes.get(i + 1), Some(b'{' | b'}' | b'`')) => {
+                    decode_text(&raw[chunk..i], body + chunk, &mut text)?;
+                    text.push(bytes[i + 1]);
+                    i += 2;
+                    chunk = i;
+                }
+                b'\\' => i += 2,
+                b'{' => {
+                    decode_text(&raw[chunk..i], body + chunk, &mut text)?;
+                    if !text.is_empty() {
+                        parts.push(StringPart::Literal(std::mem::take(&mut text)));
+                    }
+                    let open = i + 1;
+                    let close = match interpolation_hole_end(&raw[open..], &extras) {
+                        Ok(Some(end)) => open + end,
+                        _ => return Err(ParseError::new("expected '}'", Span::empty(span.end - 1))),
+                    };
+                    parts.push(StringPart::Expr(self.interpolation_hole(&raw[open..close], body + open, body + close)?));
+                    i = close + 1;
+                    chunk = i;
+                }
+                _ => i += 1,
+            }
+        }
+        decode_text(&raw[chunk..], body + chunk, &mut text)?;
+        if !text.is_empty() {
+            parts.push(StringPart::Literal(text));
+        }
+        Ok(Spanned::new(Expr::InterpolatedString { parts }, span))
+    }
+
+    /// Parse the expression `hole`, which starts at `start` and is closed by the
+    /// `}` at `close`, with a parser of its own sharing this one's state.
+    fn interpolation_hole(&mut self, hole: &'source str, start: usize, close: usize) -> Result<Spanned<Expr>, ParseError> {
+        let tokens = LexerBuilder::new().dialect(self.dialect).tokenize(hole).map_err(|err| err.offset_by(start))?;
+        let tokens = tokens.into_iter().map(|(token, span)| (token, shift(span, start))).collect();
+        let mut parser = Parser::from_tokens(tokens, self.source).with_dialect(self.dialect);
+        parser.eof = Span::new(close, close + 1);
+        parser.vararg = self.vararg;
+        parser.table = Rc::clone(&self.table);
+        parser.depth = self.depth;
+        let value = parser.expr();
+        std::mem::take(&mut parser.errors).into_iter().for_each(|err| self.record(err));
+        let value = value?;
+        if parser.peek().is_some() {
+            return Err(ParseError::new("expected '}'", parser.peek_span()));
+        }
+        Ok(value)
+    }
+}
+
+/// Decode the escapes of `raw`, text of an interpolated string starting at
+/// `start`, onto the end of `out`.
+fn decode_text(raw: &str, start: usize, out: &mut Vec<u8>) -> Result<(), ParseError> {
+    match decode_escapes(raw) {
+        Ok(value) => {
+            out.extend_from_slice(&value);
+            Ok(())
+        }
+        Err(err) => Err(ParseError::new(err.to_string(), shift(err.span(), start))),
+    }
+}
+
+/// `span` moved `offset` bytes later.
+fn shift(span: Span, offset: usize) -> Span {
+    Span::new(span.start + offset, span.end + offset)
+}
+
+/// Reject assignment targets that aren't a name, field or index.
+fn check_assignable(target: &Spanned<Expr>) -> Result<(), ParseError> {
+    match target.node {
+        Expr::Name(_) | Expr::Field { .. } | Expr::Index { .. } => Ok(()),
+        Expr::Call { .. } | Expr::MethodCall { .. } => Err(ParseError::new("cannot assign to a function call", target.span)),
+        Expr::Nil | Expr::Bool(_) | Expr::Integer(..) | Expr::Float(..) | Expr::String(_) | Expr::InterpolatedString { .. } => {
+            Err(ParseError::new("cannot assign to a literal", target.span))
+        }
+        _ => Err(ParseError::new("cannot assign to this expression", target.span)),
+    }
+}
+
+/// The error for an `in` at `span` that doesn't follow a generic `for` name list.
+fn misplaced_in(span: Span) -> ParseError {
+    ParseError::new("'in' is only valid after the names of a generic 'for'", span)
+}
+
+/// How a token reads in the text of a [`TypeExpr`].
+fn type_token_text(token: &LuaToken) -> String {
+    match token {
+        LuaToken::Identifier(name) => name.to_string(),
+        LuaToken::String(body) => format!("\"{body}\""),
+        _ => token.kind().text().unwrap_or_default().to_string(),
+    }
+}
+
+/// How a token is named in error messages.
+fn describe(token: &LuaToken) -> String {
+    match token {
+        LuaToken::Identifier(name) => format!("name '{name}'"),
+        LuaToken::String(_) | LuaToken::LongString(_) | LuaToken::InterpolatedString(_) => "string".to_string(),
+        LuaToken::Integer(_) | LuaToken::Float(_) => "number".to_string(),
+        LuaToken::Comment(_) => "comment".to_string(),
+        LuaToken::Annotation(name) => format!("annotation '@{name}'"),
+        LuaToken::CompoundAssign(op) => format!("'{}='", op.as_str()),
+        _ => format!("'{}'", token.kind().text().unwrap_or_default()),
+    }
+}
+
+//==-------------
+// Pratt table
+//==-------------
+/// Parses an expression starting with the already consumed token at `span`.
+pub type PrefixHandler =
+    Rc<dyn for<'s> Fn(&mut Parser<'s>, LuaToken<'s>, Span) -> Result<Spanned<Expr>, ParseError>>;
+/// Extends `lhs` with the already consumed operator token at `span`.
+pub type InfixHandler =
+    Rc<dyn for<'s> Fn(&mut Parser<'s>, Spanned<Expr>, LuaToken<'s>, Span) -> Result<Spanned<Expr>, ParseError>>;
+
+#[derive(Clone)]
+pub struct InfixRule {
+    /// Operators at or below the current limit end the operand instead.
+    pub precedence: u8,
+    pub handler: InfixHandler,
+}
+
+/// Expression dispatch for the Pratt parser, keyed by token kind.
+///
+/// `ParserTable::default()` holds the Lua grammar, entries can be replaced or
+/// added to parse Lua-like DSLs.
+#[derive(Clone)]
+pub struct ParserTable {
+    prefix: HashMap<TokenKind, PrefixHandler>,
+    infix: HashMap<TokenKind, InfixRule>,
+}
+
+impl ParserTable {
+    /// A table with no entries, where nothing parses as an expression.
+    pub fn empty() -> Self {
+        ParserTable { prefix: HashMap::new(), infix: HashMap::new() }
+    }
+
+    pub fn set_prefix<F>(&mut self, kind: TokenKind, handler: F) -> &mut Self
+    where
+        F: for<'s> Fn(&mut Parser<'s>, LuaToken<'s>, Span) -> Result<Spanned<Expr>, ParseError> + 'static,
+    {
+        self.prefix.insert(kind, Rc::new(handler));
+        self
+    }
+
+    pub fn set_infix<F>(&mut self, kind: TokenKind, precedence: u8, handler: F) -> &mut Self
+    where
+        F: for<'s> Fn(&mut Parser<'s>, Spanned<Expr>, LuaToken<'s>, Span) -> Result<Spanned<Expr>, ParseError>
+            + 'static,
+    {
+        self.infix.insert(kind, InfixRule { precedence, handler: Rc::new(handler) });
+        self
+    }
+
+    pub fn remove_prefix(&mut self, kind: TokenKind) -> Option<PrefixHandler> {
+        self.prefix.remove(&kind)
+    }
+
+    pub fn remove_infix(&mut self, kind: TokenKind) -> Option<InfixRule> {
+        self.infix.remove(&kind)
+    }
+
+    pub fn prefix(&self, kind: TokenKind) -> Option<&PrefixHandler> {
+        self.prefix.get(&kind)
+    }
+
+    pub fn infix(&self, kind: TokenKind) -> Option<&InfixRule> {
+        self.infix.get(&kind)
+    }
+}
+
+impl Default for ParserTable {
+    fn default() -> Self {
+        use TokenKind::*;
+
+        let mut table = ParserTable::empty();
+        for kind in [
+            Nil, True, False, Integer, Float, String, LongString, InterpolatedString, Identifier, Ellipsis, Function, LBrace, LParen,
+        ] {
+            table.set_prefix(kind, primary);
+        }
+        for kind in [Minus, Not, Length, Tilde] {
+            table.set_prefix(kind, unary);
+        }
+        for kind in [Dot, LBracket, Colon, LParen, String, LongString, LBrace] {
+            table.set_infix(kind, SUFFIX_PRECEDENCE, suffix);
+        }
+        for kind in [Or, And] {
+            table.set_infix(kind, BinOp::from_kind(kind).unwrap().precedence(), logical);
+        }
+        for kind in [
+            Less, Greater, LessEqual, GreaterEqual, NotEqual, DoubleEqual, Pipe, Tilde, Ampersand,
+            ShiftLeft, ShiftRight, Concatenate, Plus, Minus, Multiply, Divide, FloorDivide, Modulus, Exponent,
+        ] {
+            let op = BinOp::from_kind(kind).unwrap();
+            table.set_infix(kind, op.precedence(), binary);
+        }
+        table
+    }
+}
+
+thread_local! {
+    static LUA_TABLE: Rc<ParserTable> = Rc::new(ParserTable::default());
+}
+
+fn primary<'s>(parser: &mut Parser<'s>, token: LuaToken<'s>, span: Span) -> Result<Spanned<Expr>, ParseError> {
+    let node = match token {
+        LuaToken::Nil => Expr::Nil,
+        LuaToken::True => Expr::Bool(true),
+        LuaToken::False => Expr::Bool(false),
+        LuaToken::Integer(value) => Expr::Integer(value, Some(parser.source[span.range()].to_string())),
+        LuaToken::Float(value) => Expr::Float(value, Some(parser.source[span.range()].to_string())),
+        LuaToken::String(raw) => match decode_escapes(raw) {
+            Ok(value) => Expr::String(value),
+            Err(err) => {
+                let body = err.span();
+                let start = span.start + 1;
+                return Err(ParseError::new(err.to_string(), Span::new(start + body.start, start + body.end)));
+            }
+        },
+        LuaToken::LongString(value) => Expr::String(normalize_newlines(value).into_owned().into_bytes()),
+        LuaToken::InterpolatedString(raw) => return parser.interpolated_string(raw, span),
+        LuaToken::Identifier(name) => Expr::Name(name.to_string()),
+        LuaToken::Ellipsis => {
+            if !parser.vararg {
+                return Err(ParseError::new("cannot use '...' outside a vararg function", span));
+            }
+            Expr::Vararg
+        }
+        LuaToken::Function => return parser.function_body(span),
+        LuaToken::LBrace => return parser.table(span),
+        LuaToken::LParen => {
+            let inner = parser.expr()?;
+            let end = parser.expect(LuaToken::RParen, "')'")?;
+            return Ok(Spanned::new(Expr::Paren(Box::new(inner)), span.merge(end)));
+        }
+        _ => return Err(ParseError::new("expected expression", span)),
+    };
+    Ok(Spanned::new(node, span))
+}
+
+fn unary<'s>(parser: &mut Parser<'s>, token: LuaToken<'s>, span: Span) -> Result<Spanned<Expr>, ParseError> {
+    let Some(op) = UnOp::from_token(&token) else {
+        return Err(ParseError::new("expected expression", span));
+    };
+    let operand = parser.subexpr(UNARY_PRECEDENCE)?;
+    let span = span.merge(operand.span);
+    Ok(Spanned::new(Expr::Unary { op, operand: Box::new(operand) }, span))
+}
+
+fn binary<'s>(
+    parser: &mut Parser<'s>,
+    lhs: Spanned<Expr>,
+    token: LuaToken<'s>,
+    span: Span,
+) -> Result<Spanned<Expr>, ParseError> {
+    let Some(op) = BinOp::from_token(&token) else {
+        return Err(ParseError::new("expected binary operator", span));
+    };
+    let prec = op.precedence();
+    let rhs = parser.subexpr(if op.is_right_assoc() { prec - 1 } else { prec })?;
+    let span = lhs.span.merge(rhs.span);
+    Ok(Spanned::new(Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }, span))
+}
+
+/// Field, index and call suffixes, which only apply to prefix expressions.
+fn suffix<'s>(
+    parser: &mut Parser<'s>,
+    lhs: Spanned<Expr>,
+    token: LuaToken<'s>,
+    span: Span,
+) -> Result<Spanned<Expr>, ParseError> {
+    if !lhs.node.is_prefix() {
+        return Err(ParseError::new(format!("unexpected {}", describe(&token)), span));
+    }
+    let start = lhs.span;
+    let node = match token {
+        LuaToken::Dot => Expr::Field { obj: Box::new(lhs), name: parser.name()? },
+        LuaToken::LBracket => {
+            let key = parser.expr()?;
+            parser.expect(LuaToken::RBracket, "']'")?;
+            Expr::Index { obj: Box::new(lhs), key: Box::new(key) }
+        }
+        LuaToken::Colon => {
+            let method = parser.name()?;
+            let args = match parser.advance() {
+                Some((token, span)) => parser.call_args(token, span)?,
+                None => return Err(ParseError::new("expected function arguments", parser.eof)),
+            };
+            Expr::MethodCall { obj: Box::new(lhs), method, args }
+        }
+        token => Expr::Call { func: Box::new(lhs), args: parser.call_args(token, span)? },
+    };
+    Ok(Spanned::new(node, start.merge(parser.prev_span())))
+}
+
+fn logical<'s>(
+    parser: &mut Parser<'s>,
+    lhs: Spanned<Expr>,
+    token: LuaToken<'s>,
+    span: Span,
+) -> Result<Spanned<Expr>, ParseError> {
+    let op = match token {
+        LuaToken::And => BinOp::And,
+        LuaToken::Or => BinOp::Or,
+        _ => return Err(ParseError::new("expected 'and' or 'or'", span)),
+    };
+    let rhs = parser.subexpr(op.precedence())?;
+    let span = lhs.span.merge(rhs.span);
+    Ok(Spanned::new(Expr::Logical { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }, span))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_expr(source: &str) -> Spanned<Expr> {
+        Parser::new(source).unwrap().expr().unwrap()
+    }
+
+    #[test]
+    fn parse_binary_precedence() {
+        let expr = parse_expr("a + b * c");
+        let Expr::Binary { op: BinOp::Add, rhs, .. } = expr.node else { panic!("{expr:?}") };
+        assert!(matches!(rhs.node, Expr::Binary { op: BinOp::Mul, .. }));
+        assert_eq!(expr.span, Span::new(0, 9));
+    }
+    #[test]
+    fn parse_right_assoc() {
+        let expr = parse_expr("a ^ b ^ c");
+        let Expr::Binary { op: BinOp::Pow, lhs, rhs } = expr.node else { panic!("{expr:?}") };
+        assert_eq!(lhs.node, Expr::Name("a".to_string()));
+        assert!(matches!(rhs.node, Expr::Binary { op: BinOp::Pow, .. }));
+    }
+    #[test]
+    fn parse_mixed_precedence() {
+        // Every operator application in brackets, to compare with Lua's grouping.
+        fn grouped(expr: &Spanned<Expr>) -> String {
+            match &expr.node {
+                Expr::Binary { op, lhs, rhs } | Expr::Logical { op, lhs, rhs } => {
+                    format!("({} {} {})", grouped(lhs), op.as_str(), grouped(rhs))
+                }
+                Expr::Unary { op: UnOp::Not, operand } => format!("(not {})", grouped(operand)),
+                Expr::Unary { op, operand } => format!("({}{})", op.as_str(), grouped(operand)),
+                Expr::Name(name) => name.clone(),
+                Expr::Integer(value, _) => value.to_string(),
+                expr => panic!("{expr:?}"),
+            }
+        }
+        let cases = [
+            ("a or b and c == d .. e + f * g ^ h", "(a or (b and (c == (d .. (e + (f * (g ^ h)))))))"),
+            ("h ^ g * f + e .. d == c and b or a", "(((((((h ^ g) * f) + e) .. d) == c) and b) or a)"),
+            ("a < b | c ~ d & e << f .. g", "(a < (b | (c ~ (d & (e << (f .. g))))))"),
+            ("a .. b .. c ^ d ^ e", "(a .. (b .. (c ^ (d ^ e))))"),
+            ("a - b - c // d % e / f", "((a - b) - (((c // d) % e) / f))"),
+            ("a or b or c and d and e", "((a or b) or ((c and d) and e))"),
+            ("a == b ~= c < d <= e > f >= g", "((((((a == b) ~= c) < d) <= e) > f) >= g)"),
+            ("a >> b << c | d | e", "((((a >> b) << c) | d) | e)"),
+            ("-a ^ -b ^ c", "(-(a ^ (-(b ^ c))))"),
+            ("not a == b and #c + ~d", "(((not a) == b) and ((#c) + (~d)))"),
+            ("2 ^ -3 .. 1 + -x * y", "((2 ^ (-3)) .. (1 + ((-x) * y)))"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(grouped(&parse_expr(source)), expected, "{source}");
+        }
+    }
+    #[test]
+    fn parse_logical() {
+        let expr = parse_expr("a or b and c or x == nil");
+        let Expr::Logical { op: BinOp::Or, lhs, rhs } = expr.node else { panic!("{expr:?}") };
+        assert!(matches!(rhs.node, Expr::Binary { op: BinOp::Equal, .. }), "{rhs:?}");
+        let Expr::Logical { op: BinOp::Or, rhs: and, .. } = lhs.node else { panic!("{lhs:?}") };
+        assert!(matches!(and.node, Expr::Logical { op: BinOp::And, .. }), "{and:?}");
+    }
+    #[test]
+    fn parse_unary() {
+        let expr = parse_expr("-x ^ 2");
+        let Expr::Unary { op: UnOp::Neg, operand } = expr.node else { panic!("{expr:?}") };
+        assert!(matches!(operand.node, Expr::Binary { op: BinOp::Pow, .. }));
+    }
+    #[test]
+    fn parse_return_vararg() {
+        let block = Parser::new("return ...").unwrap().chunk().unwrap();
+        let Stmt::Return(values) = &block.stmts[0].node else { panic!("{block:?}") };
+        assert_eq!(values[0], Spanned::new(Expr::Vararg, Span::new(7, 10)));
+    }
+    #[test]
+    fn parse_table_vararg() {
+        let expr = parse_expr("{...}");
+        let Expr::Table(fields) = expr.node else { panic!("{expr:?}") };
+        assert_eq!(fields, vec![TableField::Positional(Spanned::new(Expr::Vararg, Span::new(1, 4)))]);
+    }
+    #[test]
+    fn parse_vararg_function() {
+        let expr = parse_expr("function(a, ...) return ... end");
+        let Expr::Function { params, is_vararg, body, .. } = expr.node else { panic!("{expr:?}") };
+        assert_eq!(params, vec![Spanned::new("a".to_string(), Span::new(9, 10))]);
+        assert!(is_vararg);
+        assert_eq!(body.stmts.len(), 1);
+    }
+    #[test]
+    fn parse_luau_annotations() {
+        let block = parse_dialect("local x: number = 1", Dialect::Luau).unwrap();
+        let Stmt::Local { types, .. } = &block.stmts[0].node else { panic!("{block:?}") };
+        assert_eq!(types, &[Some(Spanned::new(TypeExpr("number".to_string()), Span::new(9, 15)))]);
+
+        let block = parse_dialect("function f(a: string): number return 0 end", Dialect::Luau).unwrap();
+        let Stmt::Function { types, .. } = &block.stmts[0].node else { panic!("{block:?}") };
+        let text = |ty: &Option<Spanned<TypeExpr>>| ty.as_ref().map(|ty| ty.node.0.clone());
+        assert_eq!(types.params.iter().map(text).collect::<Vec<_>>(), [Some("string".to_string())]);
+        assert_eq!(text(&types.ret), Some("number".to_string()));
+
+        let block = parse_dialect("local f: (a: {string}, ...any) -> Map<string, Array<number>> | nil", Dialect::Luau).unwrap();
+        let Stmt::Local { types, .. } = &block.stmts[0].node else { panic!("{block:?}") };
+        assert_eq!(types[0].as_ref().unwrap().node.0, "(a: {string}, ...any) -> Map<string, Array<number>> | nil");
+    }
+    #[test]
+    fn parse_compound_assign() {
+        let block = parse_dialect("x += 1\nt[i] ..= 's'", Dialect::Luau).unwrap();
+        let Stmt::CompoundAssign { target, op, value } = &block.stmts[0].node else { panic!("{block:?}") };
+        assert_eq!((&target.node, *op, &value.node), (&Expr::Name("x".to_string()), BinOp::Add, &Expr::Integer(1, Some("1".to_string()))));
+        assert_eq!(block.stmts[0].span, Span::new(0, 6));
+        assert!(matches!(&block.stmts[1].node, Stmt::CompoundAssign { op: BinOp::Concat, .. }));
+        assert_eq!(crate::print::print_block(&block), "x += 1\nt[i] ..= \"s\"");
+        assert_eq!(parse("x += 1").unwrap_err()[0].message, "expected '='");
+        assert_eq!(parse_dialect("f() += 1", Dialect::Luau).unwrap_err()[0].message, "cannot assign to a function call");
+    }
+    #[test]
+    fn parse_local_attribs() {
+        let attribs = |source: &str| match parse(source).unwrap().stmts.remove(0).node {
+            Stmt::Local { attribs, .. } => attribs.into_iter().map(|attrib| attrib.map(|attrib| attrib.node)).collect::<Vec<_>>(),
+            stmt => panic!("{stmt:?}"),
+        };
+        assert_eq!(attribs("local x <const> = 1"), [Some(Attrib::Const)]);
+        assert_eq!(attribs("local x <const>, y = 1, 2"), [Some(Attrib::Const), None]);
+        assert_eq!(attribs("local x, f <close> = 1, io.open(p)"), [None, Some(Attrib::Close)]);
+        assert_eq!(attribs("local x = a < b"), [None]);
+        let block = parse("local x <const> = 1").unwrap();
+        let Stmt::Local { attribs, .. } = &block.stmts[0].node else { panic!() };
+        assert_eq!(attribs[0].as_ref().unwrap().span, Span::new(8, 15));
+
+        let err = |source: &str| parse(source).unwrap_err().remove(0);
+        assert_eq!(err("local x <const> <close> = 1"), Diagnostic::new("multiple attributes on 'x'", Span::new(16, 17)));
+        assert_eq!(err("local x <static> = 1"), Diagnostic::new("unknown attribute 'static'", Span::new(9, 15)));
+        assert_eq!(err("local x <const = 1").message, "expected '>'");
+        let lua51 = parse_dialect("local x <const> = 1", Dialect::Lua51).unwrap_err().remove(0);
+        assert_eq!(lua51, Diagnostic::new("attributes need Lua 5.4", Span::new(8, 9)).with_code("not-in-dialect"));
+    }
+    #[test]
+    fn parse_luau_annotations_rejected_in_lua() {
+        let message = "type annotations need the Luau dialect";
+        assert_eq!(parse("local x: number = 1").unwrap_err()[0], Diagnostic::new(message, Span::new(7, 8)).with_code("not-in-dialect"));
+        assert_eq!(parse("function f(a: string): number return 0 end").unwrap_err()[0].message, message);
+        let err = Parser::new("local x: number").unwrap().chunk().unwrap_err();
+        assert_eq!(err.dialect, Some(Dialect::Lua54));
+        let block = parse("local function_ok = obj:method()").unwrap();
+        assert!(matches!(&block.stmts[0].node, Stmt::Local { types, .. } if types == &[None]));
+    }
+    #[test]
+    fn parse_checkpoint_rewind() {
+        let mut parser = Parser::new("a = f(1) + 2").unwrap();
+        parser.advance();
+        let checkpoint = parser.checkpoint();
+        let ahead: Vec<_> = std::iter::from_fn(|| parser.advance()).take(4).collect();
+        assert_eq!(ahead.len(), 4);
+        parser.rewind(checkpoint);
+        assert_eq!(parser.peek(), Some(&LuaToken::Equal));
+        let again: Vec<_> = std::iter::from_fn(|| parser.advance()).take(4).collect();
+        assert_eq!(again, ahead);
+        // A failed speculative parse leaves no trace.
+        let mut parser = Parser::new("x ) y").unwrap();
+        let checkpoint = parser.checkpoint();
+        assert!(parser.chunk().is_err());
+        parser.rewind(checkpoint);
+        assert_eq!(parser.checkpoint(), checkpoint);
+        assert_eq!(parser.expr().unwrap().node, Expr::Name("x".to_string()));
+    }
+    #[test]
+    fn parse_large_table() {
+        let source = format!("return {{{}}}", (0..5000).map(|i| format!("{i}, k{i} = {i}")).collect::<Vec<_>>().join(", "));
+        let block = parse(&source).unwrap();
+        let Stmt::Return(values) = &block.stmts[0].node else { panic!() };
+        let Expr::Table(fields) = &values[0].node else { panic!("{values:?}") };
+        assert_eq!(fields.len(), 10_000);
+    }
+    #[test]
+    fn parse_nesting_limit() {
+        // Unoptimised builds use more stack per level than a test thread has.
+        let check = || {
+            let nested = |depth: usize| format!("return {}{}", "{".repeat(depth), "}".repeat(depth));
+            assert!(parse(&nested(MAX_DEPTH - 2)).is_ok());
+            let errors = parse(&nested(100_000)).unwrap_err();
+            assert_eq!(errors[0], Diagnostic::new("too many nested levels", Span::new(7 + MAX_DEPTH - 1, 7 + MAX_DEPTH)));
+            let blocks = format!("{}{}", "do ".repeat(100_000), "end ".repeat(100_000));
+            assert_eq!(parse(&blocks).unwrap_err()[0].message, "too many nested levels");
+        };
+        std::thread::Builder::new().stack_size(16 << 20).spawn(check).unwrap().join().unwrap();
+    }
+    #[test]
+    fn parse_closures() {
+        let expr = parse_expr("function() end");
+        assert!(matches!(&expr.node, Expr::Function { params, is_vararg: false, body, .. } if params.is_empty() && body.stmts.is_empty()));
+        assert_eq!(expr.span, Span::new(0, 14));
+
+        let Expr::Call { args, .. } = parse_expr("map(t, function(x) return x end)").node else { panic!() };
+        let Expr::Function { params, body, .. } = &args[1].node else { panic!("{args:?}") };
+        assert_eq!(params[0].node, "x");
+        assert!(matches!(&body.stmts[0].node, Stmt::Return(values) if values[0].node == Expr::Name("x".to_string())));
+
+        assert!(matches!(parse_expr("function(...) end").node, Expr::Function { is_vararg: true, .. }));
+        let Expr::Table(fields) = parse_expr("{f = function(a, b) end}").node else { panic!() };
+        assert!(matches!(&fields[0], TableField::Named(_, value) if matches!(value.node, Expr::Function { .. })));
+        let block = parse("local f = function(x) return x + 1 end").unwrap();
+        assert!(matches!(&block.stmts[0].node, Stmt::Local { values, .. } if matches!(values[0].node, Expr::Function { .. })));
+    }
+    #[test]
+    fn parse_function_names() {
+        let func_name = |source: &str| {
+            let block = parse(source).unwrap();
+            let Stmt::Function { name, params, .. } = &block.stmts[0].node else { panic!("{block:?}") };
+            let params: Vec<String> = params.iter().map(|param| param.node.clone()).collect();
+            (name.clone(), params)
+        };
+        let path = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        assert_eq!(func_name("function a() end"), (FuncName { path: path(&["a"]), method: None }, vec![]));
+        assert_eq!(func_name("function a.b(x) end"), (FuncName { path: path(&["a", "b"]), method: None }, path(&["x"])));
+        assert_eq!(
+            func_name("function a.b:c(x) end"),
+            (FuncName { path: path(&["a", "b"]), method: Some("c".to_string()) }, path(&["self", "x"]))
+        );
+        assert_eq!(parse("function a:b.c() end").unwrap_err()[0].message, "expected '('");
+    }
+    #[test]
+    fn parse_function_statement_vs_expression() {
+        let block = parse("function f() return function() end end\nlocal g = function() end").unwrap();
+        let Stmt::Function { body, .. } = &block.stmts[0].node else { panic!("{block:?}") };
+        assert!(matches!(&body.stmts[0].node, Stmt::Return(values) if matches!(values[0].node, Expr::Function { .. })));
+        assert!(matches!(&block.stmts[1].node, Stmt::Local { values, .. } if matches!(values[0].node, Expr::Function { .. })));
+        // An anonymous function in statement position is one error, not one per token.
+        let errors = parse("function() return 1 end\nx = 1").unwrap_err();
+        assert_eq!(errors, vec![Diagnostic::new("expected name after 'function'", Span::new(8, 9))]);
+        assert_eq!(parse("function\n").unwrap_err()[0].message, "expected name");
+    }
+    #[test]
+    fn parse_local_function() {
+        let block = parse("local function f(a, ...) return a end").unwrap();
+        let Stmt::LocalFunction { name, params, is_vararg, body, .. } = &block.stmts[0].node else { panic!("{block:?}") };
+        assert_eq!((name.node.as_str(), name.span), ("f", Span::new(15, 16)));
+        assert_eq!((params.len(), *is_vararg, body.stmts.len()), (1, true, 1));
+        assert_eq!(block.stmts[0].span, Span::new(0, 37));
+        assert_eq!(parse("local function() end").unwrap_err()[0].message, "expected name");
+        assert_eq!(parse("local function t.f() end").unwrap_err()[0].message, "expected '('");
+        let err = parse("local function f()").unwrap_err();
+        assert_eq!(err[0].message, "expected 'end' to close 'function' at byte 0");
+    }
+    #[test]
+    fn parse_vararg_outside_vararg_function() {
+        let err = Parser::new("return function(a) return ... end").unwrap().chunk().unwrap_err();
+        assert_eq!(err, ParseError::new("cannot use '...' outside a vararg function", Span::new(26, 29)));
+        // The enclosing function's `...` doesn't leak into a nested one.
+        let err = Parser::new("return function(...) return function() return ... end end").unwrap().chunk().unwrap_err();
+        assert_eq!(err.span, Span::new(46, 49));
+    }
+    #[test]
+    fn parse_custom_prefix() {
+        let mut table = ParserTable::default();
+        // A DSL "hole" placeholder standing for whatever expression follows it.
+        table.set_prefix(TokenKind::Continue, |parser, _, span| {
+            let inner = parser.subexpr(UNARY_PRECEDENCE)?;
+            let span = span.merge(inner.span);
+            Ok(Spanned::new(Expr::Paren(Box::new(inner)), span))
+        });
+        let expr = Parser::with_table("1 + continue x", Rc::new(table)).unwrap().expr().unwrap();
+        let Expr::Binary { op: BinOp::Add, rhs, .. } = expr.node else { panic!("{expr:?}") };
+        assert_eq!(rhs.span, Span::new(4, 14));
+        assert!(matches!(rhs.node, Expr::Paren(_)));
+
+        assert!(Parser::new("1 + continue x").unwrap().expr().is_err());
+    }
+    #[test]
+    fn parse_removed_infix() {
+        let mut table = ParserTable::default();
+        table.remove_infix(TokenKind::Concatenate);
+        let mut parser = Parser::with_table("a .. b", Rc::new(table)).unwrap();
+        assert_eq!(parser.expr().unwrap().node, Expr::Name("a".to_string()));
+        assert_eq!(parser.peek(), Some(&LuaToken::Concatenate));
+    }
+    #[test]
+    fn parse_program() {
+        let source = "local x, y = 1, 2\nwhile x < 10 do local z = x * 2 end\nfor i = 1, 3 do end\nfor k, v in t do break end\nif x then ::top:: elseif y then goto top else end\nrepeat local w until w\nreturn x;";
+        let block = parse(source).unwrap();
+        let kinds: Vec<_> = block.stmts.iter().map(|stmt| std::mem::discriminant(&stmt.node)).collect();
+        assert_eq!(kinds.len(), 7);
+        assert!(matches!(&block.stmts[0].node, Stmt::Local { names, values, .. } if names.len() == 2 && values.len() == 2));
+        assert!(matches!(&block.stmts[2].node, Stmt::NumericFor { step: None, .. }));
+        assert!(matches!(&block.stmts[3].node, Stmt::GenericFor { names, .. } if names.len() == 2));
+        assert!(matches!(&block.stmts[4].node, Stmt::If { arms, else_block: Some(_) } if arms.len() == 2));
+        assert_eq!(block.stmts[6].span, Span::new(source.len() - 9, source.len()));
+        assert_eq!(block.span, Span::new(0, source.len()));
+    }
+    #[test]
+    fn parse_numeric_for_step() {
+        let block = parse("for i = 10, 1, -1 do end").unwrap();
+        let Stmt::NumericFor { var, start, end, step: Some(step), .. } = &block.stmts[0].node else { panic!("{block:?}") };
+        assert_eq!(var.node, "i");
+        assert!(matches!(start.node, Expr::Integer(10, _)), "{start:?}");
+        assert!(matches!(end.node, Expr::Integer(1, _)), "{end:?}");
+        let Expr::Unary { op: UnOp::Neg, operand } = &step.node else { panic!("{step:?}") };
+        assert!(matches!(operand.node, Expr::Integer(1, _)), "{operand:?}");
+        assert_eq!(step.span, Span::new(15, 17));
+        let block = parse("for i = #t, n - 1, -(k * 2) do end").unwrap();
+        let Stmt::NumericFor { step: Some(step), .. } = &block.stmts[0].node else { panic!("{block:?}") };
+        assert!(matches!(&step.node, Expr::Unary { op: UnOp::Neg, operand } if matches!(operand.node, Expr::Paren(_))));
+    }
+    #[test]
+    fn parse_collects_diagnostics() {
+        let diagnostics = parse("local = 1\nlocal y = $\nwhile x do local 5 end\nreturn 1.2.3 end").unwrap_err();
+        assert_eq!(diagnostics, vec![
+            Diagnostic::new("expected name", Span::new(6, 7)),
+            Diagnostic::new("unexpected character '$'", Span::new(20, 21)).with_code("unexpected-char"),
+            Diagnostic::new("expected expression", Span::new(22, 27)),
+            Diagnostic::new("expected name", Span::new(39, 40)),
+            Diagnostic::new("malformed number", Span::new(52, 57)).with_code("malformed-number"),
+            Diagnostic::new("unexpected 'end'", Span::new(58, 61)),
+        ]);
+    }
+    #[test]
+    fn parse_max_errors() {
+        let source = "local = 1\n".repeat(20);
+        assert_eq!(parse(&source).unwrap_err().len(), 20);
+        let diagnostics = parse_with_max_errors(&source, Dialect::Lua54, 5).unwrap_err();
+        assert_eq!(diagnostics.len(), 6);
+        assert!(diagnostics[..5].iter().all(|diagnostic| diagnostic.message == "expected name"));
+        assert_eq!(diagnostics[4].span, Span::new(46, 47));
+        let note = Diagnostic::new("too many errors, stopped after 5", Span::empty(47)).with_code("too-many-errors");
+        assert_eq!(diagnostics[5], note);
+        // Lex errors count towards the cap, and input within it reports as usual.
+        assert_eq!(parse_with_max_errors("x = $ $ $", Dialect::Lua54, 2).unwrap_err().len(), 3);
+        assert_eq!(parse_with_max_errors("local = 1", Dialect::Lua54, 5).unwrap_err().len(), 1);
+        // Reaching the cap with input left stops there, whatever the rest holds.
+        assert_eq!(parse_with_max_errors("while x do local end", Dialect::Lua54, 1).unwrap_err().len(), 2);
+        // A cap of 0 fails on any error with just the note.
+        let note = Diagnostic::new("too many errors, stopped after 0", Span::empty(0)).with_code("too-many-errors");
+        assert_eq!(parse_with_max_errors("x = = 1", Dialect::Lua54, 0), Err(vec![note.clone()]));
+        assert_eq!(parse_with_max_errors("x = $", Dialect::Lua54, 0), Err(vec![note]));
+        assert!(parse_with_max_errors("x = 1", Dialect::Lua54, 0).is_ok());
+    }
+    #[test]
+    fn parse_lex_error_propagates() {
+        fn parse_strict(source: &str) -> Result<Block, ParseError> {
+            Parser::new(source)?.chunk()
+        }
+        assert_eq!(parse_strict("x = 1.2.3"), Err(ParseError::new("malformed number", Span::new(4, 9))));
+        let err = ParseError::from(LexerBuilder::new().dialect(Dialect::Lua51).tokenize("x = a // b").unwrap_err());
+        assert_eq!((err.message.as_str(), err.span, err.dialect), ("floor division requires Lua 5.3+", Span::new(6, 8), Some(Dialect::Lua51)));
+    }
+    #[test]
+    fn parse_unclosed_block() {
+        let err = Parser::new("while x do\nlocal y").unwrap().chunk().unwrap_err();
+        assert_eq!(err, ParseError::new("expected 'end' to close 'while' at byte 0", Span::new(18, 18)));
+    }
+    #[test]
+    fn parse_unclosed_paren() {
+        let err = Parser::new("(a + b").unwrap().expr().unwrap_err();
+        assert_eq!(err, ParseError::new("expected ')'", Span::new(6, 6)));
+    }
+    #[test]
+    fn parse_trailing_separators() {
+        let fields = |source: &str| match parse_expr(source).node {
+            Expr::Table(fields) => fields.len(),
+            expr => panic!("{expr:?}"),
+        };
+        assert_eq!(fields("{1,2,}"), 2);
+        assert_eq!(fields("{1;2;}"), 2);
+        assert_eq!(fields("{x = 1; 2,}"), 2);
+        assert_eq!(parse("f(1,2,)").unwrap_err()[0], Diagnostic::new("trailing ',' in arguments", Span::new(5, 6)));
+        assert_eq!(parse("f(,)").unwrap_err()[0].message, "expected expression");
+        assert_eq!(parse("return {,}").unwrap_err()[0].message, "expected expression");
+    }
+    #[test]
+    fn parse_mixed_table_separators() {
+        let table = |source: &str| match parse_expr(source).node {
+            Expr::Table(fields) => fields,
+            expr => panic!("{expr:?}"),
+        };
+        let fields = table("{1; 2, 3;}");
+        assert_eq!(fields.len(), 3);
+        assert!(fields.iter().all(|field| matches!(field, TableField::Positional(_))), "{fields:?}");
+        let fields = table("{[1]=2; x=3}");
+        assert!(matches!(&fields[..], [TableField::Keyed(..), TableField::Named(name, _)] if name.node == "x"), "{fields:?}");
+        assert_eq!(table("{}"), []);
+        // As in reference Lua, a separator must follow a field.
+        assert_eq!(parse("return {;}").unwrap_err()[0], Diagnostic::new("expected expression", Span::new(8, 9)));
+        assert_eq!(parse("return {;1}").unwrap_err()[0].message, "expected expression");
+        assert_eq!(parse("return {1;;2}").unwrap_err()[0], Diagnostic::new("expected expression", Span::new(10, 11)));
+    }
+    #[test]
+    fn parse_call_statement() {
+        let block = parse("f()\no:m 'x' {1}").unwrap();
+        let Stmt::Call(call) = &block.stmts[0].node else { panic!("{block:?}") };
+        assert!(matches!(&call.node, Expr::Call { args, .. } if args.is_empty()), "{call:?}");
+        let Stmt::Call(call) = &block.stmts[1].node else { panic!("{block:?}") };
+        // `o:m 'x'` returns a function that is then called with the table.
+        let Expr::Call { func, args } = &call.node else { panic!("{call:?}") };
+        assert!(matches!(&func.node, Expr::MethodCall { method, .. } if method.node == "m"), "{func:?}");
+        assert!(matches!(args[0].node, Expr::Table(_)));
+    }
+    #[test]
+    fn parse_return_placement() {
+        let values = |source: &str| match parse(source).unwrap().stmts.pop().unwrap().node {
+            Stmt::Return(values) => values.len(),
+            stmt => panic!("{stmt:?}"),
+        };
+        assert_eq!(values("return"), 0);
+        assert_eq!(values("return;"), 0);
+        assert_eq!(values("return 1, 2;"), 2);
+        assert_eq!(values("do return end return"), 0);
+        let message = "'return' must be the last statement in its block";
+        assert_eq!(parse("return 1 local x = 2").unwrap_err(), [Diagnostic::new(message, Span::new(9, 14))]);
+        assert_eq!(parse("if x then return; f() end").unwrap_err(), [Diagnostic::new(message, Span::new(18, 19))]);
+        assert_eq!(parse("return; ;").unwrap_err()[0].message, message);
+    }
+    #[test]
+    fn parse_interpolated_string() {
+        let parts = |source: &str| match parse_dialect(source, Dialect::Luau).unwrap().stmts.pop().unwrap().node {
+            Stmt::Return(mut values) => match values.pop().unwrap().node {
+                Expr::InterpolatedString { parts } => parts,
+                expr => panic!("{expr:?}"),
+            },
+            stmt => panic!("{stmt:?}"),
+        };
+        let name = Spanned::new(Expr::Name("name".to_string()), Span::new(15, 19));
+        assert_eq!(parts("return `hello {name}`"), [StringPart::Literal(b"hello ".to_vec()), StringPart::Expr(name)]);
+        assert_eq!(parts(r"return `a\{b}`"), [StringPart::Literal(b"a{b}".to_vec())]);
+        assert_eq!(parts(r"return `\`\n\\{1}`")[0], StringPart::Literal(b"`\n\\".to_vec()));
+        let nested = parts("return `{ #{1} }, {f(`{'}'}`)}!`");
+        assert!(matches!(&nested[..], [StringPart::Expr(_), StringPart::Literal(comma), StringPart::Expr(_), StringPart::Literal(bang)] if comma == b", " && bang == b"!"));
+        assert_eq!(parts("return ``"), []);
+
+        let error = |source| parse_dialect(source, Dialect::Luau).unwrap_err()[0].clone();
+        assert_eq!(error("return `{}`").message, "expected expression");
+        assert_eq!(error("return `{a b}`"), Diagnostic::new("expected '}'", Span::new(11, 12)));
+        assert_eq!(error(r"return `\q{x}`").span, Span::new(8, 10));
+        assert_eq!(parse("return `a`").unwrap_err()[0].message, "unexpected character '`'");
+    }
+    #[test]
+    fn parse_keyword_literals() {
+        assert_eq!(parse_expr("nil").node, Expr::Nil);
+        assert_eq!(parse_expr("true").node, Expr::Bool(true));
+        assert_eq!(parse_expr("false").node, Expr::Bool(false));
+        assert_eq!(parse_expr("  false").span, Span::new(2, 7));
+        let block = parse("x = nil").unwrap();
+        let Stmt::Assign { values, .. } = &block.stmts[0].node else { panic!("{block:?}") };
+        assert_eq!((&values[0].node, values[0].span), (&Expr::Nil, Span::new(4, 7)));
+        assert_eq!(parse("nil = 1").unwrap_err()[0].message, "expected statement");
+    }
+    #[test]
+    fn parse_long_string_call() {
+        let block = parse("require[[mymodule]]\nlocal m = require [==[a]]b]==]").unwrap();
+        let Stmt::Call(call) = &block.stmts[0].node else { panic!("{block:?}") };
+        let Expr::Call { func, args } = &call.node else { panic!("{call:?}") };
+        assert_eq!(func.node, Expr::Name("require".to_string()));
+        assert_eq!(args.iter().map(|arg| &arg.node).collect::<Vec<_>>(), [&Expr::String(b"mymodule".to_vec())]);
+        assert_eq!((args[0].span, call.span), (Span::new(7, 19), Span::new(0, 19)));
+        let Stmt::Local { values, .. } = &block.stmts[1].node else { panic!("{block:?}") };
+        assert!(matches!(&values[0].node, Expr::Call { args, .. } if args[0].node == Expr::String(b"a]]b".to_vec())));
+    }
+    #[test]
+    fn parse_multiline_method_chain() {
+        let block = parse("obj\n  :a()\n  :b\n  (1,\n   2)\n  .c\n  :d\n  'x'").unwrap();
+        let Stmt::Call(call) = &block.stmts[0].node else { panic!("{block:?}") };
+        let Expr::MethodCall { obj, method, args } = &call.node else { panic!("{call:?}") };
+        assert_eq!((method.node.as_str(), args.len()), ("d", 1));
+        let Expr::Field { obj, name } = &obj.node else { panic!("{obj:?}") };
+        assert_eq!(name.node, "c");
+        let Expr::MethodCall { obj, method, args } = &obj.node else { panic!("{obj:?}") };
+        assert_eq!((method.node.as_str(), args.len()), ("b", 2));
+        let Expr::MethodCall { obj, method, args } = &obj.node else { panic!("{obj:?}") };
+        assert_eq!((method.node.as_str(), args.len()), ("a", 0));
+        assert_eq!(obj.node, Expr::Name("obj".to_string()));
+        assert_eq!(call.span, Span::new(0, 43));
+    }
+    #[test]
+    fn parse_empty_blocks() {
+        assert_eq!(parse("").unwrap(), Block { stmts: Vec::new(), span: Span::empty(0) });
+        assert_eq!(parse("  -- nothing\n").unwrap().stmts, []);
+        let empty = |stmt: &Stmt| match stmt {
+            Stmt::Do(body) | Stmt::Function { body, .. } => body.stmts.is_empty(),
+            Stmt::If { arms, else_block: None } => arms[0].1.stmts.is_empty(),
+            _ => false,
+        };
+        for source in ["do end", "function f() end", "if x then end"] {
+            let block = parse(source).unwrap();
+            assert!(matches!(&block.stmts[..], [stmt] if empty(&stmt.node)), "{source}: {block:?}");
+        }
+    }
+    #[test]
+    fn parse_semicolons() {
+        let block = parse("a=1; b=2;").unwrap();
+        assert_eq!(block.stmts.len(), 2);
+        assert_eq!(crate::ast::ast_diff(&block, &parse(";;a=1;;b=2").unwrap()), None);
+        assert_eq!(parse(";;;").unwrap().stmts.len(), 0);
+        assert_eq!(parse("do ; end; return 1;").unwrap().stmts.len(), 2);
+    }
+    #[test]
+    fn parse_number_spelling() {
+        assert_eq!(parse_expr("0xFF").node, Expr::Integer(255, Some("0xFF".to_string())));
+        assert_eq!(parse_expr("1e3").node, Expr::Float(1000.0, Some("1e3".to_string())));
+        let source = "local a, b = 0xFF, 1e3";
+        assert_eq!(crate::print::print_block(&parse(source).unwrap()), source);
+    }
+    #[test]
+    fn parse_swap_and_uneven_assignment() {
+        let block = parse("a, b = b, a\nlocal x, y = y, x").unwrap();
+        let Stmt::Assign { targets, values } = &block.stmts[0].node else { panic!("{block:?}") };
+        let names = |exprs: &[Spanned<Expr>]| exprs.iter().map(|expr| expr.node.clone()).collect::<Vec<_>>();
+        let (a, b) = (Expr::Name("a".to_string()), Expr::Name("b".to_string()));
+        assert_eq!((names(targets), names(values)), (vec![a.clone(), b.clone()], vec![b, a]));
+        assert!(matches!(&block.stmts[1].node, Stmt::Local { names, values, .. } if names.len() == 2 && values.len() == 2));
+        // Extra targets get `nil` at run time, so an uneven count still parses.
+        let block = parse("a, b = 1\na = 1, 2").unwrap();
+        assert!(matches!(&block.stmts[0].node, Stmt::Assign { targets, values } if targets.len() == 2 && values.len() == 1));
+        assert!(matches!(&block.stmts[1].node, Stmt::Assign { targets, values } if targets.len() == 1 && values.len() == 2));
+    }
+    #[test]
+    fn parse_assignment() {
+        let block = parse("a.b = 1").unwrap();
+        let Stmt::Assign { targets, values } = &block.stmts[0].node else { panic!("{block:?}") };
+        let Expr::Field { obj, name } = &targets[0].node else { panic!("{targets:?}") };
+        assert_eq!((&obj.node, name.node.as_str()), (&Expr::Name("a".to_string()), "b"));
+        assert_eq!(values[0].node, Expr::Integer(1, Some("1".to_string())));
+        assert_eq!(block.stmts[0].span, Span::new(0, 7));
+
+        let block = parse("a, t.x, u[1] = 1, 2, 3").unwrap();
+        let Stmt::Assign { targets, values } = &block.stmts[0].node else { panic!("{block:?}") };
+        assert!(matches!(
+            targets.iter().map(|target| &target.node).collect::<Vec<_>>()[..],
+            [Expr::Name(_), Expr::Field { .. }, Expr::Index { .. }]
+        ));
+        assert_eq!(values.len(), 3);
+    }
+    #[test]
+    fn parse_invalid_assignment() {
+        assert_eq!(parse("a, 5 = 1, 2").unwrap_err()[0], Diagnostic::new("cannot assign to a literal", Span::new(3, 4)));
+        assert_eq!(parse("a, t.x:m() = 1, 2").unwrap_err()[0].message, "cannot assign to a function call");
+        assert_eq!(parse("f() = 1").unwrap_err()[0], Diagnostic::new("cannot assign to a function call", Span::new(0, 3)));
+        assert_eq!(parse("(a) = 1").unwrap_err()[0].message, "cannot assign to this expression");
+        assert_eq!(parse("x").unwrap_err()[0], Diagnostic::new("expected '='", Span::new(1, 1)));
+        assert_eq!(parse("x = 'a':upper()").unwrap_err()[0].message, "unexpected ':'");
+    }
+    #[test]
+    fn parse_peek_kind() {
+        let source = "local t = {a.b, 'c', 1.5, [[d]]} -- e\nreturn t:f(...)";
+        let mut parser = Parser::new(source).unwrap();
+        loop {
+            assert_eq!(parser.peek_kind(), parser.peek().map(|token| token.kind()));
+            if parser.advance().is_none() {
+                break;
+            }
+        }
+        let payload_peeks = Parser::new(source).unwrap().with_payload_peeks(true).chunk();
+        assert_eq!(payload_peeks, Parser::new(source).unwrap().chunk());
+    }
+    #[test]
+    fn parse_in_keyword() {
+        let block = parse("for k in pairs(t) do end").unwrap();
+        assert!(matches!(&block.stmts[0].node, Stmt::GenericFor { names, exprs, .. } if names.len() == 1 && exprs.len() == 1));
+        let message = "'in' is only valid after the names of a generic 'for'";
+        assert_eq!(parse("local x in y").unwrap_err()[0], Diagnostic::new(message, Span::new(8, 10)));
+        assert_eq!(parse("x in y").unwrap_err()[0], Diagnostic::new(message, Span::new(2, 4)));
+        assert_eq!(parse("for i = 1 in t do end").unwrap_err()[0].message, "expected ','");
+    }
+    #[test]
+    fn parse_long_string_newlines() {
+        let block = parse("return [[\r\nfirst\r\nsecond\rthird\n]]").unwrap();
+        let Stmt::Return(values) = &block.stmts[0].node else { panic!("{block:?}") };
+        assert_eq!(values[0].node, Expr::String(b"first\nsecond\nthird\n".to_vec()));
+    }
+    #[test]
+    fn parse_string_escapes() {
+        assert_eq!(parse_expr(r#""say \"hi\"\n""#).node, Expr::String(b"say \"hi\"\n".to_vec()));
+        assert_eq!(parse_expr(r"'\255'").node, Expr::String(vec![255]));
+        assert_eq!(parse_expr(r"'\xff\x80'").node, Expr::String(vec![0xff, 0x80]));
+        assert!(parse(r"x = '\255' .. '\xFF'").is_ok());
+        let err = Parser::new(r"x .. 'a\qb'").unwrap().expr().unwrap_err();
+        assert_eq!(err, ParseError::new("invalid escape sequence", Span::new(7, 9)));
+    }
+}
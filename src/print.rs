@@ -0,0 +1,528 @@
+use crate::ast::{needs_parens, Block, Expr, FuncTypes, Side, Stmt, StringPart, TableField, TypeExpr, UNARY_PRECEDENCE};
+use crate::span::Spanned;
+
+const INDENT: &str = "    ";
+
+/// Re-emit an expression as Lua source with the minimum parentheses needed
+/// to keep its tree.
+pub fn print_expr(expr: &Spanned<Expr>) -> String {
+    let mut printer = Printer::default();
+    printer.expr(strip_parens(expr));
+    printer.out
+}
+
+/// Re-emit a block as Lua source, one statement per line.
+pub fn print_block(block: &Block) -> String {
+    let mut printer = Printer::default();
+    printer.stmts(block);
+    printer.out
+}
+
+/// Parentheses only change meaning by truncating a multi-valued expression
+/// to one value, everywhere else the printer decides them from precedence.
+fn strip_parens(mut expr: &Spanned<Expr>) -> &Spanned<Expr> {
+    while let Expr::Paren(inner) = &expr.node {
+        if inner.node.is_multi_valued() {
+            break;
+        }
+        expr = inner;
+    }
+    expr
+}
+
+/// `name`, with `: type` appended if it has a Luau annotation.
+fn annotated(name: &str, ty: Option<&Option<Spanned<TypeExpr>>>) -> String {
+    match ty {
+        Some(Some(ty)) => format!("{name}: {}", ty.node.0),
+        _ => name.to_string(),
+    }
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    indent: usize,
+}
+
+impl Printer {
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    //==-----------
+    // Statements
+    //==-----------
+    /// Write each statement of `block` on its own line at the current indent.
+    fn stmts(&mut self, block: &Block) {
+        for (i, stmt) in block.stmts.iter().enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+            self.stmt(stmt);
+        }
+    }
+
+    /// Write an indented block body between two header/footer lines.
+    fn body(&mut self, block: &Block) {
+        self.indent += 1;
+        if !block.stmts.is_empty() {
+            self.newline();
+            self.stmts(block);
+        }
+        self.indent -= 1;
+        self.newline();
+    }
+
+    fn stmt(&mut self, stmt: &Spanned<Stmt>) {
+        match &stmt.node {
+            Stmt::Assign { targets, values } => {
+                self.expr_list(targets);
+                self.out.push_str(" = ");
+                self.expr_list(values);
+            }
+            Stmt::CompoundAssign { target, op, value } => {
+                self.expr(target);
+                self.out.push_str(&format!(" {}= ", op.as_str()));
+                self.expr(value);
+            }
+            Stmt::Call(call) => self.expr(call),
+            Stmt::Local { names, types, attribs, values } => {
+                self.out.push_str("local ");
+                let names: Vec<String> = names
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| match attribs.get(i) {
+                        Some(Some(attrib)) => format!("{} <{}>", annotated(&name.node, types.get(i)), attrib.node.as_str()),
+                        _ => annotated(&name.node, types.get(i)),
+                    })
+                    .collect();
+                self.out.push_str(&names.join(", "));
+                if !values.is_empty() {
+                    self.out.push_str(" = ");
+                    self.expr_list(values);
+                }
+            }
+            Stmt::Do(body) => {
+                self.out.push_str("do");
+                self.body(body);
+                self.out.push_str("end");
+            }
+            Stmt::While { cond, body } => {
+                self.out.push_str("while ");
+                self.expr(strip_parens(cond));
+                self.out.push_str(" do");
+                self.body(body);
+                self.out.push_str("end");
+            }
+            Stmt::Repeat { body, cond } => {
+                self.out.push_str("repeat");
+                self.body(body);
+                self.out.push_str("until ");
+                self.expr(strip_parens(cond));
+            }
+            Stmt::If { arms, else_block } => {
+                for (i, (cond, body)) in arms.iter().enumerate() {
+                    self.out.push_str(if i == 0 { "if " } else { "elseif " });
+                    self.expr(strip_parens(cond));
+                    self.out.push_str(" then");
+                    self.body(body);
+                }
+                if let Some(body) = else_block {
+                    self.out.push_str("else");
+                    self.body(body);
+                }
+                self.out.push_str("end");
+            }
+            Stmt::NumericFor { var, start, end, step, body } => {
+                self.out.push_str("for ");
+                self.out.push_str(&var.node);
+                self.out.push_str(" = ");
+                self.expr(strip_parens(start));
+                self.out.push_str(", ");
+                self.expr(strip_parens(end));
+                if let Some(step) = step {
+                    self.out.push_str(", ");
+                    self.expr(strip_parens(step));
+                }
+                self.out.push_str(" do");
+                self.body(body);
+                self.out.push_str("end");
+            }
+            Stmt::GenericFor { names, exprs, body } => {
+                self.out.push_str("for ");
+                self.names(names, &[]);
+                self.out.push_str(" in ");
+                self.expr_list(exprs);
+                self.out.push_str(" do");
+                self.body(body);
+                self.out.push_str("end");
+            }
+            Stmt::Break => self.out.push_str("break"),
+            Stmt::Goto(label) => {
+                self.out.push_str("goto ");
+                self.out.push_str(&label.node);
+            }
+            Stmt::Label(label) => {
+                self.out.push_str("::");
+                self.out.push_str(&label.node);
+                self.out.push_str("::");
+            }
+            Stmt::Return(values) => {
+                self.out.push_str("return");
+                if !values.is_empty() {
+                    self.out.push(' ');
+                    self.expr_list(values);
+                }
+            }
+            Stmt::Function { name, params, is_vararg, types, body } => {
+                self.out.push_str("function ");
+                self.out.push_str(&name.path.join("."));
+                let mut skip = 0;
+                if let Some(method) = &name.method {
+                    self.out.push(':');
+                    self.out.push_str(method);
+                    // The implicit `self` comes from the `:`.
+                    skip = 1;
+                }
+                self.params(params, *is_vararg, types, skip);
+                self.body(body);
+                self.out.push_str("end");
+            }
+            Stmt::LocalFunction { name, params, is_vararg, types, body } => {
+                self.out.push_str("local function ");
+                self.out.push_str(&name.node);
+                self.params(params, *is_vararg, types, 0);
+                self.body(body);
+                self.out.push_str("end");
+            }
+        }
+    }
+
+    //==------------
+    // Expressions
+    //==------------
+    fn expr_list(&mut self, exprs: &[Spanned<Expr>]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(strip_parens(expr));
+        }
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        match &expr.node {
+            Expr::Nil => self.out.push_str("nil"),
+            Expr::Bool(value) => self.out.push_str(if *value { "true" } else { "false" }),
+            Expr::Integer(_, Some(text)) | Expr::Float(_, Some(text)) => self.out.push_str(text),
+            Expr::Integer(value, None) => write_integer(&mut self.out, *value),
+            Expr::Float(value, None) => write_float(&mut self.out, *value),
+            Expr::String(value) => write_string(&mut self.out, value),
+            Expr::InterpolatedString { parts } => {
+                self.out.push('`');
+                for part in parts {
+                    match part {
+                        StringPart::Literal(text) => write_interpolated_text(&mut self.out, text),
+                        StringPart::Expr(value) => {
+                            self.out.push('{');
+                            self.expr(strip_parens(value));
+                            self.out.push('}');
+                        }
+                    }
+                }
+                self.out.push('`');
+            }
+            Expr::Vararg => self.out.push_str("..."),
+            Expr::Name(name) => self.out.push_str(name),
+            Expr::Function { params, is_vararg, types, body } => {
+                self.out.push_str("function");
+                self.params(params, *is_vararg, types, 0);
+                self.body(body);
+                self.out.push_str("end");
+            }
+            Expr::Table(fields) => self.table(fields),
+            Expr::Paren(inner) => {
+                if inner.node.is_multi_valued() {
+                    self.out.push('(');
+                    self.expr(inner);
+                    self.out.push(')');
+                } else {
+                    self.expr(strip_parens(inner));
+                }
+            }
+            Expr::Field { obj, name } => {
+                self.prefix(obj);
+                self.out.push('.');
+                self.out.push_str(&name.node);
+            }
+            Expr::Index { obj, key } => {
+                self.prefix(obj);
+                self.out.push('[');
+                self.expr(strip_parens(key));
+                self.out.push(']');
+            }
+            Expr::Call { func, args } => {
+                self.prefix(func);
+                self.args(args);
+            }
+            Expr::MethodCall { obj, method, args } => {
+                self.prefix(obj);
+                self.out.push(':');
+                self.out.push_str(&method.node);
+                self.args(args);
+            }
+            Expr::Binary { op, lhs, rhs } | Expr::Logical { op, lhs, rhs } => {
+                let (lhs, rhs) = (strip_parens(lhs), strip_parens(rhs));
+                let wrap_lhs = match &lhs.node {
+                    Expr::Binary { op: child, .. } | Expr::Logical { op: child, .. } => {
+                        needs_parens(*op, *child, Side::Left)
+                    }
+                    // `-x ^ 2` is `-(x ^ 2)`, so a unary left operand of `^` needs them.
+                    Expr::Unary { .. } => op.precedence() > UNARY_PRECEDENCE,
+                    // So does a folded negative literal, which prints with its sign.
+                    Expr::Integer(value, _) if *value < 0 && *value != i64::MIN => op.precedence() > UNARY_PRECEDENCE,
+                    Expr::Float(value, _) if value.is_sign_negative() => op.precedence() > UNARY_PRECEDENCE,
+                    _ => false,
+                };
+                let wrap_rhs = match &rhs.node {
+                    Expr::Binary { op: child, .. } | Expr::Logical { op: child, .. } => {
+                        needs_parens(*op, *child, Side::Right)
+                    }
+                    _ => false,
+                };
+                self.operand(lhs, wrap_lhs);
+                self.out.push(' ');
+                self.out.push_str(op.as_str());
+                self.out.push(' ');
+                self.operand(rhs, wrap_rhs);
+            }
+            Expr::Unary { op, operand } => {
+                let operand = strip_parens(operand);
+                let wrap = match &operand.node {
+                    Expr::Binary { op: child, .. } | Expr::Logical { op: child, .. } => {
+                        child.precedence() < UNARY_PRECEDENCE
+                    }
+                    _ => false,
+                };
+                self.out.push_str(op.as_str());
+                let start = self.out.len();
+                self.operand(operand, wrap);
+                // Keep `not` apart from its operand and `- -x` from becoming a comment.
+                if op.as_str().ends_with(char::is_alphabetic) || self.out[start..].starts_with('-') {
+                    self.out.insert(start, ' ');
+                }
+            }
+        }
+    }
+
+    fn operand(&mut self, expr: &Spanned<Expr>, wrap: bool) {
+        if wrap {
+            self.out.push('(');
+            self.expr(expr);
+            self.out.push(')');
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    /// Write the object of a call or index, parenthesised unless it is a prefix expression.
+    fn prefix(&mut self, expr: &Spanned<Expr>) {
+        let expr = strip_parens(expr);
+        self.operand(expr, !expr.node.is_prefix());
+    }
+
+    fn args(&mut self, args: &[Spanned<Expr>]) {
+        self.out.push('(');
+        self.expr_list(args);
+        self.out.push(')');
+    }
+
+    /// Print `names`, each followed by its entry in `types` if it has one.
+    fn names(&mut self, names: &[Spanned<String>], types: &[Option<Spanned<TypeExpr>>]) {
+        let names: Vec<String> = names.iter().enumerate().map(|(i, name)| annotated(&name.node, types.get(i))).collect();
+        self.out.push_str(&names.join(", "));
+    }
+
+    /// Print a parameter list, skipping the first `skip` parameters.
+    fn params(&mut self, params: &[Spanned<String>], is_vararg: bool, types: &FuncTypes, skip: usize) {
+        self.out.push('(');
+        let mut names: Vec<String> =
+            params.iter().enumerate().skip(skip).map(|(i, param)| annotated(&param.node, types.params.get(i))).collect();
+        if is_vararg {
+            names.push(annotated("...", Some(&types.vararg)));
+        }
+        self.out.push_str(&names.join(", "));
+        self.out.push(')');
+        if let Some(ret) = &types.ret {
+            self.out.push_str(": ");
+            self.out.push_str(&ret.node.0);
+        }
+    }
+
+    fn table(&mut self, fields: &[TableField]) {
+        self.out.push('{');
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            match field {
+                TableField::Positional(value) => self.expr(strip_parens(value)),
+                TableField::Named(name, value) => {
+                    self.out.push_str(&name.node);
+                    self.out.push_str(" = ");
+                    self.expr(strip_parens(value));
+                }
+                TableField::Keyed(key, value) => {
+                    self.out.push('[');
+                    self.expr(strip_parens(key));
+                    self.out.push_str("] = ");
+                    self.expr(strip_parens(value));
+                }
+            }
+        }
+        self.out.push('}');
+    }
+}
+
+fn write_integer(out: &mut String, value: i64) {
+    if value == i64::MIN {
+        // `9223372036854775808` is past `i64::MAX` and lexes as a float, so `-`
+        // before it can't give an integer. Hex integers wrap, and this one
+        // wraps to `i64::MIN`.
+        out.push_str("0x8000000000000000");
+    } else {
+        out.push_str(&value.to_string());
+    }
+}
+
+fn write_float(out: &mut String, value: f64) {
+    if value.is_nan() {
+        out.push_str("(0/0)");
+    } else if value.is_infinite() {
+        out.push_str(if value > 0.0 { "1e9999" } else { "-1e9999" });
+    } else {
+        // Debug formatting keeps the `.0` that marks a float subtype.
+        out.push_str(&format!("{value:?}"));
+    }
+}
+
+fn write_string(out: &mut String, value: &[u8]) {
+    out.push('"');
+    write_escaped(out, value, |out, c| match c {
+        '"' => out.push_str("\\\""),
+        '\\' => out.push_str("\\\\"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        _ => out.push(c),
+    });
+    out.push('"');
+}
+
+/// Write the text of an interpolated string, escaping what would end it or open a hole.
+fn write_interpolated_text(out: &mut String, text: &[u8]) {
+    write_escaped(out, text, |out, c| match c {
+        '`' | '{' | '}' | '\\' => {
+            out.push('\\');
+            out.push(c);
+        }
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        _ => out.push(c),
+    });
+}
+
+/// Write the UTF-8 in `bytes` a character at a time through `write_char`, and
+/// any other bytes as three-digit `\ddd` escapes so a digit after one can't
+/// extend it.
+fn write_escaped(out: &mut String, bytes: &[u8], mut write_char: impl FnMut(&mut String, char)) {
+    for chunk in bytes.utf8_chunks() {
+        chunk.valid().chars().for_each(|c| write_char(out, c));
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\{byte:03}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+
+    fn reprint(source: &str) -> String {
+        print_expr(&Parser::new(source).unwrap().expr().unwrap())
+    }
+
+    #[test]
+    fn print_minimal_parens() {
+        assert_eq!(reprint("a + b * c"), "a + b * c");
+        assert_eq!(reprint("(a + b) * c"), "(a + b) * c");
+        assert_eq!(reprint("a + (b * c)"), "a + b * c");
+        assert_eq!(reprint("((a))"), "a");
+    }
+    #[test]
+    fn print_assoc_parens() {
+        assert_eq!(reprint("a - (b - c)"), "a - (b - c)");
+        assert_eq!(reprint("(a - b) - c"), "a - b - c");
+        assert_eq!(reprint("(a ^ b) ^ c"), "(a ^ b) ^ c");
+        assert_eq!(reprint("a .. (b .. c)"), "a .. b .. c");
+    }
+    #[test]
+    fn print_function() {
+        assert_eq!(reprint("function(a, ...) return {a, (...), x = 1, [2] = ...} end"),
+            "function(a, ...)\n    return {a, (...), x = 1, [2] = ...}\nend");
+        let block = Parser::new("return function() end, 1").unwrap().chunk().unwrap();
+        assert_eq!(print_block(&block), "return function()\nend, 1");
+        let source = "function a.b:c(x, ...)\n    return self\nend";
+        assert_eq!(print_block(&crate::parse::parse(source).unwrap()), source);
+        let source = "local function f(n)\n    return f(n - 1)\nend";
+        assert_eq!(print_block(&crate::parse::parse(source).unwrap()), source);
+        let source = "local n: number, t = 1\nfunction m:f(a: {string}, ...: any): number\nend";
+        assert_eq!(print_block(&crate::parse::parse_dialect(source, crate::lex::Dialect::Luau).unwrap()), source);
+    }
+    #[test]
+    fn print_statements() {
+        let source = "local a <const>, b = 1\nwhile a do\n    ::again::\n    if b then\n        break\n    elseif a then\n        goto again\n    else\n    end\nend\nfor i = 1, 2, 3 do\nend\nrepeat\n    do\n    end\nuntil a\nfor k, v in a, b do\nend";
+        assert_eq!(print_block(&crate::parse::parse(source).unwrap()), source);
+    }
+    #[test]
+    fn print_suffixes() {
+        let source = "a.b[1], c = f(x):m(\"s\", {}), (g())\nh(a)";
+        assert_eq!(print_block(&crate::parse::parse(source).unwrap()), source);
+        assert_eq!(reprint("(a + b).c"), "(a + b).c");
+        assert_eq!(reprint("(a).b((c))"), "a.b(c)");
+        assert_eq!(reprint("-t[1] ^ 2"), "-t[1] ^ 2");
+    }
+    #[test]
+    fn print_byte_strings() {
+        assert_eq!(reprint(r"'caf\xc3\xa9\n'"), "\"caf\u{e9}\\n\"");
+        assert_eq!(reprint(r"'\2551\x80'"), r#""\2551\128""#);
+        let printed = reprint(r"'a\1\xff'");
+        assert_eq!(Parser::new(&printed).unwrap().expr().unwrap().node, Expr::String(b"a\x01\xff".to_vec()));
+    }
+    #[test]
+    fn print_unary() {
+        assert_eq!(reprint("(-x) ^ 2"), "(-x) ^ 2");
+        assert_eq!(reprint("-(x ^ 2)"), "-x ^ 2");
+        assert_eq!(reprint("-(a + b)"), "-(a + b)");
+        assert_eq!(reprint("- -x"), "- -x");
+        assert_eq!(reprint("not x == 1.0"), "not x == 1.0");
+        let mut expr = Parser::new("(5 % -3) ^ x").unwrap().expr().unwrap();
+        crate::fold::fold_expr(&mut expr);
+        assert_eq!(print_expr(&expr), "(-1) ^ x");
+    }
+    #[test]
+    fn print_folded_min_integer() {
+        for source in ["9223372036854775807 + 1", "-9223372036854775807 - 1"] {
+            let mut expr = Parser::new(source).unwrap().expr().unwrap();
+            crate::fold::fold_expr(&mut expr);
+            assert_eq!(expr.node, Expr::Integer(i64::MIN, None));
+            let printed = print_expr(&expr);
+            assert_eq!(printed, "0x8000000000000000");
+            let reparsed = Parser::new(&printed).unwrap().expr().unwrap();
+            assert_eq!(reparsed.node, Expr::Integer(i64::MIN, Some(printed)));
+        }
+    }
+}
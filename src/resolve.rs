@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use crate::ast::{for_each_child_expr, Block, Expr, Stmt};
+use crate::span::{Span, Spanned};
+
+/// What a name refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    /// The local, parameter or loop variable whose name is at this span.
+    Local(Span),
+    /// No local of that name is in scope, so a global.
+    Global,
+}
+
+/// Resolve every name expression in `block`, a whole chunk, to its binding,
+/// keyed by the span of the name expression.
+///
+/// A `local` is in scope from the statement after it to the end of its block,
+/// and a later local of the same name shadows it. The `until` condition of a
+/// `repeat` is inside the body's scope, so it sees the body's locals.
+pub fn resolve_names(block: &Block) -> HashMap<Span, Binding> {
+    let mut resolver = Resolver::default();
+    resolver.block(block);
+    resolver.bindings
+}
+
+#[derive(Default)]
+struct Resolver {
+    /// Locals in scope, innermost last.
+    scope: Vec<(String, Span)>,
+    bindings: HashMap<Span, Binding>,
+}
+
+impl Resolver {
+    fn block(&mut self, block: &Block) {
+        let outer = self.scope.len();
+        self.stmts(block);
+        self.scope.truncate(outer);
+    }
+
+    /// Resolve the statements of `block`, leaving its locals in scope.
+    fn stmts(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            self.stmt(&stmt.node);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Assign { targets, values } => targets.iter().chain(values).for_each(|expr| self.expr(expr)),
+            Stmt::CompoundAssign { target, value, .. } => {
+                self.expr(target);
+                self.expr(value);
+            }
+            Stmt::Call(expr) => self.expr(expr),
+            Stmt::Local { names, values, .. } => {
+                // The values are evaluated before the names come into scope.
+                values.iter().for_each(|expr| self.expr(expr));
+                self.declare(names);
+            }
+            Stmt::Do(body) => self.block(body),
+            Stmt::While { cond, body } => {
+                self.expr(cond);
+                self.block(body);
+            }
+            Stmt::Repeat { body, cond } => {
+                let outer = self.scope.len();
+                self.stmts(body);
+                self.expr(cond);
+                self.scope.truncate(outer);
+            }
+            Stmt::If { arms, else_block } => {
+                for (cond, body) in arms {
+                    self.expr(cond);
+                    self.block(body);
+                }
+                if let Some(body) = else_block {
+                    self.block(body);
+                }
+            }
+            Stmt::NumericFor { var, start, end, step, body } => {
+                [start, end].into_iter().chain(step).for_each(|expr| self.expr(expr));
+                self.scoped(std::slice::from_ref(var), body);
+            }
+            Stmt::GenericFor { names, exprs, body } => {
+                exprs.iter().for_each(|expr| self.expr(expr));
+                self.scoped(names, body);
+            }
+            Stmt::Function { params, body, .. } => self.scoped(params, body),
+            Stmt::LocalFunction { name, params, body, .. } => {
+                // The name is in scope in its own body, so the function can recurse.
+                self.declare(std::slice::from_ref(name));
+                self.scoped(params, body);
+            }
+            Stmt::Return(values) => values.iter().for_each(|expr| self.expr(expr)),
+            Stmt::Break | Stmt::Goto(_) | Stmt::Label(_) => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        match &expr.node {
+            Expr::Name(name) => {
+                let local = self.scope.iter().rev().find(|(local, _)| local == name);
+                let binding = local.map_or(Binding::Global, |&(_, span)| Binding::Local(span));
+                self.bindings.insert(expr.span, binding);
+            }
+            Expr::Function { params, body, .. } => self.scoped(params, body),
+            _ => for_each_child_expr(expr, |child| self.expr(child)),
+        }
+    }
+
+    /// Resolve `body` with `names`, parameters or loop variables, in scope.
+    fn scoped(&mut self, names: &[Spanned<String>], body: &Block) {
+        let outer = self.scope.len();
+        self.declare(names);
+        self.block(body);
+        self.scope.truncate(outer);
+    }
+
+    fn declare(&mut self, names: &[Spanned<String>]) {
+        self.scope.extend(names.iter().map(|name| (name.node.clone(), name.span)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn resolve_repeat_until_sees_body_locals() {
+        let source = "repeat local x = f() until x\nreturn x";
+        let bindings = resolve_names(&parse(source).unwrap());
+        assert_eq!(bindings[&Span::new(27, 28)], Binding::Local(Span::new(13, 14)));
+        assert_eq!(bindings[&Span::new(36, 37)], Binding::Global);
+        assert_eq!(bindings[&Span::new(17, 18)], Binding::Global);
+        // A `while` condition comes before its body, so can't see its locals.
+        let bindings = resolve_names(&parse("while x do local x = 1 end").unwrap());
+        assert_eq!(bindings[&Span::new(6, 7)], Binding::Global);
+    }
+    #[test]
+    fn resolve_shadowing_and_params() {
+        let source = "local x = 1 local x = x function f(x) return x end for x = x, 2 do g(x) end";
+        let bindings = resolve_names(&parse(source).unwrap());
+        // The second `local x` is only in scope after its statement.
+        assert_eq!(bindings[&Span::new(22, 23)], Binding::Local(Span::new(6, 7)));
+        assert_eq!(bindings[&Span::new(45, 46)], Binding::Local(Span::new(35, 36)));
+        assert_eq!(bindings[&Span::new(59, 60)], Binding::Local(Span::new(18, 19)));
+        assert_eq!(bindings[&Span::new(69, 70)], Binding::Local(Span::new(55, 56)));
+    }
+    #[test]
+    fn resolve_local_function_recursion() {
+        let source = "local function f(n) return f(n) end return f";
+        let bindings = resolve_names(&parse(source).unwrap());
+        assert_eq!(bindings[&Span::new(27, 28)], Binding::Local(Span::new(15, 16)));
+        assert_eq!(bindings[&Span::new(43, 44)], Binding::Local(Span::new(15, 16)));
+        // Unlike `local function`, a `local` isn't in scope in its own value.
+        let bindings = resolve_names(&parse("local g = function() return g end").unwrap());
+        assert_eq!(bindings[&Span::new(28, 29)], Binding::Global);
+    }
+}
@@ -0,0 +1,59 @@
+use std::cell::OnceCell;
+
+use crate::lex::{tokenize, LexError, LuaToken};
+use crate::span::{slice_source, LineIndex, Span};
+
+/// A Lua source owned together with what tooling derives from it.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    source: String,
+    /// Built on the first position lookup, as most files never need one.
+    lines: OnceCell<LineIndex>,
+}
+
+impl SourceFile {
+    pub fn new(source: impl Into<String>) -> Self {
+        SourceFile { source: source.into(), lines: OnceCell::new() }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        self.lines.get_or_init(|| LineIndex::new(&self.source))
+    }
+
+    /// The line and column of `offset`, see [`LineIndex::line_col`].
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        self.line_index().line_col(offset)
+    }
+
+    /// The source text under `span`, or `None` if it runs past the end or
+    /// splits a character.
+    pub fn slice(&self, span: Span) -> Option<&str> {
+        slice_source(&self.source, span)
+    }
+
+    /// Lex the whole source, stopping at the first error.
+    pub fn tokens(&self) -> Result<Vec<(LuaToken<'_>, Span)>, LexError> {
+        tokenize(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_file_queries() {
+        let file = SourceFile::new("local x = 1\nreturn x + 2");
+        let tokens = file.tokens().unwrap();
+        let (token, span) = &tokens[5];
+        assert_eq!((token, file.slice(*span)), (&LuaToken::Identifier("x"), Some("x")));
+        assert_eq!(file.slice(Span::new(20, 30)), None);
+        assert_eq!(file.line_col(span.start), (2, 8));
+        assert_eq!(file.line_col(0), (1, 1));
+        assert_eq!(file.line_index().line_count(), 2);
+    }
+}
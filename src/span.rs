@@ -0,0 +1,148 @@
+use std::ops::Range;
+
+/// A half-open byte range `start..end` into the source.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// Zero-width span at `offset`, e.g. for end of input.
+    pub fn empty(offset: usize) -> Self {
+        Span { start: offset, end: offset }
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    pub fn merge(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Span { start: range.start, end: range.end }
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.start..span.end
+    }
+}
+
+/// The text of `source` under `span`, or `None` where the span runs past the
+/// end or either side falls inside a multi-byte character.
+pub fn slice_source(source: &str, span: Span) -> Option<&str> {
+    source.get(span.range())
+}
+
+/// A value paired with the span of source it came from.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Spanned { node, span }
+    }
+}
+
+/// The start offset of every line of a source, to turn offsets into positions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let breaks = source.match_indices('\n').map(|(i, _)| i + 1);
+        LineIndex { line_starts: std::iter::once(0).chain(breaks).collect() }
+    }
+
+    /// The line and column of `offset`, both counted from 1, the column in bytes.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        (line + 1, offset - self.line_starts[line] + 1)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn span_merge_adjacent() {
+        let a = Span::new(0, 3);
+        let b = Span::new(3, 7);
+        assert_eq!(a.merge(b), Span::new(0, 7));
+        assert_eq!(b.merge(a), Span::new(0, 7));
+        assert_eq!(a.merge(b).len(), 7);
+    }
+    #[test]
+    fn span_contains() {
+        let span = Span::new(2, 5);
+        assert!(!span.contains(1));
+        assert!(span.contains(2));
+        assert!(span.contains(4));
+        assert!(!span.contains(5));
+    }
+    #[test]
+    fn span_line_index() {
+        let index = LineIndex::new("a\nbc\n\nd");
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line_col(0), (1, 1));
+        assert_eq!(index.line_col(1), (1, 2));
+        assert_eq!(index.line_col(2), (2, 1));
+        assert_eq!(index.line_col(5), (3, 1));
+        assert_eq!(index.line_col(7), (4, 2));
+    }
+    #[test]
+    fn span_slice_source() {
+        let source = "s = 'hé'";
+        assert_eq!(slice_source(source, Span::new(4, 9)), Some("'hé'"));
+        assert_eq!(slice_source(source, Span::new(4, 7)), None);
+        assert_eq!(slice_source(source, Span::new(7, 9)), None);
+        assert_eq!(slice_source(source, Span::new(9, 10)), None);
+        assert_eq!(slice_source(source, Span::empty(9)), Some(""));
+    }
+    #[test]
+    fn span_eof_is_empty() {
+        let eof = Span::empty(10);
+        assert!(eof.is_empty());
+        assert_eq!(eof.len(), 0);
+        assert!(!eof.contains(10));
+        assert!(!Span::new(9, 10).is_empty());
+    }
+}
@@ -0,0 +1,309 @@
+use crate::ast::{for_each_expr_mut, Block, Expr, Stmt};
+use crate::lex::{lexer_with_comments, Dialect, LexerBuilder, LuaToken};
+use crate::span::Spanned;
+
+/// Remove every comment from `source`, leaving all other text untouched.
+///
+/// A long comment between two tokens is replaced by a space so they don't merge.
+pub fn strip_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut last = 0;
+    for (token, range) in lexer_with_comments(source).spanned() {
+        if let Ok(LuaToken::Comment(_)) = token {
+            out.push_str(&source[last..range.start]);
+            let before = source[..range.start].chars().next_back();
+            let after = source[range.end..].chars().next();
+            if before.is_some_and(|c| !c.is_whitespace()) && after.is_some_and(|c| !c.is_whitespace()) {
+                out.push(' ');
+            }
+            last = range.end;
+        }
+    }
+    out.push_str(&source[last..]);
+    out
+}
+
+/// Shrink `source` by dropping comments and all whitespace between tokens but
+/// the single spaces needed to keep them apart, as in `local x` or `a - -b`.
+///
+/// Tokens are copied as written, so strings keep their contents. Text that
+/// fails to lex in `dialect` is copied byte for byte along with the whitespace
+/// around it, and a leading `#!` line is kept.
+pub fn minify(source: &str, dialect: Dialect) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut last = 0;
+    if source.starts_with("#!") {
+        let line = source.lines().next().unwrap_or_default();
+        out.push_str(line);
+        out.push('\n');
+        last = source.len().min(line.len() + 1);
+    }
+    let lexer = LexerBuilder::new().dialect(dialect);
+    let mut prev: Option<&str> = None;
+    let mut after_error = false;
+    for (token, range) in lexer.build(source).spanned() {
+        let text = &source[range.clone()];
+        if token.is_err() || after_error {
+            out.push_str(&source[last..range.start]);
+        } else if prev.is_some_and(|prev| merges(&lexer, prev, text)) {
+            out.push(' ');
+        }
+        out.push_str(text);
+        after_error = token.is_err();
+        prev = Some(text);
+        last = range.end;
+    }
+    if after_error {
+        out.push_str(&source[last..]);
+    }
+    out
+}
+
+/// Whether `prev` and `next` written together lex as anything but those two tokens.
+fn merges(lexer: &LexerBuilder, prev: &str, next: &str) -> bool {
+    let joined = format!("{prev}{next}");
+    let mut tokens = lexer.build(&joined).spanned();
+    let first = tokens.next().is_none_or(|(token, range)| token.is_err() || range.end != prev.len());
+    first || !matches!(tokens.next(), Some((Ok(_), _))) || tokens.next().is_some()
+}
+
+/// How [`normalize_numbers`] writes decimal fractions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NumberFormat {
+    /// Drop trailing zeros after the point, `1.500` becoming `1.5`. Off by
+    /// default, since the zeros may be there for the reader.
+    pub trim_fraction: bool,
+}
+
+/// Rewrite every number literal in `source` to a canonical spelling with the
+/// same value and subtype, leaving all other text untouched.
+///
+/// Hex literals get a lowercase `0x` and uppercase digits, leading zeros are
+/// dropped and exponents are written `e`, or `p` in hex, so `0XfF` becomes
+/// `0xFF`, `007` becomes `7` and `1E+05` becomes `1e+5`. A fraction always
+/// keeps a digit.
+pub fn normalize_numbers(source: &str, format: NumberFormat) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut last = 0;
+    for (token, range) in LexerBuilder::new().build(source).spanned() {
+        if let Ok(LuaToken::Integer(_) | LuaToken::Float(_)) = token {
+            out.push_str(&source[last..range.start]);
+            write_number(&mut out, &source[range.clone()], format);
+            last = range.end;
+        }
+    }
+    out.push_str(&source[last..]);
+    out
+}
+
+fn write_number(out: &mut String, text: &str, format: NumberFormat) {
+    let (hex, digits) = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let letters = if hex { ['p', 'P'] } else { ['e', 'E'] };
+    let (mantissa, exponent) = match digits.find(letters) {
+        Some(e) => (&digits[..e], Some(&digits[e + 1..])),
+        None => (digits, None),
+    };
+    let (int, fraction) = match mantissa.split_once('.') {
+        Some((int, fraction)) => (int, Some(fraction)),
+        None => (mantissa, None),
+    };
+    if hex {
+        out.push_str("0x");
+    }
+    let push_digits = |out: &mut String, digits: &str| match hex {
+        true => out.push_str(&digits.to_ascii_uppercase()),
+        false => out.push_str(digits),
+    };
+    push_digits(out, strip_leading_zeros(int));
+    if let Some(fraction) = fraction {
+        out.push('.');
+        push_digits(out, if format.trim_fraction { strip_trailing_zeros(fraction) } else { fraction });
+    }
+    if let Some(exponent) = exponent {
+        out.push(letters[0]);
+        let digits = exponent.trim_start_matches(['+', '-']);
+        out.push_str(&exponent[..exponent.len() - digits.len()]);
+        out.push_str(strip_leading_zeros(digits));
+    }
+}
+
+/// `digits` without leading zeros, keeping one where nothing else would be left
+/// or a `_` separator would come first.
+fn strip_leading_zeros(digits: &str) -> &str {
+    let rest = digits.trim_start_matches('0');
+    match rest.is_empty() || rest.starts_with('_') {
+        true => &digits[digits.len() - rest.len() - 1..],
+        false => rest,
+    }
+}
+
+/// Rename every identifier `from` in `source` to `to`, leaving strings,
+/// comments and all other text untouched.
+///
+/// This works on tokens alone, so field names such as the `x` of `t.x` or
+/// `{x = 1}` are renamed as well, and scoping is not considered.
+pub fn rename_identifier(source: &str, from: &str, to: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut last = 0;
+    for (token, range) in LexerBuilder::new().build(source).spanned() {
+        if let Ok(LuaToken::Identifier(name)) = token
+            && name == from
+        {
+            out.push_str(&source[last..range.start]);
+            out.push_str(to);
+            last = range.end;
+        }
+    }
+    out.push_str(&source[last..]);
+    out
+}
+
+fn strip_trailing_zeros(digits: &str) -> &str {
+    let rest = digits.trim_end_matches('0');
+    match rest.is_empty() || rest.ends_with('_') {
+        true => &digits[..rest.len() + 1],
+        false => rest,
+    }
+}
+
+/// Rewrite every `t.name` in `block` to the equivalent `t["name"]`, so analyses
+/// only see one form of constant-key indexing.
+///
+/// The key takes the span of the name. Parsing leaves the sugared form, so
+/// callers wanting the raw tree simply skip this pass.
+pub fn normalize_fields(block: &mut Block) {
+    for_each_expr_mut(block, &mut |expr| {
+        if !matches!(expr.node, Expr::Field { .. }) {
+            return;
+        }
+        let Expr::Field { obj, name } = std::mem::replace(&mut expr.node, Expr::Nil) else { unreachable!() };
+        let key = Spanned::new(Expr::String(name.node.into_bytes()), name.span);
+        expr.node = Expr::Index { obj, key: Box::new(key) };
+    });
+}
+
+/// Rewrite every Luau `target op= value` in `block` to `target = target op value`.
+///
+/// Luau evaluates `target` once, so this changes how often a call in it runs,
+/// e.g. the `f()` of `t[f()] += 1`.
+pub fn desugar_compound_assign(block: &mut Block) {
+    desugar_stmts(block);
+    for_each_expr_mut(block, &mut |expr| {
+        if let Expr::Function { body, .. } = &mut expr.node {
+            desugar_stmts(body);
+        }
+    });
+}
+
+/// Desugar the compound assignments of `block` and its nested statement blocks.
+fn desugar_stmts(block: &mut Block) {
+    for stmt in &mut block.stmts {
+        match &mut stmt.node {
+            Stmt::CompoundAssign { .. } => {
+                let Stmt::CompoundAssign { target, op, value } = std::mem::replace(&mut stmt.node, Stmt::Break) else {
+                    unreachable!()
+                };
+                let span = target.span.merge(value.span);
+                let lhs = Box::new(target.clone());
+                let value = Spanned::new(Expr::Binary { op, lhs, rhs: Box::new(value) }, span);
+                stmt.node = Stmt::Assign { targets: vec![target], values: vec![value] };
+            }
+            Stmt::Do(body)
+            | Stmt::While { body, .. }
+            | Stmt::Repeat { body, .. }
+            | Stmt::NumericFor { body, .. }
+            | Stmt::GenericFor { body, .. }
+            | Stmt::Function { body, .. }
+            | Stmt::LocalFunction { body, .. } => desugar_stmts(body),
+            Stmt::If { arms, else_block } => {
+                arms.iter_mut().for_each(|(_, body)| desugar_stmts(body));
+                if let Some(body) = else_block {
+                    desugar_stmts(body);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ast_diff;
+    use crate::parse::{parse, parse_dialect};
+
+    #[test]
+    fn strip_trailing_comment() {
+        assert_eq!(strip_comments("x = \"-- not a comment\" -- real comment"), "x = \"-- not a comment\" ");
+    }
+    #[test]
+    fn strip_long_comment() {
+        assert_eq!(strip_comments("a--[[ c ]]b\n--[==[\n]==]\nc"), "a b\n\nc");
+        assert_eq!(strip_comments("x = 'a--b' --[[ ]]"), "x = 'a--b' ");
+    }
+    #[test]
+    fn minify_whitespace() {
+        assert_eq!(minify("local  x = 1 -- c", Dialect::Lua54), "local x=1");
+        assert_eq!(minify("if a and b then\n  return f ( 'a  b' )\nend", Dialect::Lua54), "if a and b then return f('a  b')end");
+        assert_eq!(minify("x = a - -b .. 1 .. c [ [[s]] ]", Dialect::Lua54), "x=a- -b..1 ..c[ [[s]]]");
+        assert_eq!(minify("a = b == c --[[ x ]] y = 1.5e3 >= 2", Dialect::Lua54), "a=b==c y=1.5e3>=2");
+        assert_eq!(minify("#!/usr/bin/lua\nprint(1)\n", Dialect::Lua54), "#!/usr/bin/lua\nprint(1)");
+        let source = "local t = { 1, 2; x = 'y' } function t:m(...) return ... end";
+        let minified = minify(source, Dialect::Lua54);
+        assert_eq!(minified, "local t={1,2;x='y'}function t:m(...)return...end");
+        assert_eq!(ast_diff(&parse(source).unwrap(), &parse(&minified).unwrap()), None);
+    }
+    #[test]
+    fn minify_luau() {
+        let source = "x = `a{b}c`\ny = `a\\{b}` .. `{ {1} }`\nz  +=  1";
+        let minified = minify(source, Dialect::Luau);
+        assert_eq!(minified, "x=`a{b}c`y=`a\\{b}`..`{ {1} }`z+=1");
+        let ast = |source| parse_dialect(source, Dialect::Luau).unwrap();
+        assert_eq!(ast_diff(&ast(source), &ast(&minified)), None);
+    }
+    #[test]
+    fn minify_keeps_errors() {
+        assert_eq!(minify("x = `a{b}c`", Dialect::Lua54), "x= `a{b}c`");
+        assert_eq!(minify("a  = 1 $ b  = 2", Dialect::Lua54), "a=1 $ b=2");
+        assert_eq!(minify("x = 3abc  y", Dialect::Lua54), "x= 3abc  y");
+    }
+    #[test]
+    fn normalize_numbers_canonical() {
+        let source = "x = 0XfF + 007 * 0x00a_b - 1E+05 .. 00.0100 -- 0X1 stays";
+        let preserved = normalize_numbers(source, NumberFormat::default());
+        assert_eq!(preserved, "x = 0xFF + 7 * 0xA_B - 1e+5 .. 0.0100 -- 0X1 stays");
+        let trimmed = normalize_numbers(source, NumberFormat { trim_fraction: true });
+        assert_eq!(trimmed, "x = 0xFF + 7 * 0xA_B - 1e+5 .. 0.01 -- 0X1 stays");
+        assert_eq!(normalize_numbers("1.500 1.000 0_1 0 2e-007", NumberFormat { trim_fraction: true }), "1.5 1.0 0_1 0 2e-7");
+        assert_eq!(normalize_numbers("s = '007' .. 1.500", NumberFormat::default()), "s = '007' .. 1.500");
+        let hex = "0x1.8p10 0x0p4 0x10P-2 0x0.a0p+04";
+        assert_eq!(normalize_numbers(hex, NumberFormat::default()), "0x1.8p10 0x0p4 0x10p-2 0x0.A0p+4");
+        assert_eq!(normalize_numbers(hex, NumberFormat { trim_fraction: true }), "0x1.8p10 0x0p4 0x10p-2 0x0.Ap+4");
+    }
+    #[test]
+    fn rename_identifier_only() {
+        assert_eq!(rename_identifier("local x = \"x\" -- x", "x", "y"), "local y = \"x\" -- x");
+        assert_eq!(rename_identifier("xx = x + [[x]] --[[x]] x2(x)", "x", "value"), "xx = value + [[x]] --[[x]] x2(value)");
+    }
+    #[test]
+    fn normalize_field_to_index() {
+        let mut sugared = parse("return a.b, a.b.c(x.y)").unwrap();
+        normalize_fields(&mut sugared);
+        let indexed = parse("return a[\"b\"], a[\"b\"][\"c\"](x[\"y\"])").unwrap();
+        assert_eq!(ast_diff(&sugared, &indexed), None);
+        let crate::ast::Stmt::Return(values) = &sugared.stmts[0].node else { panic!() };
+        let Expr::Index { key, .. } = &values[0].node else { panic!("{values:?}") };
+        assert_eq!(key.span, crate::span::Span::new(9, 10));
+    }
+    #[test]
+    fn desugar_compound() {
+        let luau = |source| crate::parse::parse_dialect(source, crate::lex::Dialect::Luau).unwrap();
+        let mut block = luau("x += 1\nif a then t.n ..= 'a' .. b end\nf(function() y -= a ^ b end)");
+        desugar_compound_assign(&mut block);
+        let plain = parse("x = x + 1\nif a then t.n = t.n .. 'a' .. b end\nf(function() y = y - a ^ b end)").unwrap();
+        assert_eq!(ast_diff(&block, &plain), None);
+    }
+}
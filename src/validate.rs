@@ -0,0 +1,268 @@
+use crate::ast::{
+    adjustments, for_each_child_block, for_each_function_body, for_each_stmt, for_each_stmt_expr, for_each_subexpr, Adjust,
+    BinOp, Block, Expr, Stmt,
+};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::span::Spanned;
+
+/// Check every `goto` in `block`, a whole chunk, against Lua's label rules.
+///
+/// A `goto` must name a label in its own block or an enclosing one of the same
+/// function, and can't jump forward past a `local` into its scope, unless the
+/// label ends its block.
+pub fn validate_gotos(block: &Block) -> Vec<Diagnostic> {
+    let mut checker = GotoChecker::default();
+    checker.function(block);
+    checker.diagnostics
+}
+
+/// Report every `break` that isn't inside a `while`, `repeat` or `for` loop
+/// of its own function.
+pub fn validate_breaks(block: &Block) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    check_breaks(block, false, &mut diagnostics);
+    diagnostics
+}
+
+fn check_breaks(block: &Block, in_loop: bool, diagnostics: &mut Vec<Diagnostic>) {
+    for stmt in &block.stmts {
+        let in_loop = match stmt.node {
+            Stmt::Break if !in_loop => {
+                diagnostics.push(Diagnostic::new("break outside a loop", stmt.span));
+                continue;
+            }
+            Stmt::While { .. } | Stmt::Repeat { .. } | Stmt::NumericFor { .. } | Stmt::GenericFor { .. } => true,
+            // A function body can't break out of the loop it is written in.
+            Stmt::Function { .. } | Stmt::LocalFunction { .. } => false,
+            _ => in_loop,
+        };
+        let mut function = |body| check_breaks(body, false, diagnostics);
+        for_each_stmt_expr(&stmt.node, |expr| for_each_function_body(expr, &mut function));
+        for_each_child_block(&stmt.node, |body| check_breaks(body, in_loop, diagnostics));
+    }
+}
+
+/// Flag every assignment and `local` whose value count differs from its
+/// target count, unless a multi-valued last value makes up the difference.
+///
+/// Lua allows these, padding with `nil` or dropping extra values, but they are
+/// often a mistake, so these are warnings. A `local` with no values at all is
+/// left alone.
+pub fn lint_assign_counts(block: &Block) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for_each_stmt(block, &mut |stmt| {
+        let (targets, values) = match &stmt.node {
+            Stmt::Assign { targets, values } => (targets.len(), values),
+            Stmt::Local { names, values, .. } if !values.is_empty() => (names.len(), values),
+            _ => return,
+        };
+        let expands = adjustments(values).last() == Some(&Adjust::MultiTail);
+        if values.len() > targets || (values.len() < targets && !expands) {
+            let message = format!("{targets} target(s) but {} value(s)", values.len());
+            diagnostics.push(Diagnostic::new(message, stmt.span).with_severity(Severity::Warning).with_code("assign-count"));
+        }
+    });
+    diagnostics
+}
+
+/// Flag every comparison whose left operand is an unparenthesized comparison,
+/// like `a < b < c`.
+///
+/// Lua reads that as `(a < b) < c`, comparing a boolean with `c`, which fails
+/// for `<` and is rarely meant for `==`. The span is the outer comparison's.
+pub fn lint_chained_comparison(block: &Block) -> Vec<Diagnostic> {
+    let is_comparison = |expr: &Expr| {
+        matches!(
+            expr,
+            Expr::Binary {
+                op: BinOp::Less | BinOp::Greater | BinOp::LessEqual | BinOp::GreaterEqual | BinOp::Equal | BinOp::NotEqual,
+                ..
+            }
+        )
+    };
+    let mut diagnostics = Vec::new();
+    for_each_stmt(block, &mut |stmt| {
+        for_each_stmt_expr(&stmt.node, |expr| {
+            for_each_subexpr(expr, &mut |expr| {
+                if let Expr::Binary { op, lhs, .. } = &expr.node
+                    && is_comparison(&expr.node)
+                    && is_comparison(&lhs.node)
+                {
+                    let message = format!(
+                        "chained comparison: '{}' compares the boolean result of the comparison before it, use 'and' to test both",
+                        op.as_str()
+                    );
+                    diagnostics.push(
+                        Diagnostic::new(message, expr.span).with_severity(Severity::Warning).with_code("chained-comparison"),
+                    );
+                }
+            });
+        });
+    });
+    diagnostics
+}
+
+#[derive(Default)]
+struct GotoChecker<'ast> {
+    /// Blocks enclosing the current statement in its function, outermost first,
+    /// each with the index of the statement being checked in it.
+    scopes: Vec<(&'ast Block, usize)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'ast> GotoChecker<'ast> {
+    /// Check a function body, which can't see the labels around it.
+    fn function(&mut self, body: &'ast Block) {
+        let outer = std::mem::take(&mut self.scopes);
+        self.block(body);
+        self.scopes = outer;
+    }
+
+    fn block(&mut self, block: &'ast Block) {
+        self.scopes.push((block, 0));
+        for (i, stmt) in block.stmts.iter().enumerate() {
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.1 = i;
+            }
+            self.stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn stmt(&mut self, stmt: &'ast Spanned<Stmt>) {
+        match &stmt.node {
+            Stmt::Goto(label) => self.goto(label),
+            Stmt::Assign { targets, values } => {
+                targets.iter().for_each(|target| self.expr(target));
+                values.iter().for_each(|value| self.expr(value));
+            }
+            Stmt::CompoundAssign { target, value, .. } => {
+                self.expr(target);
+                self.expr(value);
+            }
+            Stmt::Call(call) => self.expr(call),
+            Stmt::Local { values, .. } | Stmt::Return(values) => values.iter().for_each(|value| self.expr(value)),
+            Stmt::Do(body) => self.block(body),
+            Stmt::While { cond, body } | Stmt::Repeat { body, cond } => {
+                self.expr(cond);
+                self.block(body);
+            }
+            Stmt::If { arms, else_block } => {
+                for (cond, body) in arms {
+                    self.expr(cond);
+                    self.block(body);
+                }
+                if let Some(body) = else_block {
+                    self.block(body);
+                }
+            }
+            Stmt::NumericFor { start, end, step, body, .. } => {
+                self.expr(start);
+                self.expr(end);
+                if let Some(step) = step {
+                    self.expr(step);
+                }
+                self.block(body);
+            }
+            Stmt::GenericFor { exprs, body, .. } => {
+                exprs.iter().for_each(|expr| self.expr(expr));
+                self.block(body);
+            }
+            Stmt::Function { body, .. } | Stmt::LocalFunction { body, .. } => self.function(body),
+            Stmt::Break | Stmt::Label(_) => {}
+        }
+    }
+
+    /// Look for function bodies, whose gotos are checked on their own.
+    fn expr(&mut self, expr: &'ast Spanned<Expr>) {
+        for_each_function_body(expr, &mut |body| self.function(body));
+    }
+
+    fn goto(&mut self, label: &Spanned<String>) {
+        for &(block, at) in self.scopes.iter().rev() {
+            let is_target = |stmt: &Spanned<Stmt>| matches!(&stmt.node, Stmt::Label(name) if name.node == label.node);
+            let Some(target) = block.stmts.iter().position(is_target) else {
+                continue;
+            };
+            // Only labels follow a label that ends its block, so no local is in scope there.
+            let at_end = block.stmts[target..].iter().all(|stmt| matches!(stmt.node, Stmt::Label(_)));
+            if target > at && !at_end {
+                let local = block.stmts[at + 1..target].iter().find_map(|stmt| match &stmt.node {
+                    Stmt::Local { names, .. } => names.first(),
+                    Stmt::LocalFunction { name, .. } => Some(name),
+                    _ => None,
+                });
+                if let Some(local) = local {
+                    let message = format!("goto '{}' jumps into the scope of local '{}'", label.node, local.node);
+                    self.diagnostics.push(Diagnostic::new(message, label.span));
+                }
+            }
+            return;
+        }
+        self.diagnostics.push(Diagnostic::new(format!("no visible label '{}' for goto", label.node), label.span));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+    use crate::span::Span;
+
+    fn validate(source: &str) -> Vec<Diagnostic> {
+        validate_gotos(&parse(source).unwrap())
+    }
+
+    #[test]
+    fn validate_assign_counts() {
+        let lint = |source: &str| lint_assign_counts(&parse(source).unwrap());
+        assert_eq!(lint("a, b = b, a\nlocal x, y = f()\nlocal z\nlocal u, v = 1, ..."), []);
+        assert_eq!(lint("a, b = 1"), [Diagnostic::new("2 target(s) but 1 value(s)", Span::new(0, 8)).with_severity(Severity::Warning).with_code("assign-count")]);
+        assert_eq!(lint("local f = function() local x = 1, 2 end").len(), 1);
+        assert_eq!(lint("a, b = (f())").len(), 1);
+    }
+    #[test]
+    fn validate_chained_comparison() {
+        let lint = |source: &str| lint_chained_comparison(&parse(source).unwrap());
+        let message = "chained comparison: '<' compares the boolean result of the comparison before it, use 'and' to test both";
+        let expected = Diagnostic::new(message, Span::new(7, 16)).with_severity(Severity::Warning).with_code("chained-comparison");
+        assert_eq!(lint("return a < b < c"), [expected]);
+        assert_eq!(lint("return a < b and b < c"), []);
+        assert_eq!(lint("return (a == b) == c, a + b < c"), []);
+        assert_eq!(lint("f(function() if x <= y == z then end end)").len(), 1);
+        assert_eq!(lint("return a < b < c < d").len(), 2);
+    }
+    #[test]
+    fn validate_break_placement() {
+        let breaks = |source: &str| validate_breaks(&parse(source).unwrap());
+        assert_eq!(breaks("while x do if y then break end end"), []);
+        assert_eq!(breaks("for i = 1, 2 do do break end end repeat break until x"), []);
+        assert_eq!(breaks("x = 1 break"), [Diagnostic::new("break outside a loop", Span::new(6, 11))]);
+        assert_eq!(breaks("if x then break end").len(), 1);
+        // Neither function form can break out of the loop around it.
+        assert_eq!(breaks("while x do f(function() break end) end").len(), 1);
+        assert_eq!(breaks("while x do function g() break end end").len(), 1);
+        assert_eq!(breaks("local f = function() while x do break end end"), []);
+    }
+    #[test]
+    fn validate_goto_ok() {
+        assert_eq!(validate("goto done\nprint(1)\n::done::"), []);
+        assert_eq!(validate("while x do if y then goto next end local z = 1 ::next:: end"), []);
+        assert_eq!(validate("::top:: local a = 1 do goto top end"), []);
+    }
+    #[test]
+    fn validate_goto_undefined_label() {
+        assert_eq!(validate("goto nowhere"), [Diagnostic::new("no visible label 'nowhere' for goto", Span::new(5, 12))]);
+        // Labels in nested blocks and enclosing functions are out of sight.
+        assert_eq!(validate("do ::inner:: end goto inner").len(), 1);
+        assert_eq!(validate("::outer:: local f = function() goto outer end").len(), 1);
+    }
+    #[test]
+    fn validate_goto_into_local_scope() {
+        let source = "do goto skip local x = 1 ::skip:: print(x) end";
+        assert_eq!(
+            validate(source),
+            [Diagnostic::new("goto 'skip' jumps into the scope of local 'x'", Span::new(8, 12))]
+        );
+    }
+}